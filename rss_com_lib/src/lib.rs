@@ -19,6 +19,57 @@ impl Url {
         Self(url)
     }
 
+    /// Checks that the input is an `http` or `https` url, and normalizes it so that the same
+    /// url written in slightly different ways results in the same [Url]:
+    /// - Surrounding whitespace is removed.
+    /// - The scheme and host are lowercased.
+    /// - The fragment (`#...`) is removed.
+    /// - A trailing slash at the end of the path is removed.
+    pub fn parse_and_normalize(input: &str) -> Result<Self, UrlError> {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return Err(UrlError::Empty);
+        }
+
+        // The fragment is only used by the browser, it is never sent to the server.
+        let without_fragment = trimmed.split('#').next().unwrap_or_default();
+
+        let (scheme, rest) = match without_fragment.split_once("://") {
+            Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+            None => {
+                return Err(match without_fragment.split_once(':') {
+                    Some((scheme, _)) => UrlError::UnsupportedScheme(scheme.to_lowercase()),
+                    None => UrlError::MissingScheme,
+                })
+            }
+        };
+
+        if scheme != "http" && scheme != "https" {
+            return Err(UrlError::UnsupportedScheme(scheme));
+        }
+
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path_and_query) = rest.split_at(authority_end);
+
+        // Only the host is case-insensitive, any user info is left as-is.
+        let authority = match authority.rsplit_once('@') {
+            Some((user_info, host)) => format!("{}@{}", user_info, host.to_lowercase()),
+            None => authority.to_lowercase(),
+        };
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        if host.is_empty() || host.starts_with(':') {
+            return Err(UrlError::MissingHost);
+        }
+
+        let (path, query) = match path_and_query.find('?') {
+            Some(query_start) => path_and_query.split_at(query_start),
+            None => (path_and_query, ""),
+        };
+        let path = path.trim_end_matches('/');
+
+        Ok(Self(format!("{}://{}{}{}", scheme, authority, path, query)))
+    }
+
     pub fn clone_string(&self) -> String {
         self.0.clone()
     }
@@ -26,6 +77,56 @@ impl Url {
     pub fn as_bytes(&self) -> &[u8] {
         self.0.as_bytes()
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The host of the url, including the port if there is one. Without any user info.
+    /// [None] if the url has no scheme.
+    pub fn host(&self) -> Option<&str> {
+        let (_, rest) = self.0.split_once("://")?;
+        let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let host = rest[..authority_end].rsplit('@').next()?;
+
+        if host.is_empty() {
+            None
+        } else {
+            Some(host)
+        }
+    }
+
+    /// Returns this url without the query parameters whose name matches one of the `patterns`.
+    /// A pattern that ends in `*` matches every name that starts with the rest of the pattern.
+    /// So `utm_*` matches both `utm_source` and `utm_campaign`.
+    pub fn without_query_params(&self, patterns: &[String]) -> Self {
+        let (without_fragment, fragment) = match self.0.find('#') {
+            Some(fragment_start) => self.0.split_at(fragment_start),
+            None => (self.0.as_str(), ""),
+        };
+        let Some((base, query)) = without_fragment.split_once('?') else {
+            return self.clone();
+        };
+
+        let kept_params: Vec<&str> = query
+            .split('&')
+            .filter(|param| {
+                let name = param.split('=').next().unwrap_or_default();
+                !patterns
+                    .iter()
+                    .any(|pattern| match pattern.strip_suffix('*') {
+                        Some(prefix) => name.starts_with(prefix),
+                        None => name == pattern,
+                    })
+            })
+            .collect();
+
+        if kept_params.is_empty() {
+            Self(format!("{}{}", base, fragment))
+        } else {
+            Self(format!("{}?{}{}", base, kept_params.join("&"), fragment))
+        }
+    }
 }
 
 impl Display for Url {
@@ -33,3 +134,152 @@ impl Display for Url {
         write!(f, "{}", self.0)
     }
 }
+
+/// Reasons why [Url::parse_and_normalize] can reject a url.
+#[derive(Debug, Eq, PartialEq)]
+pub enum UrlError {
+    Empty,
+    MissingScheme,
+    /// Only `http` and `https` urls are allowed.
+    UnsupportedScheme(String),
+    MissingHost,
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::Empty => write!(f, "The url is empty"),
+            UrlError::MissingScheme => {
+                write!(f, "The url should start with `http://` or `https://`")
+            }
+            UrlError::UnsupportedScheme(scheme) => write!(
+                f,
+                "Unsupported url scheme `{}`, only `http` and `https` are allowed",
+                scheme
+            ),
+            UrlError::MissingHost => write!(f, "The url does not contain a host"),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Url, UrlError};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_and_normalize_url() {
+        let cases = [
+            (
+                "https://example.com/feed.xml",
+                "https://example.com/feed.xml",
+            ),
+            (
+                "  https://example.com/feed.xml\n",
+                "https://example.com/feed.xml",
+            ),
+            (
+                "HTTPS://Example.COM/Feed.xml",
+                "https://example.com/Feed.xml",
+            ),
+            ("https://example.com/feed/", "https://example.com/feed"),
+            ("https://example.com/", "https://example.com"),
+            ("https://example.com", "https://example.com"),
+            ("http://example.com/feed#top", "http://example.com/feed"),
+            (
+                "https://example.com/feed/?page=1",
+                "https://example.com/feed?page=1",
+            ),
+            (
+                "https://User@Example.com:8080/feed",
+                "https://User@example.com:8080/feed",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                Url::parse_and_normalize(input),
+                Ok(Url::new(expected.to_string())),
+                "input: `{}`",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_and_normalize_rejects_invalid_urls() {
+        let cases = [
+            ("", UrlError::Empty),
+            ("   ", UrlError::Empty),
+            ("example.com/feed", UrlError::MissingScheme),
+            (
+                "file:///etc/passwd",
+                UrlError::UnsupportedScheme("file".to_string()),
+            ),
+            (
+                "javascript:alert(1)",
+                UrlError::UnsupportedScheme("javascript".to_string()),
+            ),
+            ("https:///feed", UrlError::MissingHost),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                Url::parse_and_normalize(input),
+                Err(expected),
+                "input: `{}`",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_host() {
+        let cases = [
+            ("https://example.com/feed.xml", Some("example.com")),
+            ("https://example.com", Some("example.com")),
+            ("http://example.com:8080?page=2", Some("example.com:8080")),
+            ("https://user@example.com/feed", Some("example.com")),
+            ("example.com/feed", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(Url::new(input.to_string()).host(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_without_query_params() {
+        let patterns = ["utm_*".to_string(), "fbclid".to_string()];
+        let cases = [
+            ("https://example.com/post", "https://example.com/post"),
+            (
+                "https://example.com/post?utm_source=rss&utm_medium=feed",
+                "https://example.com/post",
+            ),
+            (
+                "https://example.com/post?id=3&utm_source=rss&page=2",
+                "https://example.com/post?id=3&page=2",
+            ),
+            (
+                "https://example.com/post?fbclid=abc#comments",
+                "https://example.com/post#comments",
+            ),
+            (
+                "https://example.com/post?fbclid_keep=1&xutm_source=2",
+                "https://example.com/post?fbclid_keep=1&xutm_source=2",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                Url::new(input.to_string()).without_query_params(&patterns),
+                Url::new(expected.to_string()),
+                "input: `{}`",
+                input
+            );
+        }
+    }
+}
@@ -2,9 +2,57 @@ use crate::rss_feed::{EntryKey, FeedEntry, FeedInfo};
 use crate::Url;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::{HashMap, HashSet};
 
+/// Version of the messages in this file. Increase it when a change means that clients built
+/// before it can no longer understand the server, or the other way around.
+/// The client compares it with the server's in [`VersionResponse`].
+pub const API_SCHEMA_VERSION: u32 = 1;
+
+/// Response for `/api/version`. Does not require the user to be logged in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionResponse {
+    /// Version of the server.
+    pub crate_version: String,
+    /// The server's [`API_SCHEMA_VERSION`].
+    pub api_schema_version: u32,
+}
+
+/// Identifies one of the feed collections of a user.
+/// Every user has a default collection. Other collections can be created with `/api/create_collection`.
+///
+/// Requests that don't mention a collection are for the default collection.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+pub struct CollectionId(pub u32);
+
+/// Name and id of a collection, as listed by `/api/collections`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CollectionInfo {
+    pub id: CollectionId,
+    pub name: String,
+}
+
+/// Response for `/api/collections`.
+/// Sorted by id, so the default collection is always the first.
+pub type CollectionsResponse = Vec<CollectionInfo>;
+
+/// Request for `/api/create_collection`.
+/// The response is the [`CollectionInfo`] of the new collection.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateCollectionRequest {
+    pub name: String,
+}
+
+/// Request and response for `/api/delete_collection`.
+/// The default collection can't be deleted.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteCollectionRequestAndResponse {
+    pub id: CollectionId,
+}
+
 /// Request format for `/api/is_url_an_rss_feed`
 #[derive(Serialize, Deserialize, Debug)]
 pub struct IsUrlAnRssFeedRequest {
@@ -17,24 +65,74 @@ pub struct IsUrlAnRssFeedResponse {
     pub requested_url: Url,
     /// Name of the feed, or the error message if there is no feed.
     pub result: Result<String, String>,
+    /// The newest entries of the feed, newest first, so the user can see what they would get
+    /// before adding it. Empty if there is no feed.
+    #[serde(default)]
+    pub preview: Vec<PreviewEntry>,
+    /// The categories the feed gives itself, which the user can choose to use as tags.
+    #[serde(default)]
+    pub suggested_tags: Vec<String>,
+}
+
+/// An entry of a feed that is not added yet. See [IsUrlAnRssFeedResponse].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreviewEntry {
+    pub title: String,
+    pub link: Option<Url>,
+    pub pub_date: DateTime<Utc>,
 }
 
 /// Request format for `/api/add_feed`
 /// The response is an Ok with an empty body, if the adding worked.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddFeedRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
     pub url: Url,
     pub tags: HashSet<String>,
 }
 
+/// Response for `/api/add_feeds`, which takes a list of [`AddFeedRequest`]s.
+/// Contains whether adding succeeded for every requested url, in the same order as the request.
+pub type AddFeedsResponse = Vec<(Url, Result<(), String>)>;
+
+/// Request for `/api/import_url_list`. The response is an [`AddFeedsResponse`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportUrlListRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    /// One url per line. Blank lines and lines starting with `#` are skipped.
+    pub urls: String,
+    /// Given to every added feed.
+    #[serde(default)]
+    pub tags: HashSet<String>,
+}
+
 /// Request for `/api/feeds`
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FeedsRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
     /// What feeds to return.
     pub filter: FeedsFilter,
     pub entry_filter: EntryTypeFilter,
     /// How many entries to return.
+    /// With 0, no entries are returned, but the totals are. Together with
+    /// [`AdditionalAction::IncludeFeedsInfo`] this gets only the feeds info.
     pub amount: usize,
+    /// Only return entries that come after this position.
+    /// Use the [`FeedsResponse`] `.next_cursor` to request the next page of entries.
+    pub after: Option<EntryCursor>,
+    /// Return entries that are in multiple feeds only once.
+    /// A de-duplicated entry counts as read if any of its copies is read.
+    #[serde(default)]
+    pub dedupe: bool,
+    /// Only return entries published after this moment.
+    /// Entries without a publication date are never returned when this is set.
+    #[serde(default)]
+    pub published_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort_order: SortOrder,
     pub additional_action: AdditionalAction,
 }
 
@@ -50,13 +148,23 @@ pub enum FeedsFilter {
 pub enum EntryTypeFilter {
     All,
     UnreadOnly,
+    /// For looking back at what was read.
+    ReadOnly,
+    /// Only the entries on the "read later" list, whether they are read or not.
+    ReadLater,
 }
 
 impl EntryTypeFilter {
     pub fn apply(&self, entry: &FeedEntry) -> bool {
+        self.apply_to_state(entry.read, entry.read_later)
+    }
+
+    pub fn apply_to_state(&self, read: bool, read_later: bool) -> bool {
         match self {
             EntryTypeFilter::All => true,
-            EntryTypeFilter::UnreadOnly => !entry.read,
+            EntryTypeFilter::UnreadOnly => !read,
+            EntryTypeFilter::ReadOnly => read,
+            EntryTypeFilter::ReadLater => read_later,
         }
     }
 }
@@ -69,6 +177,8 @@ pub enum AdditionalAction {
     /// Update all the feeds, and send along an update of the feeds info.
     /// A request with this might take a while.
     UpdateFeeds,
+    /// Update only the given feed, and send along an update of the feeds info.
+    UpdateSingle(Url),
 }
 
 /// Response for `/api/feeds`
@@ -77,13 +187,193 @@ pub struct FeedsResponse {
     /// Requested feed entries, ordered by time. Contains maximum [`FeedsRequest`] `.amount` entries.
     pub feed_entries: Vec<ComFeedEntry>,
     /// How many items were available for the given request.
+    /// This includes the items before the requested [`FeedsRequest`] `.after` cursor.
+    /// Only for display, use `has_more` to know whether there is more to request.
     pub total_available: usize,
-    /// If the request included [`AdditionalAction::IncludeFeedsInfo`] or [`AdditionalAction::UpdateFeeds`],
+    /// Whether there are more entries after the `feed_entries`, to request with the `next_cursor`.
+    #[serde(default)]
+    pub has_more: bool,
+    /// Position of the last entry in `feed_entries`.
+    /// Is [`None`] if there are no more entries available after this response.
+    pub next_cursor: Option<EntryCursor>,
+    /// The [`FeedsRequest`] `.after` cursor this response is for.
+    /// If this is not [`None`], the `feed_entries` continue where the previous response stopped.
+    pub requested_after: Option<EntryCursor>,
+    /// If the request included [`AdditionalAction::IncludeFeedsInfo`], [`AdditionalAction::UpdateFeeds`] or [`AdditionalAction::UpdateSingle`],
     /// this will be filled in. Otherwise it will be [`None`].
     pub feeds_info: Option<HashMap<Url, FeedInfo>>,
+    /// Amount of entries over all feeds of the collection, regardless of the [`FeedsRequest`] filters.
+    /// Entries that are in multiple feeds are counted once.
+    /// Only filled in together with `feeds_info`.
+    pub total_entries: Option<usize>,
+    /// Amount of unread entries over all feeds of the collection, regardless of the [`FeedsRequest`] filters.
+    pub total_unread: usize,
+}
+
+/// Request for `/api/unread_total`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnreadTotalRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+}
+
+/// Response for `/api/unread_total`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnreadTotalResponse {
+    /// Amount of unread entries over all feeds of the collection.
+    pub total_unread: usize,
+}
+
+/// Request for `/api/export_feed`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportFeedRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub url: Url,
+}
+
+/// Response for `/api/export_feed`.
+/// All entries of the feed, both read and unread, ordered by time.
+pub type ExportFeedResponse = Vec<ComFeedEntry>;
+
+/// Request for `/api/import_feed`.
+/// The `entries` can be the [`ExportFeedResponse`] of an earlier export, other fields of the
+/// entries are ignored.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportFeedRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub url: Url,
+    pub entries: Vec<ImportedEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedEntry {
+    pub key: EntryKey,
+    pub read: bool,
 }
 
+/// Response for `/api/import_feed`
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ImportFeedResponse {
+    /// Entries of which the read state was applied.
+    pub matched: usize,
+    /// Entries that don't exist in the feed (anymore). These are ignored.
+    pub unmatched: usize,
+}
+
+/// Request for `/api/merge_feeds`.
+/// For when the same feed was added twice under a slightly different url, for example with
+/// and without a trailing slash. The feed `merge` is removed, and its read state and
+/// "read later" list are moved to the same entries in `keep`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MergeFeedsRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub keep: Url,
+    pub merge: Url,
+}
+
+/// Response for `/api/merge_feeds`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MergeFeedsResponse {
+    /// Entries of `keep` that got their read or "read later" state from `merge`.
+    pub carried_over: usize,
+}
+
+/// Request for `/api/create_share_link`.
+/// The link shows the names and urls of the feeds that match the `filter`, without their entries.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateShareLinkRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub filter: FeedsFilter,
+    /// How many days the link keeps working. The server has a maximum.
+    pub valid_days: u32,
+}
+
+/// Response for `/api/create_share_link`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CreateShareLinkResponse {
+    /// Can be opened without logging in. Gives an OPML file.
+    pub url: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// Response for `/api/admin/users`.
+/// Sorted by id.
+pub type AdminUsersResponse = Vec<AdminUserInfo>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AdminUserInfo {
+    pub id: u32,
+    pub name: String,
+    pub is_admin: bool,
+    /// Amount of feeds over all collections of the user.
+    pub feeds: usize,
+}
+
+/// Request and response for `/api/admin/delete_user`.
+/// Deletes the user together with their feeds and settings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminDeleteUserRequestAndResponse {
+    pub id: u32,
+}
+
+/// Request for `/api/admin/backup`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AdminBackupRequest {
+    /// Whether the passwords of the users are in the backup. Without them, the backup can only
+    /// be restored on a server that already has the users.
+    #[serde(default)]
+    pub include_passwords: bool,
+}
+
+/// Response for `/api/admin/restore`. What the server has after restoring.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AdminRestoreResponse {
+    pub users: usize,
+    /// Amount of feeds over all users and collections.
+    pub feeds: usize,
+}
+
+/// Request for `/api/fetch_timings`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FetchTimingsRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+}
+
+/// Response for `/api/fetch_timings`.
+/// The last downloads of each feed in the collection, oldest first.
+/// Feeds that were not downloaded since the server started are left out.
+pub type FetchTimingsResponse = HashMap<Url, Vec<FetchTiming>>;
+
+/// How long downloading a feed took.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FetchTiming {
+    /// When the download finished.
+    pub finished: DateTime<Utc>,
+    pub duration_millis: u64,
+    /// Whether the download and parsing of the feed went well.
+    pub success: bool,
+}
+
+/// Response for `/api/update_status`.
+/// [None] if no "Update all feeds" is running for the user.
+pub type UpdateStatusResponse = Option<UpdateProgress>;
+
+/// How far along an update of all the feeds of a collection is.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateProgress {
+    /// Amount of feeds that are done, successfully or not.
+    pub completed: usize,
+    pub total: usize,
+    /// The feeds that failed so far in this update.
+    pub currently_failing: Vec<Url>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ComFeedEntry {
     /// Reference key to this specific entry.
     pub key: EntryKey,
@@ -94,22 +384,73 @@ pub struct ComFeedEntry {
     pub link: Option<Url>,
     /// If an rss feed includes an entry with no date, it will get a default date in the past.
     pub pub_date: DateTime<Utc>,
+    /// The date the entries are sorted by, which depends on the [`SortOrder`].
+    #[serde(default)]
+    pub sort_date: DateTime<Utc>,
     pub read: bool,
+    #[serde(default)]
+    pub read_later: bool,
+    /// All feeds this entry is in, sorted. With [`FeedsRequest`] `.dedupe` this can be multiple
+    /// feeds, otherwise it is only `feed_url`.
+    #[serde(default)]
+    pub sources: Vec<Url>,
+    /// Whether the title has one of the [FeedInfo::highlight_keywords] of the feed.
+    #[serde(default)]
+    pub highlighted: bool,
 }
 
 impl ComFeedEntry {
-    pub fn new(feed_url: Url, key: EntryKey, entry: &FeedEntry) -> Self {
+    pub fn new(
+        feed_url: Url,
+        key: EntryKey,
+        entry: &FeedEntry,
+        feed_info: &FeedInfo,
+        sort_order: SortOrder,
+    ) -> Self {
         Self {
             key,
+            sources: vec![feed_url.clone()],
             feed_url,
             title: entry.title.clone(),
             link: entry.link.clone(),
             pub_date: entry.pub_date,
+            sort_date: match sort_order {
+                SortOrder::Published => entry.pub_date,
+                SortOrder::LastActivity => entry.last_activity(),
+            },
             read: entry.read,
+            read_later: entry.read_later,
+            highlighted: feed_info.is_highlighted(&entry.title),
         }
     }
 }
 
+/// What [ComFeedEntry]s are sorted by, see [ComFeedEntry::sort_key].
+type SortKey<'a> = (
+    Reverse<DateTime<Utc>>,
+    Reverse<DateTime<Utc>>,
+    &'a str,
+    &'a EntryKey,
+    &'a Url,
+);
+
+impl ComFeedEntry {
+    /// The entries are sorted with the newest first. Entries that were edited at the same time
+    /// are sorted by when they were published, and then by title.
+    /// The key and feed only make sure that no two entries are in the same position, so paging
+    /// with an [EntryCursor] doesn't skip or repeat entries. Entries from different feeds can
+    /// have the same key.
+    fn sort_key(&self) -> SortKey<'_> {
+        (
+            Reverse(self.sort_date),
+            Reverse(self.pub_date),
+            &self.title,
+            &self.key,
+            &self.feed_url,
+        )
+    }
+}
+
 impl PartialOrd for ComFeedEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -118,54 +459,335 @@ impl PartialOrd for ComFeedEntry {
 
 impl Ord for ComFeedEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Normally one would compare self to other.
-        // However, the FeedEntries should be sorted with newest first,
-        // so for the date we compare the other with self.
-        let mut ord = other.pub_date.cmp(&self.pub_date);
-
-        if ord != Ordering::Equal {
-            return ord;
-        }
-
-        ord = self.title.cmp(&other.title);
-        if ord != Ordering::Equal {
-            return ord;
-        }
-
-        ord = self.link.cmp(&other.link);
-        if ord != Ordering::Equal {
-            return ord;
-        }
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
 
-        ord = self.read.cmp(&other.read);
-        if ord != Ordering::Equal {
-            return ord;
-        }
+/// Opaque position of an entry in the sorted list of entries.
+/// Used to request the entries that come after a previously received page of entries.
+/// Only contains what the entries are sorted by, so it stays small.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EntryCursor {
+    sort_date: DateTime<Utc>,
+    pub_date: DateTime<Utc>,
+    title: String,
+    key: EntryKey,
+    feed_url: Url,
+}
 
-        ord = self.key.cmp(&other.key);
-        if ord != Ordering::Equal {
-            return ord;
+impl EntryCursor {
+    pub fn from_entry(entry: &ComFeedEntry) -> Self {
+        Self {
+            sort_date: entry.sort_date,
+            pub_date: entry.pub_date,
+            title: entry.title.clone(),
+            key: entry.key.clone(),
+            feed_url: entry.feed_url.clone(),
         }
+    }
 
-        ord
+    /// Whether the entry is sorted after this cursor.
+    pub fn is_before(&self, entry: &ComFeedEntry) -> bool {
+        let cursor: SortKey<'_> = (
+            Reverse(self.sort_date),
+            Reverse(self.pub_date),
+            &self.title,
+            &self.key,
+            &self.feed_url,
+        );
+        cursor < entry.sort_key()
     }
 }
 
 /// Request and response for `/api/set_entry_read`
 /// The server sends the request straight back, so the client doesn't have to remember what
 /// it requested from the server, and can simply "copy the server's notes".
+///
+/// The read state is set for every copy of the entry, also the ones in other feeds than `feed_url`.
+/// That way entries that are de-duplicated (see [`FeedsRequest`] `.dedupe`) don't get out of sync.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SetEntryReadRequestAndResponse {
+    #[serde(default)]
+    pub collection_id: CollectionId,
     pub feed_url: Url,
     pub entry_key: EntryKey,
     pub read: bool,
 }
 
+/// Request and response for `/api/set_entry_read_later`.
+/// Works the same as [`SetEntryReadRequestAndResponse`], but for the "read later" list.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetEntryReadLaterRequestAndResponse {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub feed_url: Url,
+    pub entry_key: EntryKey,
+    pub read_later: bool,
+}
+
+/// Request for `/api/mark_all_read`.
+/// Marks all entries of the feeds in `filter` as read.
+///
+/// With [`FeedsFilter::All`] nothing is changed unless `confirm` is set. The server answers
+/// with [`MarkAllReadResponse::NeedsConfirmation`] instead, so the client can ask the user first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MarkAllReadRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub filter: FeedsFilter,
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+impl MarkAllReadRequest {
+    pub fn needs_confirmation(&self) -> bool {
+        self.filter == FeedsFilter::All && !self.confirm
+    }
+}
+
+/// Request for `/api/reset_read`.
+/// Marks all entries of the feed as unread, so it can be read again from the start.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResetReadRequest {
+    #[serde(default)]
+    pub collection_id: CollectionId,
+    pub feed_url: Url,
+}
+
+/// Response for `/api/reset_read`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResetReadResponse {
+    /// How many entries were read before.
+    pub reset: usize,
+}
+
+/// Response for `/api/mark_all_read`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum MarkAllReadResponse {
+    /// Nothing was changed. The request has to be sent again with `confirm` set.
+    NeedsConfirmation {
+        /// How many entries would be marked read.
+        unread: usize,
+    },
+    /// The entries that were unread before, so the client can undo it by sending them
+    /// to `/api/set_entries_read` with `read` set to `false`.
+    Marked(Vec<SetEntryReadRequestAndResponse>),
+}
+
+/// Request and response for `/api/set_entries_read`.
+/// Like [`SetEntryReadRequestAndResponse`], but for many entries in one request.
+///
+/// Entries that no longer exist, for example because they were removed after being read for
+/// a long time, are skipped. Only the entries that were changed are sent back.
+pub type SetEntriesReadRequestAndResponse = Vec<SetEntryReadRequestAndResponse>;
+
 /// Request and response for `/api/set_feed_info`
 /// The server sends the request straight back, so the client doesn't have to remember what
 /// it requested from the server, and can simply "copy the server's notes".
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SetFeedInfoRequestAndResponse {
+    #[serde(default)]
+    pub collection_id: CollectionId,
     pub feed_url: Url,
     pub info: FeedInfo,
 }
+
+/// Preferences of a user. These are stored on the server, so they are the same on every device.
+/// Response for `/api/user_settings`, and request and response for `/api/set_user_settings`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "StoredUserSettings")]
+pub struct UserSettings {
+    /// Whether opening the link of an entry also marks it as read.
+    pub mark_read_on_open: bool,
+    /// Whether unread entries are shown, read entries, or both.
+    pub shown_entries: ShownEntries,
+    /// How many entries the client requests at a time.
+    /// [`None`] means all entries are requested at once.
+    pub entries_per_request: Option<usize>,
+    /// The feeds that were selected last, so the selection is the same the next time the user
+    /// opens the app.
+    pub last_selection: FeedsFilter,
+    /// How often the client requests the shown entries again, to show what the background
+    /// update of the server found. [`None`] means never.
+    pub auto_refresh_minutes: Option<u64>,
+    /// Whether long entry titles are wrapped over multiple lines. Otherwise the middle of long
+    /// titles is left out, so the end, which often has a chapter number, stays visible.
+    pub wrap_titles: bool,
+    /// What the entries are sorted by.
+    pub sort_order: SortOrder,
+    /// Which keys move through the entries and act on the selected one.
+    pub keybindings: KeyBindings,
+}
+
+impl UserSettings {
+    /// The [`FeedsRequest`] `.amount` for requesting the next [`UserSettings`] `.entries_per_request` entries.
+    pub fn entry_request_amount(&self) -> usize {
+        self.entries_per_request.unwrap_or(usize::MAX)
+    }
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        UserSettings {
+            mark_read_on_open: true,
+            shown_entries: ShownEntries::Unread,
+            entries_per_request: Some(25),
+            last_selection: FeedsFilter::All,
+            auto_refresh_minutes: None,
+            wrap_titles: false,
+            sort_order: SortOrder::Published,
+            keybindings: KeyBindings::VimStyle,
+        }
+    }
+}
+
+/// How the [UserSettings] are deserialized, so that settings from before
+/// [UserSettings] `.shown_entries` existed can still be read.
+/// New settings get their default value when deserializing old settings.
+#[derive(Deserialize)]
+#[serde(default)]
+struct StoredUserSettings {
+    mark_read_on_open: bool,
+    shown_entries: ShownEntries,
+    /// Replaced by `shown_entries`.
+    show_read_entries: bool,
+    entries_per_request: Option<usize>,
+    last_selection: FeedsFilter,
+    auto_refresh_minutes: Option<u64>,
+    wrap_titles: bool,
+    sort_order: SortOrder,
+    keybindings: KeyBindings,
+}
+
+impl Default for StoredUserSettings {
+    fn default() -> Self {
+        let settings = UserSettings::default();
+        StoredUserSettings {
+            mark_read_on_open: settings.mark_read_on_open,
+            shown_entries: settings.shown_entries,
+            show_read_entries: false,
+            entries_per_request: settings.entries_per_request,
+            last_selection: settings.last_selection,
+            auto_refresh_minutes: settings.auto_refresh_minutes,
+            wrap_titles: settings.wrap_titles,
+            sort_order: settings.sort_order,
+            keybindings: settings.keybindings,
+        }
+    }
+}
+
+impl From<StoredUserSettings> for UserSettings {
+    fn from(stored: StoredUserSettings) -> Self {
+        let shown_entries = match (stored.shown_entries, stored.show_read_entries) {
+            (ShownEntries::Unread, true) => ShownEntries::All,
+            (shown_entries, _) => shown_entries,
+        };
+
+        UserSettings {
+            mark_read_on_open: stored.mark_read_on_open,
+            shown_entries,
+            entries_per_request: stored.entries_per_request,
+            last_selection: stored.last_selection,
+            auto_refresh_minutes: stored.auto_refresh_minutes,
+            wrap_titles: stored.wrap_titles,
+            sort_order: stored.sort_order,
+            keybindings: stored.keybindings,
+        }
+    }
+}
+
+/// What the entries are sorted by, newest first. See [UserSettings].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Published,
+    /// The later of the publication date and the date the entry was last updated, so edited
+    /// entries come back to the top.
+    LastActivity,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 2] = [SortOrder::Published, SortOrder::LastActivity];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Published => "Published",
+            SortOrder::LastActivity => "Last activity",
+        }
+    }
+}
+
+/// Which keys move through the entries. See [UserSettings].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyBindings {
+    /// `j` and `k` to move, like in vim.
+    #[default]
+    VimStyle,
+    /// The arrow keys to move, and enter to open.
+    ArrowStyle,
+}
+
+impl KeyBindings {
+    pub const ALL: [KeyBindings; 2] = [KeyBindings::VimStyle, KeyBindings::ArrowStyle];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyBindings::VimStyle => "Vim (j/k)",
+            KeyBindings::ArrowStyle => "Arrow keys",
+        }
+    }
+}
+
+/// Which entries of the selected feeds are shown. See [UserSettings].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShownEntries {
+    #[default]
+    Unread,
+    Read,
+    All,
+}
+
+impl ShownEntries {
+    pub const ALL: [ShownEntries; 3] =
+        [ShownEntries::Unread, ShownEntries::Read, ShownEntries::All];
+
+    pub fn entry_filter(&self) -> EntryTypeFilter {
+        match self {
+            ShownEntries::Unread => EntryTypeFilter::UnreadOnly,
+            ShownEntries::Read => EntryTypeFilter::ReadOnly,
+            ShownEntries::All => EntryTypeFilter::All,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShownEntries::Unread => "Unread",
+            ShownEntries::Read => "Read",
+            ShownEntries::All => "All",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_body::{ShownEntries, UserSettings};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_legacy_show_read_entries_is_converted_to_shown_entries() {
+        // Given
+        let show_read = r#"{"show_read_entries":true}"#;
+        let hide_read = r#"{"show_read_entries":false}"#;
+        let new = r#"{"shown_entries":"Read"}"#;
+
+        // When
+        let show_read: UserSettings = serde_json::from_str(show_read).unwrap();
+        let hide_read: UserSettings = serde_json::from_str(hide_read).unwrap();
+        let new: UserSettings = serde_json::from_str(new).unwrap();
+
+        // Then
+        assert_eq!(show_read.shown_entries, ShownEntries::All);
+        assert_eq!(hide_read.shown_entries, ShownEntries::Unread);
+        assert_eq!(new.shown_entries, ShownEntries::Read);
+    }
+}
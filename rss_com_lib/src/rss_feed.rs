@@ -1,19 +1,320 @@
 use crate::Url;
 use base64::prelude::*;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use feed_rs::model;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::{hash_map, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 
+/// Feeds that recommend updating more often than this, are still only updated this often.
+/// To avoid being a burden for the server (and the site).
+pub const MIN_RECOMMENDED_REFRESH_INTERVAL_MINUTES: u64 = 30;
+/// Feeds that recommend updating less often than this, are still updated this often.
+/// So that a wrong recommendation doesn't stop the updates of a feed altogether.
+pub const MAX_RECOMMENDED_REFRESH_INTERVAL_MINUTES: u64 = 60 * 24 * 7;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
-#[serde(default)]
+#[serde(from = "StoredFeedInfo")]
 pub struct FeedInfo {
     pub name: String,
     pub tags: HashSet<String>,
-    /// If the last update went wrong, this contains the reason.
-    pub last_update_result: Result<(), String>,
+    /// The website the feed belongs to, as opposed to the url of the feed itself.
+    /// [None] if the feed doesn't mention it.
+    pub home_url: Option<Url>,
+    /// How the last update went. If it went wrong, this contains the reason.
+    pub update_status: UpdateStatus,
+    /// When the entries of the feed were last downloaded without problems.
+    /// [None] if that never happened, or if the feed was added before this was tracked.
+    pub last_successful_update: Option<DateTime<Utc>>,
+    /// How often the background update should download this feed.
+    /// [None] uses the `recommended_refresh_interval_minutes`, or otherwise the default interval
+    /// of the server.
+    pub refresh_interval_minutes: Option<u64>,
+    /// How often the feed itself says it is updated, for example with a `<ttl>`.
+    pub recommended_refresh_interval_minutes: Option<u64>,
+    /// What was noticed while reading the last successful download of the feed.
+    /// [None] if the feed was not downloaded since this was tracked.
+    pub last_parse_diagnostics: Option<ParseDiagnostics>,
+    /// Position in the feed list, chosen by the user. Lower comes first.
+    /// Feeds without one come after the ones with one, sorted by name.
+    pub sort_index: Option<u32>,
+    /// Paused feeds are not downloaded, not in the background and not with "Update all feeds".
+    /// Their entries are kept.
+    pub enabled: bool,
+    /// What the feed says it is about. [None] if the feed doesn't say, or if it was not
+    /// downloaded since this was tracked. The same goes for the `language` and `icon_url`.
+    pub description: Option<String>,
+    /// Language code of the feed, for example `en-us`.
+    pub language: Option<String>,
+    /// Icon or logo the feed gives itself.
+    pub icon_url: Option<Url>,
+    /// Unread entries published longer ago than this are marked read when the feed is updated.
+    /// Entries on the "read later" list are left alone. [None] keeps entries unread forever.
+    pub mark_read_after_days: Option<u32>,
+    /// Unread entries with one of these in their title are marked read when the feed is updated.
+    /// Entries on the "read later" list are left alone. Case-insensitive.
+    pub mute_keywords: Vec<String>,
+    /// Entries with one of these in their title are shown emphasized. Case-insensitive.
+    pub highlight_keywords: Vec<String>,
+}
+
+/// How the [FeedInfo] is deserialized, so that files and requests from before the
+/// [UpdateStatus] existed can still be read.
+/// Fields that are missing get the value of [FeedInfo::default].
+#[derive(Deserialize)]
+#[serde(default)]
+struct StoredFeedInfo {
+    name: String,
+    tags: HashSet<String>,
+    home_url: Option<Url>,
+    update_status: UpdateStatus,
+    /// Replaced by `update_status`.
+    last_update_result: Result<(), String>,
+    last_successful_update: Option<DateTime<Utc>>,
+    refresh_interval_minutes: Option<u64>,
+    recommended_refresh_interval_minutes: Option<u64>,
+    last_parse_diagnostics: Option<ParseDiagnostics>,
+    sort_index: Option<u32>,
+    enabled: bool,
+    description: Option<String>,
+    language: Option<String>,
+    icon_url: Option<Url>,
+    mark_read_after_days: Option<u32>,
+    mute_keywords: Vec<String>,
+    highlight_keywords: Vec<String>,
+}
+
+impl Default for StoredFeedInfo {
+    fn default() -> Self {
+        let info = FeedInfo::default();
+        StoredFeedInfo {
+            name: info.name,
+            tags: info.tags,
+            home_url: info.home_url,
+            update_status: info.update_status,
+            last_update_result: Err(LEGACY_NEVER_UPDATED_MESSAGE.to_string()),
+            last_successful_update: info.last_successful_update,
+            refresh_interval_minutes: info.refresh_interval_minutes,
+            recommended_refresh_interval_minutes: info.recommended_refresh_interval_minutes,
+            last_parse_diagnostics: info.last_parse_diagnostics,
+            sort_index: info.sort_index,
+            enabled: info.enabled,
+            description: info.description,
+            language: info.language,
+            icon_url: info.icon_url,
+            mark_read_after_days: info.mark_read_after_days,
+            mute_keywords: info.mute_keywords,
+            highlight_keywords: info.highlight_keywords,
+        }
+    }
+}
+
+/// The message the old [FeedInfo] default had, before [UpdateStatus::NeverUpdated] existed.
+const LEGACY_NEVER_UPDATED_MESSAGE: &str = "Feed not yet updated for the first time";
+
+impl From<StoredFeedInfo> for FeedInfo {
+    fn from(stored: StoredFeedInfo) -> Self {
+        // The old result doesn't say when the update happened.
+        let legacy_time = stored
+            .last_successful_update
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        let update_status = match (stored.update_status, stored.last_update_result) {
+            (UpdateStatus::NeverUpdated, Ok(())) => UpdateStatus::Ok { at: legacy_time },
+            (UpdateStatus::NeverUpdated, Err(message))
+                if message != LEGACY_NEVER_UPDATED_MESSAGE =>
+            {
+                UpdateStatus::Failed {
+                    at: legacy_time,
+                    message,
+                    kind: FetchErrorKind::Other,
+                }
+            }
+            (status, _) => status,
+        };
+
+        FeedInfo {
+            name: stored.name,
+            tags: stored.tags,
+            home_url: stored.home_url,
+            update_status,
+            last_successful_update: stored.last_successful_update,
+            refresh_interval_minutes: stored.refresh_interval_minutes,
+            recommended_refresh_interval_minutes: stored.recommended_refresh_interval_minutes,
+            last_parse_diagnostics: stored.last_parse_diagnostics,
+            sort_index: stored.sort_index,
+            enabled: stored.enabled,
+            description: stored.description,
+            language: stored.language,
+            icon_url: stored.icon_url,
+            mark_read_after_days: stored.mark_read_after_days,
+            mute_keywords: stored.mute_keywords,
+            highlight_keywords: stored.highlight_keywords,
+        }
+    }
+}
+
+/// How the last download of the entries of a feed went.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub enum UpdateStatus {
+    /// The feed was not downloaded yet since it was added.
+    #[default]
+    NeverUpdated,
+    Ok {
+        at: DateTime<Utc>,
+    },
+    Failed {
+        at: DateTime<Utc>,
+        message: String,
+        /// Statuses from before this was tracked are [FetchErrorKind::Other].
+        #[serde(default)]
+        kind: FetchErrorKind,
+    },
+}
+
+impl UpdateStatus {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, UpdateStatus::Failed { .. })
+    }
+}
+
+/// What went wrong when downloading a feed. The message of the [UpdateStatus] has the details.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum FetchErrorKind {
+    Timeout,
+    /// The host of the feed could not be found, or was refused.
+    Dns,
+    Connect,
+    /// The server responded with an error status, such as `404`.
+    HttpStatus(u16),
+    /// The download is not a feed that can be read.
+    Parse,
+    /// The server responded with a web page instead of a feed.
+    NotAFeed,
+    TooLarge,
+    #[default]
+    Other,
+}
+
+impl FetchErrorKind {
+    /// Short name without spaces, for example as a metrics label.
+    pub fn label(&self) -> &'static str {
+        match self {
+            FetchErrorKind::Timeout => "timeout",
+            FetchErrorKind::Dns => "dns",
+            FetchErrorKind::Connect => "connect",
+            FetchErrorKind::HttpStatus(_) => "http_status",
+            FetchErrorKind::Parse => "parse",
+            FetchErrorKind::NotAFeed => "not_a_feed",
+            FetchErrorKind::TooLarge => "too_large",
+            FetchErrorKind::Other => "other",
+        }
+    }
+
+    /// Whether the problem will probably go away by itself, like a server that is down for a while.
+    /// Otherwise the user probably has to do something, like changing the url.
+    pub fn is_temporary(&self) -> bool {
+        match self {
+            FetchErrorKind::Timeout | FetchErrorKind::Connect => true,
+            FetchErrorKind::HttpStatus(status) => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+/// Summary of reading a downloaded feed. Feed parsers quietly ignore items they can't read,
+/// so this explains why a feed can have fewer entries than expected.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[serde(default)]
+pub struct ParseDiagnostics {
+    /// Entries that were read from the feed.
+    pub entries_parsed: usize,
+    /// Items in the feed that were ignored. Because they couldn't be read, or because they have
+    /// the same [EntryKey] as another item.
+    pub entries_skipped: usize,
+    /// Entries without a publication date. They got a date far in the past instead.
+    pub entries_without_date: usize,
+    /// Entries without a title. They got a placeholder title instead.
+    pub entries_without_title: usize,
+}
+
+impl ParseDiagnostics {
+    pub fn has_problems(&self) -> bool {
+        self.entries_skipped > 0 || self.entries_without_date > 0 || self.entries_without_title > 0
+    }
+
+    /// For example "10 entries, 2 skipped, 1 without date".
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{} entries", self.entries_parsed)];
+        if self.entries_skipped > 0 {
+            parts.push(format!("{} skipped", self.entries_skipped));
+        }
+        if self.entries_without_date > 0 {
+            parts.push(format!("{} without date", self.entries_without_date));
+        }
+        if self.entries_without_title > 0 {
+            parts.push(format!("{} without title", self.entries_without_title));
+        }
+        parts.join(", ")
+    }
+}
+
+impl FeedInfo {
+    /// Whether the background update should download this feed again.
+    /// The interval set by the user goes first. Then the interval the feed recommends, within
+    /// [MIN_RECOMMENDED_REFRESH_INTERVAL_MINUTES] and [MAX_RECOMMENDED_REFRESH_INTERVAL_MINUTES].
+    /// And otherwise the `default_interval`.
+    /// The interval counts from the last attempt, so feeds that keep failing aren't downloaded
+    /// more often. Paused feeds are never due.
+    pub fn is_due_for_update(&self, now: DateTime<Utc>, default_interval: Duration) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let recommended = self.recommended_refresh_interval_minutes.map(|minutes| {
+            minutes.clamp(
+                MIN_RECOMMENDED_REFRESH_INTERVAL_MINUTES,
+                MAX_RECOMMENDED_REFRESH_INTERVAL_MINUTES,
+            )
+        });
+        let interval = match self.refresh_interval_minutes.or(recommended) {
+            Some(minutes) => i64::try_from(minutes).ok().and_then(Duration::try_minutes),
+            None => Some(default_interval),
+        };
+
+        let last_attempt = match &self.update_status {
+            UpdateStatus::Failed { at, .. } => self.last_successful_update.max(Some(*at)),
+            _ => self.last_successful_update,
+        };
+
+        match (last_attempt, interval) {
+            (None, _) => true,
+            (Some(last_attempt), Some(interval)) => now - last_attempt >= interval,
+            // The interval is too long to represent, so it never passes.
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Whether entries with this title should be marked read. See [FeedInfo::mute_keywords].
+    pub fn is_muted(&self, title: &str) -> bool {
+        contains_keyword(title, &self.mute_keywords)
+    }
+
+    /// See [FeedInfo::highlight_keywords].
+    pub fn is_highlighted(&self, title: &str) -> bool {
+        contains_keyword(title, &self.highlight_keywords)
+    }
+}
+
+/// Whether the `text` contains one of the `keywords`, ignoring case.
+/// Empty keywords are ignored, otherwise they would match everything.
+fn contains_keyword(text: &str, keywords: &[String]) -> bool {
+    let text = text.to_lowercase();
+    keywords
+        .iter()
+        .map(|keyword| keyword.trim())
+        .filter(|keyword| !keyword.is_empty())
+        .any(|keyword| text.contains(&keyword.to_lowercase()))
 }
 
 impl Default for FeedInfo {
@@ -21,8 +322,20 @@ impl Default for FeedInfo {
         Self {
             name: Default::default(),
             tags: Default::default(),
-            // This message should never be visible for the user, because new feeds have to be updated once on-add to get the needed info.
-            last_update_result: Err("Feed not yet updated for the first time".to_string()),
+            home_url: None,
+            update_status: UpdateStatus::NeverUpdated,
+            last_successful_update: None,
+            refresh_interval_minutes: None,
+            recommended_refresh_interval_minutes: None,
+            last_parse_diagnostics: None,
+            sort_index: None,
+            enabled: true,
+            description: None,
+            language: None,
+            icon_url: None,
+            mark_read_after_days: None,
+            mute_keywords: Vec::new(),
+            highlight_keywords: Vec::new(),
         }
     }
 }
@@ -33,6 +346,20 @@ impl Hash for FeedInfo {
         for tag in self.tags.iter() {
             tag.hash(state)
         }
+        self.home_url.hash(state);
+        self.update_status.hash(state);
+        self.last_successful_update.hash(state);
+        self.refresh_interval_minutes.hash(state);
+        self.recommended_refresh_interval_minutes.hash(state);
+        self.last_parse_diagnostics.hash(state);
+        self.sort_index.hash(state);
+        self.enabled.hash(state);
+        self.description.hash(state);
+        self.language.hash(state);
+        self.icon_url.hash(state);
+        self.mark_read_after_days.hash(state);
+        self.mute_keywords.hash(state);
+        self.highlight_keywords.hash(state);
     }
 }
 
@@ -83,16 +410,43 @@ impl IntoIterator for FeedEntries {
     }
 }
 
+/// Version of the way [EntryKey]s are generated.
+/// Increase this whenever [EntryKey::from_entry] changes, so that saved entries can be migrated.
+///
+/// - 0: Based on the title and link.
+/// - 1: Based on the guid, if the entry has one. Otherwise based on the title and link.
+pub const ENTRY_KEY_SCHEME_VERSION: u32 = 1;
+
 /// Key identifying a [FeedEntry] in a feed.
 /// The value is generated using the [blake3::Hasher].
 ///
-/// TODO (Wybe 2022-09-24): Serialize and deserialize as base64? https://github.com/serde-rs/serde/issues/661
+/// Serialized as url-safe base64 without padding, so the key can be used in urls.
 #[derive(Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct EntryKey([u8; 32]);
 
 impl EntryKey {
-    /// The key is based on the title and link of the entry.
+    /// The key is based on the guid of the entry, if the feed provides one.
+    /// Otherwise it is based on the title and link of the entry.
+    ///
+    /// Entries that were saved before guids were used have a key based on the title and link.
+    /// See [EntryKey::from_title_and_link] for migrating those.
     pub fn from_entry(entry: &FeedEntry) -> Self {
+        match &entry.guid {
+            Some(guid) => {
+                let mut hasher = blake3::Hasher::new();
+                // Prefixed, so that a guid can never result in the same key as a title.
+                hasher.update(b"guid:");
+                hasher.update(guid.as_bytes());
+
+                EntryKey(hasher.finalize().into())
+            }
+            None => Self::from_title_and_link(entry),
+        }
+    }
+
+    /// The key based on the title and link of the entry, regardless of whether it has a guid.
+    /// This is how all keys were generated before guids were used.
+    pub fn from_title_and_link(entry: &FeedEntry) -> Self {
         let mut hasher = blake3::Hasher::new();
         hasher.update(entry.title.as_bytes());
 
@@ -107,7 +461,7 @@ impl EntryKey {
 impl Debug for EntryKey {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str("EntryKey(")?;
-        f.write_str(&BASE64_STANDARD.encode(self.0))?;
+        f.write_str(&BASE64_URL_SAFE_NO_PAD.encode(self.0))?;
         f.write_str(")")
     }
 }
@@ -120,7 +474,7 @@ impl Serialize for EntryKey {
         // To send hashmaps as json (which is used by actix to send a response body)
         // the keys need to be strings.
         // Also, this is more compact than printing it as a list of base 10 numbers.
-        serializer.serialize_str(&BASE64_STANDARD.encode(self.0))
+        serializer.serialize_str(&BASE64_URL_SAFE_NO_PAD.encode(self.0))
     }
 }
 
@@ -133,8 +487,11 @@ impl<'de> Deserialize<'de> for EntryKey {
 
         String::deserialize(deserializer)
             .and_then(|string| {
-                BASE64_STANDARD
-                    .decode(string)
+                BASE64_URL_SAFE_NO_PAD
+                    .decode(&string)
+                    // Keys used to be saved with the standard base64 alphabet, and with padding.
+                    // Those are still accepted, so older persistence files can be loaded.
+                    .or_else(|_| BASE64_STANDARD.decode(&string))
                     .map_err(|err| Error::custom(err.to_string()))
             })
             .and_then(|byte_vec| {
@@ -155,9 +512,24 @@ pub struct FeedEntry {
     /// If an rss feed includes an entry with no date, it will get a default date in the past.
     pub pub_date: DateTime<Utc>,
     pub read: bool,
+    /// Whether the user put the entry on their "read later" list. Independent of `read`.
+    pub read_later: bool,
+    /// Identifier given to the entry by the feed itself (`<guid>` in rss, `<id>` in atom).
+    /// Unlike the title, this should not change when the entry is edited.
+    pub guid: Option<String>,
+    /// When the feed says the entry was last changed. [None] if the feed doesn't say, or if
+    /// the entry was saved before this was tracked.
+    pub updated: Option<DateTime<Utc>>,
 }
 
 impl FeedEntry {
+    /// The later of the publication date and the `updated` date. So an entry that was edited
+    /// after it was published counts as new again.
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        self.updated
+            .map_or(self.pub_date, |updated| updated.max(self.pub_date))
+    }
+
     pub fn from_raw_feed_entry(item: &model::Entry) -> (EntryKey, Self) {
         // If the entry has no publication date, we will us a default date far in the past.
         let default_date = DateTime::UNIX_EPOCH;
@@ -179,6 +551,11 @@ impl FeedEntry {
             link: item.links.first().map(|link| Url::new(link.href.clone())),
             pub_date,
             read: false,
+            read_later: false,
+            guid: Some(item.id.trim())
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+            updated: item.updated.map(|updated| updated.with_timezone(&Utc)),
         };
         let key = EntryKey::from_entry(&entry);
         (key, entry)
@@ -187,16 +564,20 @@ impl FeedEntry {
 
 #[cfg(test)]
 mod tests {
-    use crate::rss_feed::{EntryKey, FeedEntry};
-    use chrono::{TimeZone, Utc};
+    use crate::rss_feed::{
+        EntryKey, FeedEntry, FeedInfo, FetchErrorKind, ParseDiagnostics, UpdateStatus,
+    };
+    use crate::Url;
+    use chrono::{Duration, TimeZone, Utc};
     use pretty_assertions::assert_eq;
     use serde_json;
 
     /// If the hashing algorithm used to generate [FeedEntry] keys changes, then the keys in the saved
     /// persistence files won't match anymore with the ones generated by the application.
     ///
-    /// This test is here to alert us that this has happened, and additional checks should be put
-    /// in place to migrate the data.
+    /// This test is here to alert us that this has happened. If it has, increase the
+    /// [ENTRY_KEY_SCHEME_VERSION](crate::rss_feed::ENTRY_KEY_SCHEME_VERSION), so the saved
+    /// entries are migrated on startup.
     #[test]
     fn hash_algorithm_change_guard() {
         // Given
@@ -205,6 +586,9 @@ mod tests {
             link: None,
             pub_date: Utc.with_ymd_and_hms(2022, 9, 10, 1, 3, 4).unwrap(),
             read: false,
+            read_later: false,
+            guid: None,
+            updated: None,
         };
 
         // When
@@ -213,7 +597,53 @@ mod tests {
         // Then
         assert_eq!(
             format!("{:?}", key),
-            "EntryKey(+vjG8EtOdpGWNayLWPbELTE7RcppsbgbTvIlWG/76ls=)".to_string()
+            "EntryKey(-vjG8EtOdpGWNayLWPbELTE7RcppsbgbTvIlWG_76ls)".to_string()
+        );
+    }
+
+    /// Same as [hash_algorithm_change_guard], but for entries that have a guid.
+    #[test]
+    fn hash_algorithm_change_guard_with_guid() {
+        // Given
+        let entry = FeedEntry {
+            title: "Title".to_owned(),
+            link: None,
+            pub_date: Utc.with_ymd_and_hms(2022, 9, 10, 1, 3, 4).unwrap(),
+            read: false,
+            read_later: false,
+            guid: Some("https://example.com/?p=123".to_owned()),
+            updated: None,
+        };
+
+        // When
+        let key = EntryKey::from_entry(&entry);
+
+        // Then
+        assert_eq!(
+            format!("{:?}", key),
+            "EntryKey(-CBrKsIYptL_aDBudg5YNF3FyYUUADfaYOTRUrJRKio)".to_string()
+        );
+        // The guid is used instead of the title and link.
+        assert_ne!(key, EntryKey::from_title_and_link(&entry));
+    }
+
+    #[test]
+    fn test_entry_key_falls_back_to_title_and_link_without_guid() {
+        // Given
+        let entry = FeedEntry {
+            title: "Title".to_owned(),
+            link: Some(Url::new("https://example.com/title".to_owned())),
+            pub_date: Default::default(),
+            read: false,
+            read_later: false,
+            guid: None,
+            updated: None,
+        };
+
+        // Then
+        assert_eq!(
+            EntryKey::from_entry(&entry),
+            EntryKey::from_title_and_link(&entry)
         );
     }
 
@@ -226,16 +656,194 @@ mod tests {
         let string = serde_json::to_string(&key).unwrap();
 
         // Then
-        assert_eq!(string, "\"AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM=\"");
+        assert_eq!(string, "\"AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM\"");
     }
 
     #[test]
     fn test_entry_key_deserialization() {
         // When
         let key: EntryKey =
-            serde_json::from_str("\"AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM=\"").unwrap();
+            serde_json::from_str("\"AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM\"").unwrap();
 
         // Then
         assert_eq!(key, EntryKey([3; 32]));
     }
+
+    /// Keys in older persistence files use the standard base64 alphabet, with padding.
+    #[test]
+    fn test_entry_key_deserialization_of_standard_base64() {
+        // When
+        let key: EntryKey =
+            serde_json::from_str("\"+vjG8EtOdpGWNayLWPbELTE7RcppsbgbTvIlWG/76ls=\"").unwrap();
+
+        // Then
+        assert_eq!(
+            format!("{:?}", key),
+            "EntryKey(-vjG8EtOdpGWNayLWPbELTE7RcppsbgbTvIlWG_76ls)"
+        );
+    }
+
+    #[test]
+    fn test_feed_is_due_for_update_after_its_interval() {
+        // Given
+        let now = Utc.with_ymd_and_hms(2024, 9, 10, 12, 0, 0).unwrap();
+        let default_interval = Duration::hours(12);
+        let updated_two_hours_ago = FeedInfo {
+            last_successful_update: Some(now - Duration::hours(2)),
+            ..Default::default()
+        };
+
+        // Then
+        assert!(FeedInfo::default().is_due_for_update(now, default_interval));
+        assert!(!updated_two_hours_ago.is_due_for_update(now, default_interval));
+        assert!(FeedInfo {
+            refresh_interval_minutes: Some(60),
+            ..updated_two_hours_ago.clone()
+        }
+        .is_due_for_update(now, default_interval));
+        assert!(!FeedInfo {
+            refresh_interval_minutes: Some(u64::MAX),
+            ..updated_two_hours_ago.clone()
+        }
+        .is_due_for_update(now, default_interval));
+        // Recommendations are bounded, user set intervals are not.
+        assert!(FeedInfo {
+            recommended_refresh_interval_minutes: Some(u64::MAX),
+            last_successful_update: Some(now - Duration::days(8)),
+            ..updated_two_hours_ago.clone()
+        }
+        .is_due_for_update(now, Duration::days(30)));
+        assert!(!FeedInfo {
+            recommended_refresh_interval_minutes: Some(1),
+            last_successful_update: Some(now - Duration::minutes(10)),
+            ..updated_two_hours_ago
+        }
+        .is_due_for_update(now, default_interval));
+    }
+
+    #[test]
+    fn test_failing_feed_is_due_for_update_after_its_interval_since_the_last_attempt() {
+        // Given
+        let now = Utc.with_ymd_and_hms(2024, 9, 10, 12, 0, 0).unwrap();
+        let failed = |minutes_ago: i64| FeedInfo {
+            refresh_interval_minutes: Some(60),
+            last_successful_update: Some(now - Duration::days(2)),
+            update_status: UpdateStatus::Failed {
+                at: now - Duration::minutes(minutes_ago),
+                message: "Timed out".to_string(),
+                kind: FetchErrorKind::Timeout,
+            },
+            ..Default::default()
+        };
+
+        // Then
+        assert!(!failed(10).is_due_for_update(now, Duration::hours(12)));
+        assert!(failed(60).is_due_for_update(now, Duration::hours(12)));
+    }
+
+    #[test]
+    fn test_paused_feed_is_never_due_for_update() {
+        // Given
+        let now = Utc.with_ymd_and_hms(2024, 9, 10, 12, 0, 0).unwrap();
+        let paused = FeedInfo {
+            enabled: false,
+            ..Default::default()
+        };
+
+        // Then
+        assert!(!paused.is_due_for_update(now, Duration::hours(12)));
+    }
+
+    #[test]
+    fn test_feeds_without_enabled_field_are_enabled() {
+        // Given
+        let stored = r#"{"name":"A"}"#;
+
+        // When
+        let info: FeedInfo = serde_json::from_str(stored).unwrap();
+
+        // Then
+        assert!(info.enabled);
+    }
+
+    #[test]
+    fn test_legacy_update_result_is_converted_to_update_status() {
+        // Given
+        let never_updated = r#"{"name":"A","last_update_result":{"Err":"Feed not yet updated for the first time"}}"#;
+        let failed = r#"{"name":"B","last_update_result":{"Err":"Timeout"}}"#;
+        let ok = r#"{"name":"C","last_update_result":{"Ok":null},"last_successful_update":"2024-09-01T12:00:00Z"}"#;
+
+        // When
+        let never_updated: FeedInfo = serde_json::from_str(never_updated).unwrap();
+        let failed: FeedInfo = serde_json::from_str(failed).unwrap();
+        let ok: FeedInfo = serde_json::from_str(ok).unwrap();
+
+        // Then
+        assert_eq!(never_updated.update_status, UpdateStatus::NeverUpdated);
+        assert!(failed.update_status.is_failed());
+        assert_eq!(
+            ok.update_status,
+            UpdateStatus::Ok {
+                at: Utc.with_ymd_and_hms(2024, 9, 1, 12, 0, 0).unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_diagnostics_summary_only_mentions_problems() {
+        // Given
+        let clean = ParseDiagnostics {
+            entries_parsed: 10,
+            ..Default::default()
+        };
+        let broken = ParseDiagnostics {
+            entries_parsed: 8,
+            entries_skipped: 2,
+            entries_without_date: 1,
+            entries_without_title: 0,
+        };
+
+        // Then
+        assert!(!clean.has_problems());
+        assert_eq!(clean.summary(), "10 entries");
+        assert!(broken.has_problems());
+        assert_eq!(broken.summary(), "8 entries, 2 skipped, 1 without date");
+    }
+
+    #[test]
+    fn test_update_status_survives_serialization() {
+        // Given
+        let info = FeedInfo {
+            update_status: UpdateStatus::Failed {
+                at: Utc.with_ymd_and_hms(2024, 9, 1, 12, 0, 0).unwrap(),
+                message: "Timeout".to_string(),
+                kind: FetchErrorKind::Timeout,
+            },
+            ..Default::default()
+        };
+
+        // When
+        let json = serde_json::to_string(&info).unwrap();
+        let deserialized: FeedInfo = serde_json::from_str(&json).unwrap();
+
+        // Then
+        assert_eq!(deserialized, info);
+    }
+
+    #[test]
+    fn test_keywords_match_titles_ignoring_case() {
+        // Given
+        let info = FeedInfo {
+            mute_keywords: vec!["sponsored".to_string(), " ".to_string()],
+            highlight_keywords: vec!["Rust".to_string()],
+            ..Default::default()
+        };
+
+        // Then
+        assert!(info.is_muted("SPONSORED: The best laptops"));
+        assert!(!info.is_muted("Weekly news"));
+        assert!(info.is_highlighted("Announcing rust 2.0"));
+        assert!(!info.is_highlighted("Announcing Go 2.0"));
+        assert!(!FeedInfo::default().is_muted("Anything"));
+    }
 }
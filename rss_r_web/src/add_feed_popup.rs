@@ -1,9 +1,14 @@
 use crate::edit_feed_popup::TagSelector;
+use crate::hyperlink::NewTabHyperlink;
 use crate::requests::{ApiEndpoint, Requests, Response};
 use crate::{POPUP_ALIGN, POPUP_OFFSET};
+use chrono::Local;
 use egui::{Button, Context, TextEdit, Ui};
 use log::warn;
-use rss_com_lib::message_body::{AddFeedRequest, IsUrlAnRssFeedRequest, IsUrlAnRssFeedResponse};
+use rss_com_lib::message_body::{
+    AddFeedRequest, AddFeedsResponse, CollectionId, ImportUrlListRequest, IsUrlAnRssFeedRequest,
+    IsUrlAnRssFeedResponse, PreviewEntry,
+};
 use rss_com_lib::Url;
 use std::collections::HashSet;
 
@@ -15,21 +20,40 @@ pub struct AddFeedPopup {
     /// at any point, and then it might not be a valid rss url anymore.
     /// TODO (Wybe 2022-07-14): Provision for multiple feeds being available?
     feed_test_response: Option<Result<(Url, String), String>>,
+    /// The newest entries of the tested feed, so the user can check it is the right one.
+    feed_preview: Vec<PreviewEntry>,
+    /// Why the server refused to add the feed, for example because of a limit.
+    add_feed_error: Option<String>,
     tag_selector: TagSelector,
+    /// Whether the user is adding multiple urls at once, instead of testing a single url.
+    bulk_mode: bool,
+    /// One url per line.
+    bulk_input: String,
+    /// Result of the last bulk add.
+    bulk_results: Option<AddFeedsResponse>,
+    /// The collection the feeds are added to.
+    collection_id: CollectionId,
 }
 
 impl AddFeedPopup {
-    pub fn new(known_tags: HashSet<String>) -> Self {
+    pub fn new(known_tags: HashSet<String>, collection_id: CollectionId) -> Self {
         AddFeedPopup {
             input_url: "".to_string(),
             feed_test_response: None,
+            feed_preview: vec![],
+            add_feed_error: None,
             tag_selector: TagSelector::new(HashSet::new(), known_tags),
+            bulk_mode: false,
+            bulk_input: "".to_string(),
+            bulk_results: None,
+            collection_id,
         }
     }
 
     pub fn show(&mut self, ctx: &Context, requests: &mut Requests) -> AddFeedPopupResponse {
         let mut is_open = true;
         let mut feed_was_added = false;
+        let mut bulk_feeds_were_added = false;
 
         egui::Window::new("Add feed")
             .open(&mut is_open)
@@ -37,6 +61,14 @@ impl AddFeedPopup {
             .resizable(false)
             .collapsible(false)
             .show(ctx, |ui| {
+                ui.checkbox(&mut self.bulk_mode, "Add multiple feeds");
+
+                if self.bulk_mode {
+                    self.tag_selector.show(ui);
+                    bulk_feeds_were_added = self.show_bulk_input(ui, requests);
+                    return;
+                }
+
                 self.show_url_input(ui, requests);
 
                 self.tag_selector.show(ui);
@@ -45,13 +77,18 @@ impl AddFeedPopup {
                     match response {
                         Ok((url, name)) => {
                             ui.label(format!("Feed found: {}", name));
+                            show_preview(ui, &self.feed_preview);
 
                             AddFeedPopup::show_add_feed_button(
                                 ui,
                                 requests,
                                 url,
                                 &self.tag_selector,
+                                self.collection_id,
                             );
+                            if let Some(error) = &self.add_feed_error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
                         }
                         Err(error_message) => {
                             ui.colored_label(egui::Color32::RED, error_message);
@@ -66,11 +103,16 @@ impl AddFeedPopup {
                                 // Success.
                                 feed_was_added = true;
                             }
-                            // TODO (Wybe 2022-07-16): Add error reporting.
-                            Response::NotOk(_) => {}
+                            Response::NotOk(_) => {
+                                self.add_feed_error =
+                                    requests.take_failure_message(ApiEndpoint::AddFeed);
+                            }
+                            // Shown in the general failure banner, with the option to retry.
                             Response::Error => {}
                         }
                     } else {
+                        // The error of a previous attempt.
+                        self.add_feed_error = None;
                         ui.spinner();
                     }
                 }
@@ -78,6 +120,8 @@ impl AddFeedPopup {
 
         if feed_was_added {
             AddFeedPopupResponse::FeedAdded
+        } else if bulk_feeds_were_added {
+            AddFeedPopupResponse::BulkFeedsAdded
         } else if !is_open {
             AddFeedPopupResponse::ClosePopup
         } else {
@@ -109,6 +153,7 @@ impl AddFeedPopup {
                 requests.new_request_with_json_body(ApiEndpoint::IsUrlAnRssFeed, &request_body);
 
                 self.feed_test_response = None;
+                self.feed_preview.clear();
             }
         });
 
@@ -118,6 +163,9 @@ impl AddFeedPopup {
                 if let Response::Ok(body) = response {
                     if let Ok(rss_response) = serde_json::from_str::<IsUrlAnRssFeedResponse>(&body)
                     {
+                        self.feed_preview = rss_response.preview;
+                        self.tag_selector
+                            .set_suggested_tags(rss_response.suggested_tags);
                         match rss_response.result {
                             Ok(name) => {
                                 self.feed_test_response =
@@ -144,11 +192,72 @@ impl AddFeedPopup {
         }
     }
 
+    /// Returns true if any feeds were added.
+    fn show_bulk_input(&mut self, ui: &mut Ui, requests: &mut Requests) -> bool {
+        let request_ongoing = requests.has_request(ApiEndpoint::ImportUrlList);
+        let mut feeds_were_added = false;
+
+        TextEdit::multiline(&mut self.bulk_input)
+            .hint_text("One url per line, lines starting with # are skipped")
+            .show(ui);
+
+        if ui
+            .add_enabled(!request_ongoing, Button::new("Add all"))
+            .clicked()
+        {
+            let request_body = ImportUrlListRequest {
+                collection_id: self.collection_id,
+                urls: self.bulk_input.clone(),
+                tags: self.tag_selector.get_selected_tags(),
+            };
+            requests.new_request_with_json_body(ApiEndpoint::ImportUrlList, &request_body);
+
+            self.bulk_results = None;
+        }
+
+        if request_ongoing {
+            if let Some(response) = requests.ready(ApiEndpoint::ImportUrlList) {
+                if let Response::Ok(body) = response {
+                    if let Ok(results) = serde_json::from_str::<AddFeedsResponse>(&body) {
+                        feeds_were_added = results.iter().any(|(_, result)| result.is_ok());
+                        self.bulk_results = Some(results);
+                    }
+                } else {
+                    warn!(
+                        "Something went wrong while adding feeds. Response was: {:?}",
+                        response
+                    );
+                }
+            } else {
+                ui.spinner();
+            }
+        }
+
+        if let Some(results) = &self.bulk_results {
+            for (url, result) in results {
+                match result {
+                    Ok(()) => {
+                        ui.label(format!("Added: {}", url));
+                    }
+                    Err(error_message) => {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            format!("Failed: {}: {}", url, error_message),
+                        );
+                    }
+                }
+            }
+        }
+
+        feeds_were_added
+    }
+
     fn show_add_feed_button(
         ui: &mut Ui,
         requests: &mut Requests,
         feed_url: &Url,
         tag_selector: &TagSelector,
+        collection_id: CollectionId,
     ) {
         if ui
             .add_enabled(
@@ -160,6 +269,7 @@ impl AddFeedPopup {
             requests.new_request_with_json_body(
                 ApiEndpoint::AddFeed,
                 AddFeedRequest {
+                    collection_id,
                     url: feed_url.clone(),
                     tags: tag_selector.get_selected_tags(),
                 },
@@ -168,6 +278,34 @@ impl AddFeedPopup {
     }
 }
 
+fn show_preview(ui: &mut Ui, preview: &[PreviewEntry]) {
+    if preview.is_empty() {
+        return;
+    }
+
+    ui.label("Latest entries:");
+    egui::Grid::new("feed-preview-grid")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for entry in preview {
+                ui.label(
+                    entry
+                        .pub_date
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d")
+                        .to_string(),
+                );
+                if let Some(link) = &entry.link {
+                    ui.add(NewTabHyperlink::from_label_and_url(&entry.title, link));
+                } else {
+                    ui.label(&entry.title);
+                }
+                ui.end_row();
+            }
+        });
+}
+
 pub enum AddFeedPopupResponse {
     /// Nothing to do.
     None,
@@ -175,4 +313,7 @@ pub enum AddFeedPopupResponse {
     ClosePopup,
     /// User has added an rss feed. Update the list.
     FeedAdded,
+    /// User has added multiple rss feeds at once. Update the list, but keep the popup open
+    /// so the user can see which ones failed.
+    BulkFeedsAdded,
 }
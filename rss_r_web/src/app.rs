@@ -1,18 +1,29 @@
 use crate::login::LoginView;
-use crate::requests::{ApiEndpoint, Requests};
+use crate::requests::{ApiEndpoint, Requests, Response};
 use crate::rss_collection::RssDisplay;
 use eframe::Frame;
 use egui::{Align2, Context, Ui, Vec2, Visuals};
-use log::info;
+use log::{info, warn};
+use rss_com_lib::message_body::{FeedsFilter, VersionResponse, API_SCHEMA_VERSION};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Title of the browser tab, the same as in `index.html`.
+const TITLE: &str = "Feedreader";
 
 pub struct RssApp {
     // TODO (Wybe 2022-07-11): Store config server side? And retrieve on log-in?
     config: Config,
     requests: Requests,
     active_view: ActiveView,
+    /// Feed selection from before the session expired.
+    /// Restored when the user logs in again.
+    selection_before_session_expired: Option<FeedsFilter>,
     version_string: String,
+    /// Version of the server, if it doesn't fit this client anymore.
+    /// Happens when the browser still has the client from before the server was updated.
+    outdated_for_server_version: Option<String>,
+    /// Last title that was set on the browser tab, so we only set it when it changes.
+    document_title: String,
 }
 
 impl RssApp {
@@ -29,12 +40,19 @@ impl RssApp {
             Visuals::light()
         };
         cc.egui_ctx.set_visuals(visuals);
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+
+        let mut requests = Requests::new(cc.egui_ctx.clone());
+        requests.new_request_without_body(ApiEndpoint::Version);
 
         RssApp {
             config,
-            requests: Requests::new(cc.egui_ctx.clone()),
+            requests,
             active_view: ActiveView::Login(LoginView::default()),
+            selection_before_session_expired: None,
             version_string: format!("v{}", VERSION),
+            outdated_for_server_version: None,
+            document_title: TITLE.to_string(),
         }
     }
 }
@@ -48,19 +66,36 @@ impl eframe::App for RssApp {
 
         if !self.requests.is_authenticated() && !at_login_view {
             // No longer authenticated. Back to login view.
-            self.active_view = ActiveView::Login(LoginView::default());
+            if let ActiveView::RssCollection(collection) = &self.active_view {
+                self.selection_before_session_expired = Some(collection.current_selection());
+            }
+            self.active_view = ActiveView::Login(LoginView::after_session_expired());
+        }
+
+        if let ActiveView::RssCollection(collection) = &mut self.active_view {
+            // Not while typing, for example in the add feed popup.
+            if !ctx.wants_keyboard_input() {
+                collection.handle_key_presses(ctx);
+            }
+
+            collection.auto_refresh(ctx, &mut self.requests);
         }
 
         egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if let ActiveView::RssCollection(collection) = &mut self.active_view {
                     collection.show_feeds_button(ui);
+                    collection.show_unread_total(ui);
+                    collection.show_collection_switcher(ui, &mut self.requests);
+                    ui.separator();
+                    collection.show_quick_filters(ui, &mut self.requests);
                 }
 
                 if self.requests.has_request(ApiEndpoint::Logout) {
                     if self.requests.ready(ApiEndpoint::Logout).is_some() {
                         info!("Logged out");
                         self.requests.set_authenticated(false);
+                        self.selection_before_session_expired = None;
                         self.active_view = ActiveView::Login(LoginView::default());
                     } else {
                         ui.spinner();
@@ -86,6 +121,34 @@ impl eframe::App for RssApp {
             });
         });
 
+        if self.requests.has_request(ApiEndpoint::Version) {
+            if let Some(Response::Ok(body)) = self.requests.ready(ApiEndpoint::Version) {
+                if let Ok(version) = serde_json::from_str::<VersionResponse>(&body) {
+                    if version.api_schema_version != API_SCHEMA_VERSION {
+                        warn!(
+                            "Server api schema version {} differs from ours, {}",
+                            version.api_schema_version, API_SCHEMA_VERSION
+                        );
+                        self.outdated_for_server_version = Some(version.crate_version);
+                    }
+                }
+            }
+        }
+
+        if let Some(server_version) = &self.outdated_for_server_version {
+            egui::TopBottomPanel::top("outdated-client-banner").show(ctx, |ui| {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!(
+                        "The server was updated to v{}. Please refresh the page to get the new version of this app.",
+                        server_version
+                    ),
+                );
+            });
+        }
+
+        show_failed_request_banner(ctx, &mut self.requests);
+
         if let ActiveView::RssCollection(collection) = &mut self.active_view {
             collection.show_feed_list(ctx, &mut self.requests);
             collection.handle_popups(ctx, &mut self.requests)
@@ -110,11 +173,27 @@ impl eframe::App for RssApp {
 
         if logged_in {
             self.requests.set_authenticated(true);
-            let new_display = RssDisplay::new(ctx);
+            let mut new_display = RssDisplay::new(ctx);
+            if let Some(selection) = self.selection_before_session_expired.take() {
+                new_display.set_selection(selection);
+            }
             new_display.on_login(&mut self.requests);
 
             self.active_view = ActiveView::RssCollection(Box::new(new_display));
         }
+
+        // Show the unread total in the browser tab, so new entries are noticed when the tab is
+        // in the background.
+        let document_title = match &self.active_view {
+            ActiveView::RssCollection(collection) if collection.total_unread() > 0 => {
+                format!("({}) {}", collection.total_unread(), TITLE)
+            }
+            _ => TITLE.to_string(),
+        };
+        if document_title != self.document_title {
+            set_document_title(&document_title);
+            self.document_title = document_title;
+        }
     }
 
     /// Called by the frame work to save state before shutdown.
@@ -141,6 +220,39 @@ impl Default for Config {
     }
 }
 
+/// Shows what went wrong with the last failed request, so the app doesn't silently appear frozen
+/// when for example the server is restarting.
+fn show_failed_request_banner(ctx: &Context, requests: &mut Requests) {
+    let Some(failed) = requests.failed_request() else {
+        return;
+    };
+    let message = format!("{:?} request failed: {}", failed.endpoint, failed.message);
+
+    egui::TopBottomPanel::top("failed-request-banner").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.colored_label(ui.visuals().error_fg_color, message);
+
+            if ui.button("Retry").clicked() {
+                requests.retry_failed_request();
+            }
+            if ui.button("Dismiss").clicked() {
+                requests.dismiss_failed_request();
+            }
+        });
+    });
+}
+
+/// Sets the title of the browser tab.
+#[cfg(target_arch = "wasm32")]
+fn set_document_title(title: &str) {
+    if let Some(document) = web_sys::window().and_then(|w| w.document()) {
+        document.set_title(title);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn set_document_title(_title: &str) {}
+
 /// Returns whether the user selected dark mode.
 fn global_dark_light_mode_switch(ui: &mut Ui) -> Option<bool> {
     let style = (*ui.ctx().style()).clone();
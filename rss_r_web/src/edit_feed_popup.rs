@@ -1,25 +1,68 @@
+use crate::hyperlink::NewTabHyperlink;
 use crate::requests::{ApiEndpoint, Requests, Response};
 use crate::{POPUP_ALIGN, POPUP_OFFSET};
 use egui::{Context, TextEdit, Ui};
-use rss_com_lib::message_body::SetFeedInfoRequestAndResponse;
+use rss_com_lib::message_body::{
+    CollectionId, MergeFeedsRequest, ResetReadRequest, ResetReadResponse,
+    SetFeedInfoRequestAndResponse,
+};
 use rss_com_lib::rss_feed::FeedInfo;
 use rss_com_lib::Url;
 use std::collections::HashSet;
 
+/// The background update only checks every few minutes, so shorter intervals make no difference.
+const MIN_REFRESH_INTERVAL_MINUTES: u64 = 10;
+const DEFAULT_CUSTOM_REFRESH_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_MARK_READ_AFTER_DAYS: u32 = 14;
+
 pub struct EditFeedPopup {
     feed_url: Url,
     feed_info: FeedInfo,
     tag_selector: TagSelector,
+    /// The collection the feed is in.
+    collection_id: CollectionId,
+    /// Why the server refused the changes, for example because of a limit.
+    save_error: Option<String>,
+    /// How many entries were marked unread with "Mark all unread".
+    reset_amount: Option<usize>,
+    /// The other feeds of the collection and their names, that this feed can be merged into.
+    merge_candidates: Vec<(Url, String)>,
+    /// The feed the user chose to merge this feed into.
+    merge_into: Option<Url>,
+    /// The [FeedInfo::mute_keywords] as the user types them, separated by commas.
+    mute_keywords: String,
+    /// The [FeedInfo::highlight_keywords] as the user types them, separated by commas.
+    highlight_keywords: String,
 }
 
 impl EditFeedPopup {
-    pub fn new(feed_url: Url, feed_info: FeedInfo, known_tags: HashSet<String>) -> Self {
+    pub fn new(
+        feed_url: Url,
+        feed_info: FeedInfo,
+        known_tags: HashSet<String>,
+        collection_id: CollectionId,
+        all_feeds: &[(Url, String)],
+    ) -> Self {
         let tag_selector = TagSelector::new(feed_info.tags.clone(), known_tags);
+        let merge_candidates = all_feeds
+            .iter()
+            .filter(|(url, _)| *url != feed_url)
+            .cloned()
+            .collect();
+        let mute_keywords = feed_info.mute_keywords.join(", ");
+        let highlight_keywords = feed_info.highlight_keywords.join(", ");
 
         Self {
             feed_url,
             feed_info,
             tag_selector,
+            collection_id,
+            save_error: None,
+            reset_amount: None,
+            merge_candidates,
+            merge_into: None,
+            mute_keywords,
+            highlight_keywords,
         }
     }
 
@@ -34,16 +77,191 @@ impl EditFeedPopup {
             .collapsible(false)
             .show(ctx, |ui| {
                 ui.heading(&self.feed_info.name);
+                if let Some(description) = &self.feed_info.description {
+                    ui.weak(description);
+                }
+                if let Some(home_url) = &self.feed_info.home_url {
+                    ui.add(NewTabHyperlink::from_label_and_url("Visit site", home_url));
+                }
 
                 self.tag_selector.show(ui);
 
+                let mut custom_interval = self.feed_info.refresh_interval_minutes.is_some();
+                ui.checkbox(&mut custom_interval, "Custom refresh interval")
+                    .on_hover_text(
+                        "Otherwise the feed is refreshed as often as the server default",
+                    );
+                if custom_interval {
+                    let minutes = self
+                        .feed_info
+                        .refresh_interval_minutes
+                        .get_or_insert(DEFAULT_CUSTOM_REFRESH_INTERVAL_MINUTES);
+                    ui.add(
+                        egui::DragValue::new(minutes)
+                            .clamp_range(MIN_REFRESH_INTERVAL_MINUTES..=u64::MAX)
+                            .suffix(" minutes"),
+                    );
+                } else {
+                    self.feed_info.refresh_interval_minutes = None;
+                    if let Some(minutes) = self.feed_info.recommended_refresh_interval_minutes {
+                        ui.label(format!("The feed recommends every {} minutes", minutes));
+                    }
+                }
+
+                let mut paused = !self.feed_info.enabled;
+                ui.checkbox(&mut paused, "Paused").on_hover_text(
+                    "Paused feeds are not updated. The entries that are already there are kept",
+                );
+                self.feed_info.enabled = !paused;
+
+                let mut mark_old_read = self.feed_info.mark_read_after_days.is_some();
+                ui.checkbox(&mut mark_old_read, "Mark old entries read")
+                    .on_hover_text(
+                        "Unread entries older than this are marked read when the feed is updated. \
+                        Entries on the read later list are left alone",
+                    );
+                if mark_old_read {
+                    let days = self
+                        .feed_info
+                        .mark_read_after_days
+                        .get_or_insert(DEFAULT_MARK_READ_AFTER_DAYS);
+                    ui.add(
+                        egui::DragValue::new(days)
+                            .clamp_range(1..=u32::MAX)
+                            .suffix(" days"),
+                    );
+                } else {
+                    self.feed_info.mark_read_after_days = None;
+                }
+
+                egui::Grid::new("keywords").num_columns(2).show(ui, |ui| {
+                    ui.label("Mute").on_hover_text(
+                        "Unread entries with one of these words in their title are marked read. \
+                        Entries on the read later list are left alone",
+                    );
+                    ui.add(
+                        TextEdit::singleline(&mut self.mute_keywords)
+                            .hint_text("Comma separated, for example: sponsored, giveaway"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Highlight")
+                        .on_hover_text("Entries with one of these words in their title stand out");
+                    ui.add(
+                        TextEdit::singleline(&mut self.highlight_keywords)
+                            .hint_text("Comma separated"),
+                    );
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !requests.has_request(ApiEndpoint::ResetRead),
+                            egui::Button::new("Mark all unread"),
+                        )
+                        .on_hover_text("To read the feed again from the start")
+                        .clicked()
+                    {
+                        requests.new_request_with_json_body(
+                            ApiEndpoint::ResetRead,
+                            ResetReadRequest {
+                                collection_id: self.collection_id,
+                                feed_url: self.feed_url.clone(),
+                            },
+                        );
+                    }
+
+                    if requests.has_request(ApiEndpoint::ResetRead) {
+                        if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::ResetRead) {
+                            if let Ok(reset) = serde_json::from_str::<ResetReadResponse>(&body) {
+                                self.reset_amount = Some(reset.reset);
+                                response = EditFeedPopupResponse::ReadStateReset;
+                            }
+                        } else {
+                            ui.spinner();
+                        }
+                    }
+                    if let Some(amount) = self.reset_amount {
+                        ui.label(format!("{} entries marked unread", amount));
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            self.merge_into.is_some()
+                                && !requests.has_request(ApiEndpoint::MergeFeeds),
+                            egui::Button::new("Merge into"),
+                        )
+                        .on_hover_text(
+                            "For when the same feed was added twice. Removes this feed, and \
+                            keeps which entries are read or on the read later list",
+                        )
+                        .clicked()
+                    {
+                        if let Some(merge_into) = &self.merge_into {
+                            self.save_error = None;
+                            requests.new_request_with_json_body(
+                                ApiEndpoint::MergeFeeds,
+                                MergeFeedsRequest {
+                                    collection_id: self.collection_id,
+                                    keep: merge_into.clone(),
+                                    merge: self.feed_url.clone(),
+                                },
+                            );
+                        }
+                    }
+
+                    let merge_into_name = self
+                        .merge_into
+                        .as_ref()
+                        .and_then(|merge_into| {
+                            self.merge_candidates
+                                .iter()
+                                .find(|(url, _)| url == merge_into)
+                        })
+                        .map_or("Choose a feed", |(_, name)| name.as_str());
+                    egui::ComboBox::from_id_source("merge-into")
+                        .selected_text(merge_into_name)
+                        .show_ui(ui, |ui| {
+                            for (url, name) in &self.merge_candidates {
+                                ui.selectable_value(&mut self.merge_into, Some(url.clone()), name)
+                                    .on_hover_text(url.as_str());
+                            }
+                        });
+
+                    if requests.has_request(ApiEndpoint::MergeFeeds) {
+                        match requests.ready(ApiEndpoint::MergeFeeds) {
+                            Some(Response::Ok(_)) => {
+                                response = EditFeedPopupResponse::FeedsMerged;
+                            }
+                            Some(Response::NotOk(_)) => {
+                                self.save_error =
+                                    requests.take_failure_message(ApiEndpoint::MergeFeeds);
+                            }
+                            // Shown in the general failure banner, with the option to retry.
+                            Some(Response::Error) => {}
+                            None => {
+                                ui.spinner();
+                            }
+                        }
+                    }
+                });
+
+                ui.separator();
+
                 // TODO (Wybe 2022-09-25): Add an api to edit a feed's info.
                 if ui.button("Save").clicked() {
                     self.feed_info.tags = self.tag_selector.get_selected_tags();
+                    self.feed_info.mute_keywords = parse_keywords(&self.mute_keywords);
+                    self.feed_info.highlight_keywords = parse_keywords(&self.highlight_keywords);
+                    self.save_error = None;
 
                     requests.new_request_with_json_body(
                         ApiEndpoint::SetFeedInfo,
                         SetFeedInfoRequestAndResponse {
+                            collection_id: self.collection_id,
                             feed_url: self.feed_url.clone(),
                             info: self.feed_info.clone(),
                         },
@@ -51,21 +269,33 @@ impl EditFeedPopup {
                 }
 
                 if requests.has_request(ApiEndpoint::SetFeedInfo) {
-                    // TODO (Wybe 2022-09-27): Add error handling.
-                    if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::SetFeedInfo) {
-                        if let Ok(feeds_response) =
-                            serde_json::from_str::<SetFeedInfoRequestAndResponse>(&body)
-                        {
-                            // Success.
-                            response = EditFeedPopupResponse::FeedInfoEdited(
-                                feeds_response.feed_url,
-                                feeds_response.info,
-                            );
+                    match requests.ready(ApiEndpoint::SetFeedInfo) {
+                        Some(Response::Ok(body)) => {
+                            if let Ok(feeds_response) =
+                                serde_json::from_str::<SetFeedInfoRequestAndResponse>(&body)
+                            {
+                                // Success.
+                                response = EditFeedPopupResponse::FeedInfoEdited(
+                                    feeds_response.feed_url,
+                                    Box::new(feeds_response.info),
+                                );
+                            }
+                        }
+                        Some(Response::NotOk(_)) => {
+                            self.save_error =
+                                requests.take_failure_message(ApiEndpoint::SetFeedInfo);
+                        }
+                        // Shown in the general failure banner, with the option to retry.
+                        Some(Response::Error) => {}
+                        None => {
+                            ui.spinner();
                         }
-                    } else {
-                        ui.spinner();
                     }
                 }
+
+                if let Some(error) = &self.save_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
             });
 
         if response == EditFeedPopupResponse::None && !is_open {
@@ -76,6 +306,15 @@ impl EditFeedPopup {
     }
 }
 
+/// The keywords in a comma separated list, without surrounding spaces and empty ones.
+fn parse_keywords(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|keyword| !keyword.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 #[derive(Eq, PartialEq)]
 pub enum EditFeedPopupResponse {
     /// Nothing to do.
@@ -83,13 +322,20 @@ pub enum EditFeedPopupResponse {
     /// User wants to close the popup. No new feeds.
     ClosePopup,
     /// Info was edited. Contains the url of the edited feed, and the new info.
-    FeedInfoEdited(Url, FeedInfo),
+    /// Boxed, because the info is much larger than the other variants.
+    FeedInfoEdited(Url, Box<FeedInfo>),
+    /// All entries of the feed were marked unread. The popup stays open.
+    ReadStateReset,
+    /// The feed was merged into another one, so it doesn't exist anymore.
+    FeedsMerged,
 }
 
 pub struct TagSelector {
     /// List of tags and whether they are selected for this feed.
     tags: Vec<(String, bool)>,
     new_tag: String,
+    /// Tags that are only in the list because the feed suggested them.
+    suggested_tags: HashSet<String>,
 }
 
 impl TagSelector {
@@ -109,6 +355,7 @@ impl TagSelector {
         let mut selector = TagSelector {
             tags: tag_selection,
             new_tag: String::new(),
+            suggested_tags: HashSet::new(),
         };
         selector.sort_tags();
 
@@ -121,7 +368,10 @@ impl TagSelector {
 
         for (tag, selected) in self.tags.iter_mut() {
             // TODO (Wybe 2022-09-25): We should be able to show the tag without cloning the text.
-            ui.checkbox(selected, tag.clone());
+            let checkbox = ui.checkbox(selected, tag.clone());
+            if self.suggested_tags.contains(tag) {
+                checkbox.on_hover_text("Suggested by the feed");
+            }
         }
 
         ui.horizontal(|ui| {
@@ -151,6 +401,22 @@ impl TagSelector {
         ui.separator();
     }
 
+    /// Adds the tags that are not in the list yet, without selecting them.
+    /// Suggestions from before that the user didn't select are removed.
+    pub fn set_suggested_tags(&mut self, suggested_tags: Vec<String>) {
+        let previous_suggestions = std::mem::take(&mut self.suggested_tags);
+        self.tags
+            .retain(|(tag, selected)| *selected || !previous_suggestions.contains(tag));
+
+        for tag in suggested_tags {
+            if !self.tags.iter().any(|(known_tag, _)| known_tag == &tag) {
+                self.tags.push((tag.clone(), false));
+                self.suggested_tags.insert(tag);
+            }
+        }
+        self.sort_tags();
+    }
+
     pub fn get_selected_tags(&self) -> HashSet<String> {
         self.tags
             .iter()
@@ -163,3 +429,18 @@ impl TagSelector {
             .sort_by(|(tag, _), (other_tag, _)| tag.cmp(other_tag));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::edit_feed_popup::parse_keywords;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_keywords_are_split_on_commas_and_trimmed() {
+        assert_eq!(
+            parse_keywords(" sponsored,Giveaway , ,, big news "),
+            vec!["sponsored", "Giveaway", "big news"]
+        );
+        assert!(parse_keywords("").is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use egui::load::{SizeHint, TexturePoll};
+use egui::{Image, Sense, TextureOptions, Ui, Vec2};
+use poll_promise::Promise;
+use std::collections::HashMap;
+
+const FAVICON_SIZE: f32 = 16.0;
+
+/// The favicons of the sites the feeds belong to, per host.
+/// Each favicon is requested only once.
+#[derive(Default)]
+pub struct Favicons {
+    favicons: HashMap<String, FaviconState>,
+}
+
+enum FaviconState {
+    Loading(Promise<ehttp::Result<ehttp::Response>>),
+    /// The uri the favicon is registered under with egui.
+    /// [None] if the site has no favicon, or it is in a format we can't show.
+    Loaded(Option<String>),
+}
+
+impl Favicons {
+    /// Shows the favicon of the host, or a placeholder if it is still loading or there is none.
+    pub fn show(&mut self, ui: &mut Ui, host: Option<&str>) {
+        let size = Vec2::splat(FAVICON_SIZE);
+
+        let texture = host
+            .and_then(|host| self.uri(ui.ctx(), host))
+            .and_then(|uri| {
+                match ui.ctx().try_load_texture(
+                    &uri,
+                    TextureOptions::LINEAR,
+                    SizeHint::Size(FAVICON_SIZE as u32, FAVICON_SIZE as u32),
+                ) {
+                    Ok(TexturePoll::Ready { texture }) => Some(texture),
+                    // Still decoding, or it could not be decoded.
+                    _ => None,
+                }
+            });
+
+        match texture {
+            Some(texture) => {
+                ui.add(Image::from_texture(texture).fit_to_exact_size(size));
+            }
+            None => {
+                let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+                ui.painter().circle_filled(
+                    rect.center(),
+                    FAVICON_SIZE / 4.0,
+                    ui.visuals().weak_text_color(),
+                );
+            }
+        }
+    }
+
+    /// Requests the favicon if that hasn't happened yet.
+    fn uri(&mut self, ctx: &egui::Context, host: &str) -> Option<String> {
+        let state = self.favicons.entry(host.to_string()).or_insert_with(|| {
+            let request = ehttp::Request::get(format!("../api/favicon?host={}", host));
+            let (sender, promise) = Promise::new();
+            let ctx = ctx.clone();
+            ehttp::fetch(request, move |response| {
+                // Wake up UI thread.
+                ctx.request_repaint();
+                sender.send(response)
+            });
+
+            FaviconState::Loading(promise)
+        });
+
+        if let FaviconState::Loading(promise) = state {
+            let loaded = match promise.ready()? {
+                Ok(response) if response.ok => {
+                    // The image loaders pick the format based on the extension of the uri.
+                    image_extension(response.content_type()).map(|extension| {
+                        let uri = format!("bytes://favicon/{}.{}", host, extension);
+                        ctx.include_bytes(uri.clone(), response.bytes.clone());
+                        uri
+                    })
+                }
+                _ => None,
+            };
+            *state = FaviconState::Loaded(loaded);
+        }
+
+        match state {
+            FaviconState::Loaded(uri) => uri.clone(),
+            FaviconState::Loading(_) => None,
+        }
+    }
+}
+
+/// The file extension of the image formats we can show.
+fn image_extension(content_type: Option<&str>) -> Option<&'static str> {
+    let mime = content_type?.split(';').next()?.trim();
+    match mime.to_ascii_lowercase().as_str() {
+        "image/png" => Some("png"),
+        "image/x-icon" | "image/vnd.microsoft.icon" => Some("ico"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        _ => None,
+    }
+}
@@ -1,11 +1,15 @@
 use crate::add_feed_popup::{AddFeedPopup, AddFeedPopupResponse};
 use crate::edit_feed_popup::{EditFeedPopup, EditFeedPopupResponse};
+use crate::favicons::Favicons;
+use crate::hyperlink::NewTabHyperlink;
 use crate::requests::Requests;
-use egui::collapsing_header::CollapsingState;
-use egui::{RichText, Ui};
-use rss_com_lib::message_body::FeedsFilter;
-use rss_com_lib::rss_feed::FeedInfo;
+use chrono::{DateTime, Local, Utc};
+use egui::collapsing_header::{paint_default_icon, CollapsingState};
+use egui::{Id, RichText, Stroke, Ui};
+use rss_com_lib::message_body::{CollectionId, FeedsFilter};
+use rss_com_lib::rss_feed::{FeedInfo, UpdateStatus};
 use rss_com_lib::Url;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Default)]
@@ -16,9 +20,16 @@ pub struct FeedListDisplay {
     feeds_without_tags: Vec<(Url, FeedInfo)>,
     /// A copy of all known tags. For quick access.
     known_tags: HashSet<String>,
+    /// The url and name of all feeds, sorted by name. For choosing a feed to merge another into.
+    feed_names: Vec<(Url, String)>,
     selection: FeedsFilter,
+    /// Only show the feeds for which the last update went wrong.
+    only_broken_feeds: bool,
     add_feed_popup: Option<AddFeedPopup>,
     edit_feed_popup: Option<EditFeedPopup>,
+    /// The collection the shown feeds are in.
+    collection_id: CollectionId,
+    favicons: Favicons,
 }
 
 impl FeedListDisplay {
@@ -26,10 +37,17 @@ impl FeedListDisplay {
         Default::default()
     }
 
-    pub fn update_feeds_info(&mut self, new_feeds: &HashMap<Url, FeedInfo>) {
+    /// Returns true if the selection was reset to [FeedsFilter::All], because the selected
+    /// feed or tag no longer exists.
+    pub fn update_feeds_info(&mut self, new_feeds: &HashMap<Url, FeedInfo>) -> bool {
         let mut feeds_by_tag: BTreeMap<String, Vec<(Url, FeedInfo)>> = BTreeMap::new();
         self.feeds_without_tags = Vec::new();
         self.known_tags = HashSet::new();
+        self.feed_names = new_feeds
+            .iter()
+            .map(|(url, info)| (url.clone(), info.name.clone()))
+            .collect();
+        self.feed_names.sort_by(|(_, a), (_, b)| a.cmp(b));
 
         // Collect all the feeds per tag.
         for (url, info) in new_feeds.iter() {
@@ -50,93 +68,145 @@ impl FeedListDisplay {
 
         // Sort the feeds per tag.
         for feeds in feeds_by_tag.values_mut() {
-            feeds.sort_by(|(_, this_info), (_, other_info)| this_info.name.cmp(&other_info.name));
+            feeds.sort_by(|(_, this_info), (_, other_info)| feed_order(this_info, other_info));
         }
         self.feeds_without_tags
-            .sort_by(|(_, this_info), (_, other_info)| this_info.name.cmp(&other_info.name));
+            .sort_by(|(_, this_info), (_, other_info)| feed_order(this_info, other_info));
 
         // Update selection
-        match &self.selection {
-            FeedsFilter::All => {
-                // Nothing to do.
-            }
-            FeedsFilter::Tag(tag) => {
-                if !feeds_by_tag.contains_key(tag) {
-                    // Tag has disappeared.
-                    self.selection = FeedsFilter::All;
-                }
-            }
-            FeedsFilter::Single(_) => {
-                // todo: Check if the url is still there.
-            }
+        let selection_exists = match &self.selection {
+            FeedsFilter::All => true,
+            FeedsFilter::Tag(tag) => feeds_by_tag.contains_key(tag),
+            FeedsFilter::Single(url) => new_feeds.contains_key(url),
+        };
+        if !selection_exists {
+            // Tag or feed has disappeared.
+            self.selection = FeedsFilter::All;
         }
 
         self.feed_tags = feeds_by_tag;
+
+        !selection_exists
     }
 
     pub fn current_selection(&self) -> FeedsFilter {
         self.selection.clone()
     }
 
+    pub fn set_selection(&mut self, selection: FeedsFilter) {
+        self.selection = selection;
+    }
+
+    /// Forgets the feeds of the previous collection, until [FeedListDisplay::update_feeds_info]
+    /// is called with the feeds of the new one.
+    pub fn set_collection(&mut self, collection_id: CollectionId) {
+        *self = FeedListDisplay {
+            only_broken_feeds: self.only_broken_feeds,
+            collection_id,
+            favicons: std::mem::take(&mut self.favicons),
+            ..Default::default()
+        };
+    }
+
     pub fn show(&mut self, ui: &mut Ui) -> FeedListDisplayResponse {
         let mut response = FeedListDisplayResponse::None;
 
         if ui.button("Add feed").clicked() && self.add_feed_popup.is_none() {
-            self.add_feed_popup = Some(AddFeedPopup::new(self.known_tags.clone()));
+            self.add_feed_popup = Some(AddFeedPopup::new(
+                self.known_tags.clone(),
+                self.collection_id,
+            ));
         }
 
+        ui.checkbox(&mut self.only_broken_feeds, "Show only broken feeds");
+
         ui.separator();
 
-        egui::ScrollArea::vertical().show(ui, |ui| {
-            if selectable_value(ui, self.selection == FeedsFilter::All, "All feeds") {
-                self.selection = FeedsFilter::All;
-                response = FeedListDisplayResponse::SelectionChanged;
-            }
+        let only_broken_feeds = self.only_broken_feeds;
+        let is_shown = |info: &FeedInfo| !only_broken_feeds || info.update_status.is_failed();
+        let list_id = ui.make_persistent_id("feed-list");
+        let ctx = ui.ctx().clone();
+        let rows = feed_list_rows(
+            &self.feeds_without_tags,
+            &self.feed_tags,
+            is_shown,
+            |group| {
+                CollapsingState::load_with_default_open(&ctx, list_id.with(group), false).is_open()
+            },
+        );
 
-            if !self.feeds_without_tags.is_empty() {
-                ui.collapsing("Untagged", |ui| {
-                    for (url, info) in self.feeds_without_tags.iter() {
-                        feed_info_display(
-                            ui,
-                            url,
-                            info,
-                            &mut response,
-                            &mut self.selection,
-                            &mut self.edit_feed_popup,
-                            &self.known_tags,
-                        );
+        // Accounts can have hundreds of feeds, so only the rows that are in view are shown.
+        // This needs every row to be the same height, so nothing in a row may wrap.
+        let row_height = ui.spacing().interact_size.y;
+        egui::ScrollArea::vertical().show_rows(ui, row_height, rows.len(), |ui, row_range| {
+            for row in &rows[row_range] {
+                match *row {
+                    FeedListRow::AllFeeds => {
+                        if selectable_value(ui, self.selection == FeedsFilter::All, "All feeds") {
+                            self.selection = FeedsFilter::All;
+                            response = FeedListDisplayResponse::SelectionChanged;
+                        }
                     }
-                });
-            }
+                    FeedListRow::Group(group) => {
+                        let mut state = CollapsingState::load_with_default_open(
+                            ui.ctx(),
+                            list_id.with(group),
+                            false,
+                        );
+                        ui.horizontal(|ui| {
+                            state.show_toggle_button(ui, paint_default_icon);
+                            match group {
+                                None => {
+                                    ui.label("Untagged");
+                                }
+                                Some(tag) => {
+                                    let tag_selected = match &self.selection {
+                                        FeedsFilter::Tag(selected_tag) => selected_tag == tag,
+                                        _ => false,
+                                    };
 
-            for (tag, feeds) in self.feed_tags.iter() {
-                let collapse_id = ui.make_persistent_id(tag);
-                CollapsingState::load_with_default_open(ui.ctx(), collapse_id, false)
-                    .show_header(ui, |ui| {
-                        let tag_selected = match &self.selection {
-                            FeedsFilter::Tag(selected_tag) => selected_tag == tag,
-                            _ => false,
-                        };
+                                    if selectable_value(ui, tag_selected, tag) {
+                                        self.selection = FeedsFilter::Tag(tag.clone());
 
-                        if selectable_value(ui, tag_selected, tag) {
-                            self.selection = FeedsFilter::Tag(tag.clone());
+                                        response = FeedListDisplayResponse::SelectionChanged;
+                                    }
+                                }
+                            }
+                        });
+                        state.store(ui.ctx());
+                    }
+                    FeedListRow::Feed {
+                        group,
+                        feeds,
+                        index,
+                    } => {
+                        let (url, info) = &feeds[index];
+                        let dropped = ui
+                            .horizontal(|ui| {
+                                ui.add_space(ui.spacing().indent);
+                                feed_info_display(
+                                    ui,
+                                    group.map_or("Untagged", String::as_str),
+                                    url,
+                                    info,
+                                    &mut response,
+                                    &mut self.selection,
+                                    &mut self.edit_feed_popup,
+                                    &self.known_tags,
+                                    &self.feed_names,
+                                    self.collection_id,
+                                    &mut self.favicons,
+                                )
+                            })
+                            .inner;
 
-                            response = FeedListDisplayResponse::SelectionChanged;
+                        if let Some(moved) = dropped {
+                            response = FeedListDisplayResponse::FeedsReordered(reorder_feeds(
+                                feeds, &moved, url,
+                            ));
                         }
-                    })
-                    .body(|ui| {
-                        for (url, info) in feeds {
-                            feed_info_display(
-                                ui,
-                                url,
-                                info,
-                                &mut response,
-                                &mut self.selection,
-                                &mut self.edit_feed_popup,
-                                &self.known_tags,
-                            );
-                        }
-                    });
+                    }
+                }
             }
         });
 
@@ -161,6 +231,9 @@ impl FeedListDisplay {
                     self.add_feed_popup = None;
                     response = FeedListPopupResponse::FeedAdded;
                 }
+                AddFeedPopupResponse::BulkFeedsAdded => {
+                    response = FeedListPopupResponse::FeedAdded;
+                }
             }
         }
 
@@ -177,6 +250,13 @@ impl FeedListDisplay {
 
                     response = FeedListPopupResponse::FeedInfoEdited(url, new_info);
                 }
+                EditFeedPopupResponse::ReadStateReset => {
+                    response = FeedListPopupResponse::ReadStateReset;
+                }
+                EditFeedPopupResponse::FeedsMerged => {
+                    self.edit_feed_popup = None;
+                    response = FeedListPopupResponse::FeedsMerged;
+                }
             }
         }
 
@@ -184,61 +264,425 @@ impl FeedListDisplay {
     }
 }
 
+/// A line in the feed list.
+enum FeedListRow<'a> {
+    AllFeeds,
+    /// The header of a tag, or of the untagged feeds if [None]. Can be collapsed.
+    Group(Option<&'a String>),
+    /// The feed at `index` in the `feeds` of the `group`.
+    Feed {
+        group: Option<&'a String>,
+        feeds: &'a [(Url, FeedInfo)],
+        index: usize,
+    },
+}
+
+/// The feed list as a flat list of rows, so only the rows that are in view have to be shown.
+/// Feeds for which `is_shown` returns false are left out, and so are groups without any shown
+/// feeds. The feeds of a group are only there if `is_open` returns true for the group.
+fn feed_list_rows<'a>(
+    feeds_without_tags: &'a [(Url, FeedInfo)],
+    feed_tags: &'a BTreeMap<String, Vec<(Url, FeedInfo)>>,
+    is_shown: impl Fn(&FeedInfo) -> bool,
+    is_open: impl Fn(Option<&String>) -> bool,
+) -> Vec<FeedListRow<'a>> {
+    let groups = std::iter::once((None, feeds_without_tags)).chain(
+        feed_tags
+            .iter()
+            .map(|(tag, feeds)| (Some(tag), feeds.as_slice())),
+    );
+
+    let mut rows = vec![FeedListRow::AllFeeds];
+    for (group, feeds) in groups {
+        let shown: Vec<usize> = feeds
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, info))| is_shown(info))
+            .map(|(index, _)| index)
+            .collect();
+        if shown.is_empty() {
+            continue;
+        }
+
+        rows.push(FeedListRow::Group(group));
+        if is_open(group) {
+            rows.extend(shown.into_iter().map(|index| FeedListRow::Feed {
+                group,
+                feeds,
+                index,
+            }));
+        }
+    }
+    rows
+}
+
+/// The feeds can be dragged by their handle, to reorder them within the `group` (a tag, or the
+/// untagged feeds). Returns the url of the feed that was dropped on this one, if any.
+#[allow(clippy::too_many_arguments)]
 fn feed_info_display(
     ui: &mut Ui,
+    group: &str,
     feed_url: &Url,
     info: &FeedInfo,
     response: &mut FeedListDisplayResponse,
     selection: &mut FeedsFilter,
     edit_feed_popup: &mut Option<EditFeedPopup>,
     known_tags: &HashSet<String>,
-) {
+    feed_names: &[(Url, String)],
+    collection_id: CollectionId,
+    favicons: &mut Favicons,
+) -> Option<Url> {
     let selected = match selection {
         FeedsFilter::Single(selected_url) => selected_url == feed_url,
         _ => false,
     };
 
-    ui.horizontal(|ui| {
-        match &info.last_update_result {
-            Ok(()) => {
-                ui.label("-");
+    let row = ui.horizontal(|ui| {
+        // The same feed can be in multiple tags, so the group is part of the id.
+        let drag_id = Id::new(("feed-drag", group, feed_url.as_str()));
+        ui.dnd_drag_source(drag_id, DraggedFeed(feed_url.clone()), |ui| {
+            ui.weak("☰");
+        })
+        .response
+        .on_hover_text("Drag to reorder");
+
+        match &info.update_status {
+            _ if !info.enabled => {
+                ui.weak("⏸")
+                    .on_hover_text("Paused, the feed is not updated");
+            }
+            UpdateStatus::NeverUpdated => {
+                ui.weak("○").on_hover_text("Not updated yet");
+            }
+            UpdateStatus::Ok { at } => {
+                let mut hover_text = format!("Updated {}", local_time_string(at));
+                let mut marker = RichText::new("-");
+                if let Some(diagnostics) = &info.last_parse_diagnostics {
+                    hover_text.push_str(&format!("\n{}", diagnostics.summary()));
+                    // The update did work, so this is a warning instead of an error.
+                    if diagnostics.has_problems() {
+                        marker = marker.color(ui.visuals().warn_fg_color);
+                    }
+                }
+                ui.label(marker).on_hover_text(hover_text);
             }
-            Err(message) => {
-                ui.label(RichText::new("?").color(ui.visuals().error_fg_color))
-                    .on_hover_text(message);
+            UpdateStatus::Failed { at, message, kind } => {
+                // Problems that probably go away by themselves are less alarming.
+                let (marker, color) = if kind.is_temporary() {
+                    ("⏱", ui.visuals().warn_fg_color)
+                } else {
+                    ("?", ui.visuals().error_fg_color)
+                };
+                ui.label(RichText::new(marker).color(color))
+                    .on_hover_text(format!(
+                        "Update failed {}:\n{}",
+                        local_time_string(at),
+                        message
+                    ));
             }
         }
 
-        ui.horizontal_wrapped(|ui| {
-            if selectable_value(ui, selected, &info.name) {
-                *selection = FeedsFilter::Single(feed_url.clone());
-                *response = FeedListDisplayResponse::SelectionChanged;
+        let site_url = info.home_url.as_ref().unwrap_or(feed_url);
+        favicons.show(ui, site_url.host());
+
+        let mut toggled = selected;
+        let name = ui
+            .toggle_value(&mut toggled, &info.name)
+            .on_hover_text("Right-click for more options");
+        if name.clicked() {
+            *selection = FeedsFilter::Single(feed_url.clone());
+            *response = FeedListDisplayResponse::SelectionChanged;
+        }
+
+        // The options are in a menu instead of next to the name, so the row keeps the same
+        // height as the others.
+        name.context_menu(|ui| {
+            if ui.button("Edit").clicked() {
+                if edit_feed_popup.is_none() {
+                    *edit_feed_popup = Some(EditFeedPopup::new(
+                        feed_url.clone(),
+                        info.clone(),
+                        known_tags.clone(),
+                        collection_id,
+                        feed_names,
+                    ));
+                }
+                ui.close_menu();
+            }
+
+            if let Some(home_url) = &info.home_url {
+                ui.add(NewTabHyperlink::from_label_and_url("Visit site", home_url));
             }
 
-            // Only show the edit buton if the feed is selected.
-            if selected && ui.button("Edit").clicked() && edit_feed_popup.is_none() {
-                *edit_feed_popup = Some(EditFeedPopup::new(
-                    feed_url.clone(),
-                    info.clone(),
-                    known_tags.clone(),
-                ));
+            if ui.button("🔄 Refresh").clicked() {
+                *response = FeedListDisplayResponse::RefreshFeed(feed_url.clone());
+                ui.close_menu();
             }
         });
     });
+
+    let row = row.response;
+    if row.dnd_hover_payload::<DraggedFeed>().is_some() {
+        ui.painter().rect_stroke(
+            row.rect,
+            2.0,
+            Stroke::new(1.0, ui.visuals().selection.stroke.color),
+        );
+    }
+
+    row.dnd_release_payload::<DraggedFeed>()
+        .map(|dragged| dragged.0.clone())
+        .filter(|dragged| dragged != feed_url)
+}
+
+/// What is dragged around when reordering the feed list.
+struct DraggedFeed(Url);
+
+/// Feeds with a `sort_index` first, in that order. Then the rest by name.
+fn feed_order(this: &FeedInfo, other: &FeedInfo) -> Ordering {
+    (this.sort_index.is_none(), this.sort_index, &this.name).cmp(&(
+        other.sort_index.is_none(),
+        other.sort_index,
+        &other.name,
+    ))
+}
+
+/// Moves the `moved` feed to the place of the `target` feed, and numbers the `feeds` in their
+/// new order. Returns only the feeds of which the `sort_index` changed.
+/// Returns nothing if either feed is not in the `feeds`.
+fn reorder_feeds(feeds: &[(Url, FeedInfo)], moved: &Url, target: &Url) -> Vec<(Url, FeedInfo)> {
+    let position = |url: &Url| feeds.iter().position(|(feed_url, _)| feed_url == url);
+    let (Some(from), Some(to)) = (position(moved), position(target)) else {
+        return vec![];
+    };
+
+    let mut order: Vec<&(Url, FeedInfo)> = feeds.iter().collect();
+    let feed = order.remove(from);
+    order.insert(to, feed);
+
+    order
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (url, info))| {
+            let sort_index = u32::try_from(index).ok();
+            (info.sort_index != sort_index).then(|| {
+                (
+                    url.clone(),
+                    FeedInfo {
+                        sort_index,
+                        ..info.clone()
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+fn local_time_string(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
 }
 
 pub enum FeedListDisplayResponse {
     None,
     SelectionChanged,
+    /// The user wants to fetch the latest entries of only this feed.
+    RefreshFeed(Url),
+    /// The user dragged a feed to another place. Contains the feeds that got a new `sort_index`.
+    FeedsReordered(Vec<(Url, FeedInfo)>),
 }
 
 pub enum FeedListPopupResponse {
     None,
-    FeedInfoEdited(Url, FeedInfo),
+    /// Boxed, because the info is much larger than the other variants.
+    FeedInfoEdited(Url, Box<FeedInfo>),
     FeedAdded,
+    /// The entries of a feed were marked unread, so the shown entries are outdated.
+    ReadStateReset,
+    /// A feed was merged into another one, so the feed list is outdated.
+    FeedsMerged,
 }
 
 /// A selectable value that will return true if it has been selected by the user.
 fn selectable_value(ui: &mut Ui, mut selected: bool, text: &str) -> bool {
     ui.toggle_value(&mut selected, text).clicked()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::feed_list_display::{feed_list_rows, reorder_feeds, FeedListDisplay, FeedListRow};
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::FeedsFilter;
+    use rss_com_lib::rss_feed::{FeedInfo, UpdateStatus};
+    use rss_com_lib::Url;
+    use rstest::rstest;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    #[rstest]
+    #[case(FeedsFilter::All, FeedsFilter::All, false)]
+    #[case(FeedsFilter::Tag("news".to_string()), FeedsFilter::Tag("news".to_string()), false)]
+    #[case(FeedsFilter::Tag("gone".to_string()), FeedsFilter::All, true)]
+    #[case(FeedsFilter::Single(Url::new("feed".to_string())), FeedsFilter::Single(Url::new("feed".to_string())), false)]
+    #[case(FeedsFilter::Single(Url::new("gone".to_string())), FeedsFilter::All, true)]
+    fn test_selection_is_reset_when_it_no_longer_exists(
+        #[case] selection: FeedsFilter,
+        #[case] expected_selection: FeedsFilter,
+        #[case] expected_reset: bool,
+    ) {
+        // Given
+        let mut display = FeedListDisplay::new();
+        display.set_selection(selection);
+        let feeds = HashMap::from([(
+            Url::new("feed".to_string()),
+            FeedInfo {
+                tags: HashSet::from(["news".to_string()]),
+                ..Default::default()
+            },
+        )]);
+
+        // When
+        let reset = display.update_feeds_info(&feeds);
+
+        // Then
+        assert_eq!(reset, expected_reset);
+        assert_eq!(display.current_selection(), expected_selection);
+    }
+
+    #[test]
+    fn test_feeds_with_sort_index_come_first() {
+        // Given
+        let feed = |name: &str, sort_index: Option<u32>| {
+            (
+                Url::new(name.to_string()),
+                FeedInfo {
+                    name: name.to_string(),
+                    sort_index,
+                    ..Default::default()
+                },
+            )
+        };
+        let feeds = HashMap::from([
+            feed("a", None),
+            feed("b", Some(1)),
+            feed("c", Some(0)),
+            feed("d", None),
+        ]);
+        let mut display = FeedListDisplay::new();
+
+        // When
+        display.update_feeds_info(&feeds);
+
+        // Then
+        let names: Vec<&str> = display
+            .feeds_without_tags
+            .iter()
+            .map(|(_, info)| info.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn test_feed_list_rows_leave_out_collapsed_and_hidden_feeds() {
+        // Given
+        let feed = |name: &str, failed: bool| {
+            (
+                Url::new(name.to_string()),
+                FeedInfo {
+                    name: name.to_string(),
+                    update_status: if failed {
+                        UpdateStatus::Failed {
+                            at: Default::default(),
+                            message: "".to_string(),
+                            kind: Default::default(),
+                        }
+                    } else {
+                        UpdateStatus::NeverUpdated
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+        let feeds_without_tags = vec![feed("untagged ok", false), feed("untagged broken", true)];
+        let feed_tags = BTreeMap::from([
+            ("closed".to_string(), vec![feed("closed broken", true)]),
+            ("ok only".to_string(), vec![feed("ok", false)]),
+            ("open".to_string(), vec![feed("open broken", true)]),
+        ]);
+
+        // When
+        let rows = feed_list_rows(
+            &feeds_without_tags,
+            &feed_tags,
+            |info| info.update_status.is_failed(),
+            |group| group.map(String::as_str) != Some("closed"),
+        );
+
+        // Then
+        let names: Vec<&str> = rows
+            .iter()
+            .map(|row| match row {
+                FeedListRow::AllFeeds => "All feeds",
+                FeedListRow::Group(group) => group.map_or("Untagged", String::as_str),
+                FeedListRow::Feed { feeds, index, .. } => feeds[*index].1.name.as_str(),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "All feeds",
+                "Untagged",
+                "untagged broken",
+                "closed",
+                "open",
+                "open broken"
+            ]
+        );
+    }
+
+    #[rstest]
+    // Moving down places the feed after the target.
+    #[case("a", "c", vec!["b", "c", "a"])]
+    // Moving up places the feed before the target.
+    #[case("c", "a", vec!["c", "a", "b"])]
+    #[case("a", "unknown", vec!["a", "b", "c"])]
+    fn test_reorder_feeds(#[case] moved: &str, #[case] target: &str, #[case] expected: Vec<&str>) {
+        // Given
+        let feeds: Vec<(Url, FeedInfo)> = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                (
+                    Url::new(name.to_string()),
+                    FeedInfo {
+                        name: name.to_string(),
+                        sort_index: Some(index as u32),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        // When
+        let changed = reorder_feeds(
+            &feeds,
+            &Url::new(moved.to_string()),
+            &Url::new(target.to_string()),
+        );
+
+        // Then
+        let mut new_order = feeds.clone();
+        for (url, info) in changed {
+            if let Some((_, existing)) = new_order.iter_mut().find(|(feed_url, _)| *feed_url == url)
+            {
+                *existing = info;
+            }
+        }
+        new_order.sort_by_key(|(_, info)| info.sort_index);
+        let names: Vec<&str> = new_order
+            .iter()
+            .map(|(_, info)| info.name.as_str())
+            .collect();
+        assert_eq!(names, expected);
+    }
+}
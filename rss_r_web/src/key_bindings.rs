@@ -0,0 +1,87 @@
+//! Which keys do what, for each of the [KeyBindings] the user can choose from.
+
+use egui::Key;
+use rss_com_lib::message_body::KeyBindings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    NextEntry,
+    PreviousEntry,
+    /// Opens the link of the selected entry in a new tab.
+    OpenEntry,
+    /// Marks the selected entry as read, or as unread if it was read.
+    ToggleRead,
+    ToggleSidepanel,
+    ToggleHelp,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 6] = [
+        KeyAction::NextEntry,
+        KeyAction::PreviousEntry,
+        KeyAction::OpenEntry,
+        KeyAction::ToggleRead,
+        KeyAction::ToggleSidepanel,
+        KeyAction::ToggleHelp,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeyAction::NextEntry => "Select the next entry",
+            KeyAction::PreviousEntry => "Select the previous entry",
+            KeyAction::OpenEntry => "Open the selected entry",
+            KeyAction::ToggleRead => "Mark the selected entry read or unread",
+            KeyAction::ToggleSidepanel => "Show or hide the feeds",
+            KeyAction::ToggleHelp => "Show or hide this help",
+        }
+    }
+}
+
+/// The key for the `action`, and how it is shown to the user.
+pub fn binding(key_bindings: KeyBindings, action: KeyAction) -> (Key, &'static str) {
+    match (key_bindings, action) {
+        (KeyBindings::VimStyle, KeyAction::NextEntry) => (Key::J, "j"),
+        (KeyBindings::VimStyle, KeyAction::PreviousEntry) => (Key::K, "k"),
+        (KeyBindings::VimStyle, KeyAction::OpenEntry) => (Key::O, "o"),
+        (KeyBindings::VimStyle, KeyAction::ToggleRead) => (Key::M, "m"),
+        (KeyBindings::ArrowStyle, KeyAction::NextEntry) => (Key::ArrowDown, "↓"),
+        (KeyBindings::ArrowStyle, KeyAction::PreviousEntry) => (Key::ArrowUp, "↑"),
+        (KeyBindings::ArrowStyle, KeyAction::OpenEntry) => (Key::Enter, "Enter"),
+        (KeyBindings::ArrowStyle, KeyAction::ToggleRead) => (Key::Space, "Space"),
+        // The same in both, these don't get in the way of anything.
+        (_, KeyAction::ToggleSidepanel) => (Key::B, "b"),
+        (_, KeyAction::ToggleHelp) => (Key::Questionmark, "?"),
+    }
+}
+
+/// The actions of which the key was pressed this frame.
+/// Should not be called while the user is typing, for example in the add feed popup.
+pub fn pressed_actions(ctx: &egui::Context, key_bindings: KeyBindings) -> Vec<KeyAction> {
+    ctx.input(|input| {
+        KeyAction::ALL
+            .into_iter()
+            .filter(|action| input.key_pressed(binding(key_bindings, *action).0))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key_bindings::{binding, KeyAction};
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::KeyBindings;
+    use rstest::rstest;
+    use std::collections::HashSet;
+
+    #[rstest]
+    #[case(KeyBindings::VimStyle)]
+    #[case(KeyBindings::ArrowStyle)]
+    fn test_every_action_has_its_own_key(#[case] key_bindings: KeyBindings) {
+        let keys: HashSet<_> = KeyAction::ALL
+            .into_iter()
+            .map(|action| binding(key_bindings, action).0)
+            .collect();
+
+        assert_eq!(keys.len(), KeyAction::ALL.len());
+    }
+}
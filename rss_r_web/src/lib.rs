@@ -4,8 +4,10 @@
 mod add_feed_popup;
 mod app;
 mod edit_feed_popup;
+mod favicons;
 mod feed_list_display;
 mod hyperlink;
+mod key_bindings;
 mod login;
 mod requests;
 mod rss_collection;
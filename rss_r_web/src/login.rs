@@ -10,9 +10,19 @@ pub struct LoginView {
     password: String,
     state: State,
     show_invalid_user_or_password_message: bool,
+    /// Whether the user got here because their session expired, instead of by logging out.
+    show_session_expired_message: bool,
 }
 
 impl LoginView {
+    /// Login view for when the server no longer accepted the session.
+    pub fn after_session_expired() -> Self {
+        Self {
+            show_session_expired_message: true,
+            ..Default::default()
+        }
+    }
+
     /// Returns `true` if the login is successful.
     pub fn show(&mut self, ui: &mut Ui, requests: &mut Requests) -> bool {
         match self.state {
@@ -43,6 +53,13 @@ impl LoginView {
                 false
             }
             State::UsernameAndPasswordLogin => {
+                if self.show_session_expired_message {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "Your session expired, please log in again",
+                    );
+                }
+
                 self.show_login_fields(ui, requests);
 
                 if requests.has_request(ApiEndpoint::Login) {
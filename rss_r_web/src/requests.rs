@@ -2,10 +2,23 @@ use crate::requests::HttpStatus::Other;
 use log::warn;
 use poll_promise::Promise;
 use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 pub struct Requests {
     promises: HashMap<ApiEndpoint, Promise<ehttp::Result<ehttp::Response>>>,
+    /// Hashes of the json bodies of the requests that are currently in flight.
+    /// Used to avoid sending the same request again while the first one hasn't returned yet.
+    in_flight_body_hashes: HashMap<ApiEndpoint, u64>,
+    /// Copies of the requests that are in flight, so they can be sent again if they fail.
+    sent_requests: HashMap<ApiEndpoint, ehttp::Request>,
+    /// The last response per endpoint that came with an `ETag`.
+    /// If the same request is sent again, the server can answer `304 Not Modified` instead of
+    /// sending the same response again.
+    cached_responses: HashMap<ApiEndpoint, CachedResponse>,
+    /// The last request that failed, if the user hasn't retried or dismissed it yet.
+    failed_request: Option<FailedRequest>,
     /// If a promise returns with a `401: Unauthenticated`, this will go false.
     authenticated: bool,
     /// Needed to queue a redraw on the gui upon receiving a response.
@@ -16,6 +29,10 @@ impl Requests {
     pub fn new(ctx: egui::Context) -> Self {
         Requests {
             promises: HashMap::new(),
+            in_flight_body_hashes: HashMap::new(),
+            sent_requests: HashMap::new(),
+            cached_responses: HashMap::new(),
+            failed_request: None,
             authenticated: false,
             context: ctx,
         }
@@ -29,6 +46,36 @@ impl Requests {
         self.authenticated = authenticated;
     }
 
+    /// The last request that did not return successfully.
+    pub fn failed_request(&self) -> Option<&FailedRequest> {
+        self.failed_request.as_ref()
+    }
+
+    /// Sends the last failed request again.
+    pub fn retry_failed_request(&mut self) {
+        if let Some(failed) = self.failed_request.take() {
+            self.add_request(failed.endpoint, failed.request);
+        }
+    }
+
+    pub fn dismiss_failed_request(&mut self) {
+        self.failed_request = None;
+    }
+
+    /// If the last failed request was for `endpoint`, takes it over from the general failure
+    /// banner, so it can be shown where the user made the request instead.
+    pub fn take_failure_message(&mut self, endpoint: ApiEndpoint) -> Option<String> {
+        if self
+            .failed_request
+            .as_ref()
+            .is_some_and(|failed| failed.endpoint == endpoint)
+        {
+            self.failed_request.take().map(|failed| failed.message)
+        } else {
+            None
+        }
+    }
+
     /// TODO (Wybe 2022-07-16): Add timeout
     pub fn poll(&self) {
         for promise in self.promises.values() {
@@ -57,16 +104,46 @@ impl Requests {
     /// Creates a new request for the given endpoint.
     /// Overwrites any request that currently exists for that endpoint.
     /// TODO (Wybe 2022-09-25): Allow multiple requests of the same type to be sent at the same time?
+    /// If an identical request for this endpoint is still in flight, nothing is sent.
     pub fn new_request_with_json_body(&mut self, endpoint: ApiEndpoint, body: impl Serialize) {
-        let mut request = endpoint.request_with_body(serde_json::to_vec(&body).unwrap());
+        let body = serde_json::to_vec(&body).unwrap();
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let body_hash = hasher.finish();
+
+        if self.has_request(endpoint)
+            && self.in_flight_body_hashes.get(&endpoint) == Some(&body_hash)
+        {
+            // The server is already working on this exact request.
+            return;
+        }
+
+        let mut request = endpoint.request_with_body(body);
         //TODO (Wybe 2022-07-16): Make this no longer magic strings, but constants somewhere.
         request
             .headers
             .insert("Content-Type".to_string(), "application/json".to_string());
+        if let Some(cached) = self
+            .cached_responses
+            .get(&endpoint)
+            .filter(|cached| cached.body_hash == body_hash)
+        {
+            request
+                .headers
+                .insert("If-None-Match".to_string(), cached.etag.clone());
+        }
         self.add_request(endpoint, request);
+
+        self.in_flight_body_hashes.insert(endpoint, body_hash);
     }
 
     fn add_request(&mut self, endpoint: ApiEndpoint, request: ehttp::Request) {
+        // Any request that was in flight for this endpoint is replaced.
+        self.in_flight_body_hashes.remove(&endpoint);
+
+        self.sent_requests.insert(endpoint, request.clone());
+
         let (sender, promise) = Promise::new();
         let ctx = self.context.clone();
         ehttp::fetch(request, move |response| {
@@ -85,8 +162,10 @@ impl Requests {
     }
 
     /// Returns `Some` if a request returned successfully, and clears the request.
+    /// If the request failed, it is remembered as the [Requests::failed_request].
     pub fn ready(&mut self, endpoint: ApiEndpoint) -> Option<Response> {
         let mut promise_handled = false;
+        let mut failure_message = None;
 
         let result = self.promises.get(&endpoint).and_then(|promise| {
             let ready = promise.ready();
@@ -97,7 +176,27 @@ impl Requests {
 
                         match status_code {
                             HttpStatus::Ok => {
-                                Response::Ok(response.text().unwrap_or("").to_string())
+                                let body = response.text().unwrap_or("").to_string();
+                                let body_hash = self.in_flight_body_hashes.get(&endpoint);
+                                if let (Some(etag), Some(body_hash)) =
+                                    (response.headers.get("etag"), body_hash)
+                                {
+                                    self.cached_responses.insert(
+                                        endpoint,
+                                        CachedResponse {
+                                            body_hash: *body_hash,
+                                            etag: etag.to_string(),
+                                            body: body.clone(),
+                                        },
+                                    );
+                                }
+                                Response::Ok(body)
+                            }
+                            // Nothing changed since the response we have, so that is used again.
+                            HttpStatus::NotModified
+                                if self.cached_responses.contains_key(&endpoint) =>
+                            {
+                                Response::Ok(self.cached_responses[&endpoint].body.clone())
                             }
                             HttpStatus::Unauthorized => {
                                 self.authenticated = false;
@@ -105,12 +204,26 @@ impl Requests {
                             }
                             _ => {
                                 warn!("Request `{:?}` return NOT OK: {:?}", endpoint, status_code);
+                                let mut message = format!(
+                                    "The server responded with status {} {}",
+                                    response.status, response.status_text
+                                );
+                                // For example which limit was reached.
+                                if let Some(reason) =
+                                    response.text().filter(|text| !text.trim().is_empty())
+                                {
+                                    message.push_str(&format!(": {}", reason.trim()));
+                                }
+                                failure_message = Some(message);
                                 Response::NotOk(status_code)
                             }
                         }
                     }
-                    // TODO (Wybe 2022-07-11): Handle errors. For example: the server isn't online.
-                    Err(_e) => Response::Error,
+                    Err(error) => {
+                        warn!("Request `{:?}` failed: {}", endpoint, error);
+                        failure_message = Some(format!("Could not reach the server: {}", error));
+                        Response::Error
+                    }
                 };
                 // We are done with this promise, so it can be cleaned up.
                 promise_handled = true;
@@ -122,23 +235,87 @@ impl Requests {
 
         if promise_handled {
             self.promises.remove(&endpoint);
+            self.in_flight_body_hashes.remove(&endpoint);
+            let request = self.sent_requests.remove(&endpoint);
+
+            if let (Some(message), Some(request)) = (failure_message, request) {
+                self.failed_request = Some(FailedRequest {
+                    endpoint,
+                    message,
+                    request,
+                });
+            } else if self
+                .failed_request
+                .as_ref()
+                .is_some_and(|failed| failed.endpoint == endpoint)
+            {
+                // The endpoint works again.
+                self.failed_request = None;
+            }
         }
 
         result
     }
 }
 
+/// See [Requests] `.cached_responses`.
+struct CachedResponse {
+    /// Hash of the json body of the request this response is for.
+    body_hash: u64,
+    etag: String,
+    body: String,
+}
+
+/// A request that did not return successfully.
+pub struct FailedRequest {
+    pub endpoint: ApiEndpoint,
+    /// Description of what went wrong, for display to the user.
+    pub message: String,
+    request: ehttp::Request,
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub enum ApiEndpoint {
+    /// Version of the server, to check whether this client still fits it.
+    Version,
     TestAuthCookie,
     Login,
     Logout,
     IsUrlAnRssFeed,
     AddFeed,
+    /// Add multiple feeds at once, from a list with one url per line.
+    ImportUrlList,
     /// List feeds and their entries.
     Feeds,
-    SetEntryRead,
+    /// All unread entries of the selected feeds, to open them at once.
+    /// Uses the same api as [ApiEndpoint::Feeds], but doesn't replace the request for the shown entries.
+    UnreadLinks,
+    /// Set the read state of one or more entries.
+    SetEntriesRead,
+    MarkAllRead,
+    /// Mark all entries of a feed as unread.
+    ResetRead,
+    /// Put an entry on the "read later" list, or take it off.
+    SetEntryReadLater,
+    /// Amount of unread entries over all feeds of a collection.
+    UnreadTotal,
     SetFeedInfo,
+    /// Saves the [FeedInfo](rss_com_lib::rss_feed::FeedInfo) `.sort_index` of a reordered feed.
+    /// Uses the same api as [ApiEndpoint::SetFeedInfo], so the edit feed popup doesn't get
+    /// these responses.
+    SetFeedOrder,
+    UserSettings,
+    SetUserSettings,
+    /// How far along the running "Update all feeds" is.
+    UpdateStatus,
+    /// List the collections of the user.
+    Collections,
+    CreateCollection,
+    DeleteCollection,
+    /// A link to the selected feeds, for people without an account.
+    CreateShareLink,
+    /// Remove a feed that is a duplicate of another one, keeping its read state.
+    MergeFeeds,
 }
 
 impl ApiEndpoint {
@@ -150,14 +327,28 @@ impl ApiEndpoint {
 
     pub fn request_with_body(&self, body: Vec<u8>) -> ehttp::Request {
         let endpoint = match self {
+            Self::Version => "version",
             Self::TestAuthCookie => "test_auth_cookie",
             Self::Login => "login",
             Self::Logout => "logout",
             Self::IsUrlAnRssFeed => "is_url_an_rss_feed",
             Self::AddFeed => "add_feed",
-            Self::Feeds => "feeds",
-            Self::SetEntryRead => "set_entry_read",
-            Self::SetFeedInfo => "set_feed_info",
+            Self::ImportUrlList => "import_url_list",
+            Self::Feeds | Self::UnreadLinks => "feeds",
+            Self::SetEntriesRead => "set_entries_read",
+            Self::MarkAllRead => "mark_all_read",
+            Self::ResetRead => "reset_read",
+            Self::SetEntryReadLater => "set_entry_read_later",
+            Self::UnreadTotal => "unread_total",
+            Self::SetFeedInfo | Self::SetFeedOrder => "set_feed_info",
+            Self::UserSettings => "user_settings",
+            Self::SetUserSettings => "set_user_settings",
+            Self::UpdateStatus => "update_status",
+            Self::Collections => "collections",
+            Self::CreateCollection => "create_collection",
+            Self::DeleteCollection => "delete_collection",
+            Self::CreateShareLink => "create_share_link",
+            Self::MergeFeeds => "merge_feeds",
         };
 
         ehttp::Request::post(format!("../api/{}", endpoint), body)
@@ -174,6 +365,7 @@ pub enum Response {
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum HttpStatus {
     Ok,
+    NotModified,
     Unauthorized,
     Other(u16),
 }
@@ -182,6 +374,7 @@ impl HttpStatus {
     pub fn from_u16(value: u16) -> Self {
         match value {
             200 => Self::Ok,
+            304 => Self::NotModified,
             401 => Self::Unauthorized,
             _ => Other(value),
         }
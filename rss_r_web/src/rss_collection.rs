@@ -1,34 +1,144 @@
 use crate::feed_list_display::{FeedListDisplay, FeedListDisplayResponse, FeedListPopupResponse};
 use crate::hyperlink::NewTabHyperlink;
+use crate::key_bindings::{binding, pressed_actions, KeyAction};
 use crate::requests::{ApiEndpoint, Requests, Response};
-use chrono::Local;
+use chrono::{DateTime, Duration, Local, Utc};
+use egui::output::OpenUrl;
 use egui::{Color32, RichText, Ui, Vec2};
 use rss_com_lib::message_body::{
-    AdditionalAction, ComFeedEntry, EntryTypeFilter, FeedsRequest, FeedsResponse,
-    SetEntryReadRequestAndResponse,
+    AdditionalAction, CollectionId, CollectionInfo, CollectionsResponse, ComFeedEntry,
+    CreateCollectionRequest, CreateShareLinkRequest, CreateShareLinkResponse,
+    DeleteCollectionRequestAndResponse, EntryCursor, EntryTypeFilter, FeedsFilter, FeedsRequest,
+    FeedsResponse, KeyBindings, MarkAllReadRequest, MarkAllReadResponse,
+    SetEntriesReadRequestAndResponse, SetEntryReadLaterRequestAndResponse,
+    SetEntryReadRequestAndResponse, SetFeedInfoRequestAndResponse, ShownEntries, SortOrder,
+    UnreadTotalRequest, UnreadTotalResponse, UpdateProgress, UpdateStatusResponse, UserSettings,
 };
 use rss_com_lib::rss_feed::{EntryKey, FeedInfo};
 use rss_com_lib::Url;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 
 const SIDEPANEL_COLLAPSE_WIDTH: f32 = 900.0;
-const DEFAULT_ENTRY_REQUEST_AMOUNT: usize = 30;
+/// Below this width of the entry list, [EntryColumns::compact] is used.
+const COMPACT_ENTRIES_WIDTH: f32 = 700.0;
+/// Part of the width of the entry list that titles can take up with [UserSettings] `.wrap_titles`.
+const WRAPPED_TITLE_WIDTH_FRACTION: f32 = 0.6;
+/// Opening more tabs than this at once has to be confirmed first.
+const OPEN_ALL_UNREAD_CONFIRMATION_AMOUNT: usize = 10;
+/// The choices for [UserSettings] `.entries_per_request`.
+const ENTRIES_PER_REQUEST_CHOICES: [Option<usize>; 4] = [Some(25), Some(50), Some(100), None];
+/// The choices for [UserSettings] `.auto_refresh_minutes`.
+const AUTO_REFRESH_CHOICES: [Option<u64>; 4] = [None, Some(5), Some(15), Some(30)];
+/// How often the progress of "Update all feeds" is requested while it runs.
+const UPDATE_STATUS_INTERVAL_SECONDS: f64 = 1.0;
+/// How long share links keep working.
+const SHARE_LINK_VALID_DAYS: u32 = 7;
+
+/// Limits the shown entries to recently published ones, independent of the feed selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum RecencyFilter {
+    #[default]
+    Any,
+    /// Published since local midnight.
+    Today,
+    /// Published in the last 7 days.
+    ThisWeek,
+}
+
+impl RecencyFilter {
+    /// Calculated at the moment of the request, so "today" moves along when the page stays open.
+    fn published_after(&self) -> Option<DateTime<Utc>> {
+        match self {
+            RecencyFilter::Any => None,
+            RecencyFilter::Today => Local::now()
+                .date_naive()
+                .and_hms_opt(0, 0, 0)
+                .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+                .map(|midnight| midnight.with_timezone(&Utc)),
+            RecencyFilter::ThisWeek => Some(Utc::now() - Duration::days(7)),
+        }
+    }
+}
 
 /// Stores info about the rss feeds the user is following.
 /// Is updated by information received from the server.
 pub struct RssDisplay {
+    /// The collection that is shown. All requests are for this collection.
+    collection_id: CollectionId,
+    /// All collections of the user, as received from the server.
+    collections: Vec<CollectionInfo>,
+    /// Name input for creating a new collection.
+    new_collection_name: String,
     feeds_info: HashMap<Url, FeedInfo>,
     feeds_display: FeedListDisplay,
     /// Entries we have recieved from the server, based on the selection in the feeds_display.
     feed_entries: Vec<DisplayFeedEntry>,
+    /// Index in `feed_entries` of the entry that was selected with the keyboard.
+    selected_entry: Option<usize>,
+    /// Whether the entry list should scroll to the `selected_entry`, because it just changed.
+    scroll_to_selected: bool,
+    /// The rows of the entry list that were shown in the last frame.
+    visible_rows: Range<usize>,
+    /// Whether the keyboard shortcuts are listed.
+    show_key_help: bool,
     /// How many feed entries we have requested last request.
     requested_entry_amount: usize,
-    /// How many feed entries are available on the server.
+    /// How many feed entries are available on the server. Only for display, this is lowered
+    /// when entries disappear from the view, so it can be off from what the server has.
     available_entry_amount: usize,
-    /// Whether or not to request feed entries that have already been read.
-    show_read_entries: bool,
+    /// Whether the server has more entries for the current selection than we have.
+    has_more_entries: bool,
+    /// How many unread entries there are over all feeds of the collection, regardless of the selection.
+    total_unread: usize,
+    /// How many entries there are over all feeds of the collection, regardless of the selection.
+    total_entries: usize,
+    /// Where to continue when requesting more entries.
+    /// [None] if there are no more entries to request.
+    next_cursor: Option<EntryCursor>,
+    /// Whether entries that are in multiple feeds should only be shown once.
+    hide_duplicate_entries: bool,
+    recency: RecencyFilter,
+    /// Whether the [ApiEndpoint::Feeds] request in flight is for updating all feeds.
+    /// That takes a while, so the user gets to see that it is still running.
+    updating_all_feeds: bool,
+    /// The last progress the server reported for "Update all feeds".
+    /// [None] before the first report arrives.
+    update_progress: Option<UpdateProgress>,
+    /// When the progress was last requested, in egui time.
+    last_update_status_request: f64,
+    /// Whether the "read later" list of all feeds is shown, instead of the selected feeds.
+    show_read_later: bool,
+    /// Read state changes that are not sent yet. They are sent together in one request,
+    /// once the previous one has returned.
+    pending_read_changes: SetEntriesReadRequestAndResponse,
+    /// The entries that the last "mark all read" changed, so it can be undone.
+    /// Empty if there is nothing to undo.
+    marked_all_read: SetEntriesReadRequestAndResponse,
+    /// How many entries "mark all read" would mark over all feeds, waiting for the user to
+    /// confirm. [None] if there is nothing to confirm.
+    mark_all_read_to_confirm: Option<usize>,
+    /// Changes to the "read later" list that are not sent yet. They are sent one at a time.
+    pending_read_later_changes: VecDeque<SetEntryReadLaterRequestAndResponse>,
+    /// Feeds that got a new place in the feed list, that are not saved yet. They are sent one
+    /// at a time.
+    pending_feed_order_changes: VecDeque<SetFeedInfoRequestAndResponse>,
+    /// Unread entries that "open all unread" found, waiting for the user to confirm opening them.
+    /// Empty if there is nothing to confirm.
+    links_to_confirm: Vec<ComFeedEntry>,
+    /// Entries that still need to be opened in a new tab. Egui opens one url per frame.
+    links_to_open: VecDeque<ComFeedEntry>,
+    /// The share link that was created last, or why it could not be created.
+    /// [None] if the share link popup is closed.
+    share_link: Option<Result<CreateShareLinkResponse, String>>,
+    /// Preferences of the user, as stored on the server.
+    settings: UserSettings,
     /// Whether to show the side panel with the feed list or not.
     open_sidepanel: bool,
+    /// When the last entries were received, in egui time (seconds since the app started).
+    /// The auto refresh waits for its interval after this, so it never comes right after
+    /// another request.
+    last_entries_received: f64,
     /// Previous size of the web page
     /// used to determine when the size changes.
     previous_page_size: Vec2,
@@ -40,67 +150,524 @@ impl RssDisplay {
         let open_sidepanel = page_size.x >= SIDEPANEL_COLLAPSE_WIDTH;
 
         RssDisplay {
+            collection_id: CollectionId::default(),
+            collections: vec![],
+            new_collection_name: "".to_string(),
             feeds_info: HashMap::new(),
             feeds_display: FeedListDisplay::new(),
             feed_entries: vec![],
-            requested_entry_amount: DEFAULT_ENTRY_REQUEST_AMOUNT,
+            selected_entry: None,
+            scroll_to_selected: false,
+            visible_rows: 0..0,
+            show_key_help: false,
+            requested_entry_amount: UserSettings::default().entry_request_amount(),
             available_entry_amount: 0,
-            show_read_entries: false,
+            has_more_entries: false,
+            total_unread: 0,
+            total_entries: 0,
+            next_cursor: None,
+            hide_duplicate_entries: false,
+            recency: RecencyFilter::Any,
+            updating_all_feeds: false,
+            update_progress: None,
+            last_update_status_request: 0.0,
+            show_read_later: false,
+            pending_read_changes: vec![],
+            marked_all_read: vec![],
+            pending_read_later_changes: VecDeque::new(),
+            pending_feed_order_changes: VecDeque::new(),
+            mark_all_read_to_confirm: None,
+            links_to_confirm: vec![],
+            links_to_open: VecDeque::new(),
+            share_link: None,
+            settings: UserSettings::default(),
             open_sidepanel,
+            last_entries_received: 0.0,
             previous_page_size: page_size,
         }
     }
 
+    pub fn current_selection(&self) -> FeedsFilter {
+        self.feeds_display.current_selection()
+    }
+
+    /// Call this before [RssDisplay::on_login], so the first request is for the given selection.
+    pub fn set_selection(&mut self, selection: FeedsFilter) {
+        self.settings.last_selection = selection.clone();
+        self.feeds_display.set_selection(selection);
+    }
+
+    /// Request for the entries of the selected feeds, using the current display settings.
+    /// Or for the "read later" list of all feeds, if that is shown.
+    fn feeds_request(
+        &self,
+        amount: usize,
+        after: Option<EntryCursor>,
+        additional_action: AdditionalAction,
+    ) -> FeedsRequest {
+        let (filter, entry_filter) = if self.show_read_later {
+            (FeedsFilter::All, EntryTypeFilter::ReadLater)
+        } else {
+            (
+                self.feeds_display.current_selection(),
+                self.settings.shown_entries.entry_filter(),
+            )
+        };
+
+        FeedsRequest {
+            collection_id: self.collection_id,
+            filter,
+            entry_filter,
+            amount,
+            after,
+            dedupe: self.hide_duplicate_entries,
+            published_after: self.recency.published_after(),
+            sort_order: self.settings.sort_order,
+            additional_action,
+        }
+    }
+
     pub fn show_feeds_button(&mut self, ui: &mut Ui) {
-        ui.toggle_value(&mut self.open_sidepanel, "Feeds");
+        ui.toggle_value(&mut self.open_sidepanel, "Feeds")
+            .on_hover_text("Show or hide the feeds (b)");
+    }
+
+    pub fn toggle_sidepanel(&mut self) {
+        self.open_sidepanel = !self.open_sidepanel;
+    }
+
+    /// Does what the keys pressed this frame are bound to, in the [UserSettings] `.keybindings`.
+    /// Should not be called while the user is typing, for example in the add feed popup.
+    pub fn handle_key_presses(&mut self, ctx: &egui::Context) {
+        for action in pressed_actions(ctx, self.settings.keybindings) {
+            match action {
+                KeyAction::NextEntry => {
+                    let last = self.feed_entries.len().saturating_sub(1);
+                    self.selected_entry =
+                        Some(self.selected_entry.map_or(0, |index| (index + 1).min(last)));
+                    self.scroll_to_selected = true;
+                }
+                KeyAction::PreviousEntry => {
+                    self.selected_entry = Some(
+                        self.selected_entry
+                            .map_or(0, |index| index.saturating_sub(1)),
+                    );
+                    self.scroll_to_selected = true;
+                }
+                KeyAction::OpenEntry => {
+                    let Some(entry) = self.selected_entry() else {
+                        continue;
+                    };
+                    let Some(link) = &entry.link else {
+                        continue;
+                    };
+                    let url = link.clone_string();
+                    let mark_read = !entry.read && self.settings.mark_read_on_open;
+                    let read_request = entry.set_read_request(self.collection_id, true);
+
+                    ctx.output_mut(|output| output.open_url = Some(OpenUrl { url, new_tab: true }));
+                    if mark_read {
+                        self.pending_read_changes.push(read_request);
+                    }
+                }
+                KeyAction::ToggleRead => {
+                    if let Some(entry) = self.selected_entry() {
+                        let read_request = entry.set_read_request(self.collection_id, !entry.read);
+                        self.pending_read_changes.push(read_request);
+                    }
+                }
+                KeyAction::ToggleSidepanel => self.toggle_sidepanel(),
+                KeyAction::ToggleHelp => self.show_key_help = !self.show_key_help,
+            }
+        }
+    }
+
+    fn selected_entry(&self) -> Option<&DisplayFeedEntry> {
+        self.selected_entry
+            .and_then(|index| self.feed_entries.get(index))
+    }
+
+    /// Requests the shown entries again every [UserSettings] `.auto_refresh_minutes`.
+    /// Only what the server already has is requested, the feeds themselves are not updated.
+    pub fn auto_refresh(&mut self, ctx: &egui::Context, requests: &mut Requests) {
+        let now = ctx.input(|input| input.time);
+        let Some(wait_seconds) = seconds_until_auto_refresh(
+            self.settings.auto_refresh_minutes,
+            self.last_entries_received,
+            now,
+        ) else {
+            return;
+        };
+
+        if wait_seconds > 0.0 {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(wait_seconds));
+        } else if !requests.has_request(ApiEndpoint::Feeds) {
+            // Counts as received, so a failing request isn't sent again every frame.
+            self.last_entries_received = now;
+            self.refresh_entries(requests);
+        }
+    }
+
+    /// Requests the progress of "Update all feeds" every [UPDATE_STATUS_INTERVAL_SECONDS],
+    /// and keeps the last one that arrived.
+    fn poll_update_status(&mut self, ctx: &egui::Context, requests: &mut Requests) {
+        if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::UpdateStatus) {
+            if let Ok(status) = serde_json::from_str::<UpdateStatusResponse>(&body) {
+                self.update_progress = status;
+            }
+        }
+
+        let now = ctx.input(|input| input.time);
+        let wait_seconds = self.last_update_status_request + UPDATE_STATUS_INTERVAL_SECONDS - now;
+        if wait_seconds > 0.0 {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(wait_seconds));
+        } else if !requests.has_request(ApiEndpoint::UpdateStatus) {
+            self.last_update_status_request = now;
+            requests.new_request_without_body(ApiEndpoint::UpdateStatus);
+        }
+    }
+
+    pub fn total_unread(&self) -> usize {
+        self.total_unread
+    }
+
+    pub fn show_unread_total(&self, ui: &mut Ui) {
+        let text = RichText::new(format!("{} unread", self.total_unread));
+        if self.total_unread > 0 {
+            ui.label(text.strong());
+        } else {
+            ui.label(text);
+        }
+    }
+
+    /// One-click filters for catching up. Clicking an active filter turns it off again.
+    pub fn show_quick_filters(&mut self, ui: &mut Ui, requests: &mut Requests) {
+        let last_recency = self.recency;
+
+        for (recency, text) in [
+            (RecencyFilter::Today, "Today"),
+            (RecencyFilter::ThisWeek, "This week"),
+        ] {
+            if ui.selectable_label(self.recency == recency, text).clicked() {
+                self.recency = if self.recency == recency {
+                    RecencyFilter::Any
+                } else {
+                    recency
+                };
+            }
+        }
+
+        if last_recency != self.recency {
+            requests.new_request_with_json_body(
+                ApiEndpoint::Feeds,
+                self.feeds_request(self.requested_entry_amount, None, AdditionalAction::None),
+            );
+            self.available_entry_amount = 0;
+            self.has_more_entries = false;
+        }
+
+        if ui
+            .selectable_label(
+                self.settings.shown_entries == ShownEntries::Unread,
+                "Unread",
+            )
+            .clicked()
+        {
+            let shown_entries = match self.settings.shown_entries {
+                ShownEntries::Unread => ShownEntries::All,
+                _ => ShownEntries::Unread,
+            };
+            let settings = UserSettings {
+                shown_entries,
+                ..self.settings.clone()
+            };
+            requests.new_request_with_json_body(ApiEndpoint::SetUserSettings, &settings);
+            self.on_settings_changed(settings, requests);
+        }
+    }
+
+    /// Lets the user switch between, create, and delete collections.
+    pub fn show_collection_switcher(&mut self, ui: &mut Ui, requests: &mut Requests) {
+        if requests.has_request(ApiEndpoint::Collections) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::Collections) {
+                if let Ok(collections) = serde_json::from_str::<CollectionsResponse>(&body) {
+                    self.collections = collections;
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::CreateCollection) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::CreateCollection) {
+                if let Ok(info) = serde_json::from_str::<CollectionInfo>(&body) {
+                    let id = info.id;
+                    self.collections.push(info);
+                    self.switch_collection(id, requests);
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::DeleteCollection) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::DeleteCollection) {
+                if let Ok(response) =
+                    serde_json::from_str::<DeleteCollectionRequestAndResponse>(&body)
+                {
+                    self.collections.retain(|info| info.id != response.id);
+                    if self.collection_id == response.id {
+                        self.switch_collection(CollectionId::default(), requests);
+                    }
+                }
+            }
+        }
+
+        let current_name = self
+            .collections
+            .iter()
+            .find(|info| info.id == self.collection_id)
+            .map(|info| info.name.clone())
+            .unwrap_or_default();
+
+        let mut selected_id = self.collection_id;
+        egui::ComboBox::from_id_source("collection-switcher")
+            .selected_text(&current_name)
+            .show_ui(ui, |ui| {
+                for info in &self.collections {
+                    ui.selectable_value(&mut selected_id, info.id, &info.name);
+                }
+            });
+        if selected_id != self.collection_id {
+            self.switch_collection(selected_id, requests);
+        }
+
+        ui.menu_button("Collections", |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_collection_name)
+                        .hint_text("New collection"),
+                );
+                let can_create = !self.new_collection_name.trim().is_empty()
+                    && !requests.has_request(ApiEndpoint::CreateCollection);
+                if ui
+                    .add_enabled(can_create, egui::Button::new("Create"))
+                    .clicked()
+                {
+                    requests.new_request_with_json_body(
+                        ApiEndpoint::CreateCollection,
+                        CreateCollectionRequest {
+                            name: self.new_collection_name.trim().to_string(),
+                        },
+                    );
+                    self.new_collection_name.clear();
+                    ui.close_menu();
+                }
+            });
+
+            // The default collection can't be deleted.
+            if self.collection_id != CollectionId::default()
+                && ui
+                    .button(format!("Delete `{}` and all its feeds", current_name))
+                    .clicked()
+            {
+                requests.new_request_with_json_body(
+                    ApiEndpoint::DeleteCollection,
+                    DeleteCollectionRequestAndResponse {
+                        id: self.collection_id,
+                    },
+                );
+                ui.close_menu();
+            }
+        });
     }
 
+    /// Shows the feeds of another collection. The selection is reset, because the selected
+    /// feeds or tags are from the previous collection.
+    fn switch_collection(&mut self, collection_id: CollectionId, requests: &mut Requests) {
+        self.collection_id = collection_id;
+        self.feeds_display.set_collection(collection_id);
+        self.feeds_info.clear();
+        self.feed_entries.clear();
+        self.selected_entry = None;
+        self.total_unread = 0;
+        self.total_entries = 0;
+        self.next_cursor = None;
+        self.pending_read_changes.clear();
+        self.pending_read_later_changes.clear();
+        self.pending_feed_order_changes.clear();
+        self.marked_all_read.clear();
+        self.mark_all_read_to_confirm = None;
+        self.links_to_confirm.clear();
+        self.share_link = None;
+
+        if self.settings.last_selection != FeedsFilter::All {
+            self.settings.last_selection = FeedsFilter::All;
+            requests.new_request_with_json_body(ApiEndpoint::SetUserSettings, &self.settings);
+        }
+
+        self.requested_entry_amount = self.settings.entry_request_amount();
+        requests.new_request_with_json_body(
+            ApiEndpoint::Feeds,
+            self.feeds_request(
+                self.requested_entry_amount,
+                None,
+                AdditionalAction::IncludeFeedsInfo,
+            ),
+        );
+        self.available_entry_amount = 0;
+        self.has_more_entries = false;
+    }
+
+    /// Shows the add and edit feed popups, and handles what they return.
+    /// Must be called every frame, also when no popup is open, because the popups wait for
+    /// responses of requests they sent. [RssApp](crate::app::RssApp) calls it right after
+    /// [RssDisplay::show_feed_list].
     pub fn handle_popups(&mut self, ctx: &egui::Context, requests: &mut Requests) {
+        let key_bindings = self.settings.keybindings;
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut self.show_key_help)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("key-help-grid").show(ui, |ui| {
+                    for action in KeyAction::ALL {
+                        ui.monospace(binding(key_bindings, action).1);
+                        ui.label(action.description());
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(unread) = self.mark_all_read_to_confirm {
+            let mut confirm = false;
+            let mut cancel = false;
+            egui::Window::new("Mark all read")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "This will mark {} entries of all feeds read. Continue?",
+                        with_thousands_separators(unread)
+                    ));
+                    ui.horizontal(|ui| {
+                        confirm = ui.button("Mark read").clicked();
+                        cancel = ui.button("Cancel").clicked();
+                    });
+                });
+
+            if confirm {
+                self.mark_all_read_to_confirm = None;
+                requests.new_request_with_json_body(
+                    ApiEndpoint::MarkAllRead,
+                    MarkAllReadRequest {
+                        collection_id: self.collection_id,
+                        filter: FeedsFilter::All,
+                        confirm: true,
+                    },
+                );
+            } else if cancel {
+                self.mark_all_read_to_confirm = None;
+            }
+        }
+
+        if let Some(share_link) = &self.share_link {
+            let mut close = false;
+            egui::Window::new("Share link")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    match share_link {
+                        Ok(link) => {
+                            ui.label("Anyone with this link can download the names and urls of the selected feeds. Not their entries.");
+                            ui.horizontal(|ui| {
+                                ui.add(egui::TextEdit::singleline(&mut link.url.as_str()));
+                                if ui.button("Copy").clicked() {
+                                    ui.output_mut(|output| output.copied_text = link.url.clone());
+                                }
+                            });
+                            ui.label(format!(
+                                "Works until {}",
+                                link.expires
+                                    .with_timezone(&Local)
+                                    .format("%Y-%m-%d %H:%M")
+                            ));
+                        }
+                        Err(message) => {
+                            ui.label(message);
+                        }
+                    }
+                    close = ui.button("Close").clicked();
+                });
+
+            if close {
+                self.share_link = None;
+            }
+        }
+
+        if !self.links_to_confirm.is_empty() {
+            let mut open = false;
+            let mut cancel = false;
+            egui::Window::new("Open all unread")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Open {} entries in new tabs?",
+                        self.links_to_confirm.len()
+                    ));
+                    ui.label("The browser might block some of them as popups.");
+                    ui.horizontal(|ui| {
+                        open = ui.button("Open").clicked();
+                        cancel = ui.button("Cancel").clicked();
+                    });
+                });
+
+            if open {
+                let entries = std::mem::take(&mut self.links_to_confirm);
+                self.open_entries(entries);
+            } else if cancel {
+                self.links_to_confirm.clear();
+            }
+        }
+
         let response = self.feeds_display.handle_popups(ctx, requests);
 
         match response {
             FeedListPopupResponse::None => {} // Nothing to do.
             FeedListPopupResponse::FeedInfoEdited(url, new_info) => {
+                let mut keywords_changed = false;
                 if let Some(feed) = self.feeds_info.get_mut(&url) {
-                    *feed = new_info;
+                    // The server marks entries read and highlights them based on the keywords,
+                    // so the shown entries are outdated when those change.
+                    keywords_changed = feed.mute_keywords != new_info.mute_keywords
+                        || feed.highlight_keywords != new_info.highlight_keywords;
+                    *feed = *new_info;
                 }
 
-                self.feeds_display.update_feeds_info(&self.feeds_info);
+                if self.feeds_display.update_feeds_info(&self.feeds_info) {
+                    self.on_feed_selection_changed(requests);
+                } else if keywords_changed {
+                    self.refresh_entries(requests);
+                }
             }
-            FeedListPopupResponse::FeedAdded => {
+            FeedListPopupResponse::FeedAdded | FeedListPopupResponse::FeedsMerged => {
                 requests.new_request_with_json_body(
                     ApiEndpoint::Feeds,
-                    FeedsRequest {
-                        filter: self.feeds_display.current_selection(),
-                        entry_filter: if self.show_read_entries {
-                            EntryTypeFilter::All
-                        } else {
-                            EntryTypeFilter::UnreadOnly
-                        },
-                        amount: self.requested_entry_amount,
-                        additional_action: AdditionalAction::IncludeFeedsInfo,
-                    },
+                    self.feeds_request(
+                        self.requested_entry_amount,
+                        None,
+                        AdditionalAction::IncludeFeedsInfo,
+                    ),
                 );
                 self.available_entry_amount = 0;
+                self.has_more_entries = false;
             }
+            FeedListPopupResponse::ReadStateReset => self.refresh_entries(requests),
         }
     }
 
     pub fn show_feed_list(&mut self, ctx: &egui::Context, requests: &mut Requests) {
         let page_size = ctx.screen_rect().size();
 
-        if page_size.x < SIDEPANEL_COLLAPSE_WIDTH
-            && self.previous_page_size.x >= SIDEPANEL_COLLAPSE_WIDTH
-        {
-            // Went below the collapse size.
-            self.open_sidepanel = false;
-        } else if page_size.x >= SIDEPANEL_COLLAPSE_WIDTH
-            && self.previous_page_size.x < SIDEPANEL_COLLAPSE_WIDTH
-        {
-            // Went above the collapse size.
-            self.open_sidepanel = true;
-        }
-
+        self.open_sidepanel =
+            auto_collapse_sidepanel(self.open_sidepanel, self.previous_page_size.x, page_size.x);
         self.previous_page_size = page_size;
 
         if !self.open_sidepanel {
@@ -108,54 +675,248 @@ impl RssDisplay {
         }
 
         egui::SidePanel::left("side-panel").show(ctx, |ui| {
-            let last_show_read_entries = self.show_read_entries;
-            ui.checkbox(&mut self.show_read_entries, "Show read entries");
+            egui::TopBottomPanel::bottom("side-panel-footer").show_inside(ui, |ui| {
+                ui.label(format!(
+                    "{} feeds · {} entries · {} unread",
+                    with_thousands_separators(self.feeds_info.len()),
+                    with_thousands_separators(self.total_entries),
+                    with_thousands_separators(self.total_unread),
+                ));
+            });
+
+            let mut settings = self.settings.clone();
+            egui::ComboBox::from_label("Shown entries")
+                .selected_text(settings.shown_entries.label())
+                .show_ui(ui, |ui| {
+                    for choice in ShownEntries::ALL {
+                        ui.selectable_value(&mut settings.shown_entries, choice, choice.label());
+                    }
+                });
+            egui::ComboBox::from_label("Sort by")
+                .selected_text(settings.sort_order.label())
+                .show_ui(ui, |ui| {
+                    for choice in SortOrder::ALL {
+                        ui.selectable_value(&mut settings.sort_order, choice, choice.label());
+                    }
+                })
+                .response
+                .on_hover_text("\"Last activity\" also counts when an entry was edited");
+            let last_hide_duplicate_entries = self.hide_duplicate_entries;
+            ui.checkbox(&mut self.hide_duplicate_entries, "Hide duplicate entries");
 
-            if last_show_read_entries != self.show_read_entries {
+            if last_hide_duplicate_entries != self.hide_duplicate_entries {
                 requests.new_request_with_json_body(
                     ApiEndpoint::Feeds,
-                    FeedsRequest {
-                        filter: self.feeds_display.current_selection(),
-                        entry_filter: if self.show_read_entries {
-                            EntryTypeFilter::All
-                        } else {
-                            EntryTypeFilter::UnreadOnly
-                        },
-                        amount: self.requested_entry_amount,
-                        additional_action: AdditionalAction::None,
-                    },
+                    self.feeds_request(self.requested_entry_amount, None, AdditionalAction::None),
                 );
                 self.available_entry_amount = 0;
+                self.has_more_entries = false;
             }
 
-            if ui.button("Update all feeds").clicked() {
+            if ui
+                .toggle_value(&mut self.show_read_later, "Read later")
+                .on_hover_text("Show the entries you marked to read later, of all feeds")
+                .changed()
+            {
+                self.on_feed_selection_changed(requests);
+            }
+
+            ui.checkbox(&mut settings.mark_read_on_open, "Mark read when opening");
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Keys")
+                    .selected_text(settings.keybindings.label())
+                    .show_ui(ui, |ui| {
+                        for choice in KeyBindings::ALL {
+                            ui.selectable_value(&mut settings.keybindings, choice, choice.label());
+                        }
+                    });
+                ui.toggle_value(&mut self.show_key_help, "?")
+                    .on_hover_text("List the keyboard shortcuts");
+            });
+            ui.checkbox(&mut settings.wrap_titles, "Wrap long titles")
+                .on_hover_text("Otherwise the middle of long titles is left out");
+            egui::ComboBox::from_label("Entries per request")
+                .selected_text(entries_per_request_text(settings.entries_per_request))
+                .show_ui(ui, |ui| {
+                    for choice in ENTRIES_PER_REQUEST_CHOICES {
+                        ui.selectable_value(
+                            &mut settings.entries_per_request,
+                            choice,
+                            entries_per_request_text(choice),
+                        );
+                    }
+                });
+            egui::ComboBox::from_label("Auto refresh")
+                .selected_text(auto_refresh_text(settings.auto_refresh_minutes))
+                .show_ui(ui, |ui| {
+                    for choice in AUTO_REFRESH_CHOICES {
+                        ui.selectable_value(
+                            &mut settings.auto_refresh_minutes,
+                            choice,
+                            auto_refresh_text(choice),
+                        );
+                    }
+                });
+            if settings != self.settings {
+                requests.new_request_with_json_body(ApiEndpoint::SetUserSettings, &settings);
+                self.on_settings_changed(settings, requests);
+            }
+
+            // The response arrived, or another feeds request replaced the update.
+            self.updating_all_feeds &= requests.has_request(ApiEndpoint::Feeds);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !self.updating_all_feeds,
+                        egui::Button::new("Update all feeds"),
+                    )
+                    .clicked()
+                {
+                    requests.new_request_with_json_body(
+                        ApiEndpoint::Feeds,
+                        self.feeds_request(
+                            self.requested_entry_amount,
+                            None,
+                            AdditionalAction::UpdateFeeds,
+                        ),
+                    );
+                    self.updating_all_feeds = true;
+                    self.update_progress = None;
+                }
+
+                if self.updating_all_feeds {
+                    self.poll_update_status(ui.ctx(), requests);
+                    ui.spinner();
+                    let label = ui.label(update_progress_text(self.update_progress.as_ref()));
+                    if let Some(progress) = &self.update_progress {
+                        if !progress.currently_failing.is_empty() {
+                            label.on_hover_text(format!(
+                                "{} failed so far",
+                                progress.currently_failing.len()
+                            ));
+                        }
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !requests.has_request(ApiEndpoint::MarkAllRead)
+                            && self.mark_all_read_to_confirm.is_none()
+                            && !self.show_read_later,
+                        egui::Button::new("Mark all read"),
+                    )
+                    .on_hover_text("Marks all entries of the selected feeds as read")
+                    .clicked()
+                {
+                    requests.new_request_with_json_body(
+                        ApiEndpoint::MarkAllRead,
+                        MarkAllReadRequest {
+                            collection_id: self.collection_id,
+                            filter: self.current_selection(),
+                            // Marking everything is confirmed separately.
+                            confirm: false,
+                        },
+                    );
+                }
+
+                if !self.marked_all_read.is_empty()
+                    && ui
+                        .button(format!("Undo ({})", self.marked_all_read.len()))
+                        .clicked()
+                {
+                    self.pending_read_changes
+                        .extend(self.marked_all_read.drain(..).map(|entry| {
+                            SetEntryReadRequestAndResponse {
+                                read: false,
+                                ..entry
+                            }
+                        }));
+                }
+            });
+
+            if ui
+                .add_enabled(
+                    !requests.has_request(ApiEndpoint::UnreadLinks)
+                        && self.links_to_open.is_empty(),
+                    egui::Button::new("Open all unread"),
+                )
+                .on_hover_text(
+                    "Opens the links of all unread entries of the selected feeds in new tabs",
+                )
+                .clicked()
+            {
+                // Not only the entries that are shown, those might be just the first page.
                 requests.new_request_with_json_body(
-                    ApiEndpoint::Feeds,
+                    ApiEndpoint::UnreadLinks,
                     FeedsRequest {
-                        filter: self.feeds_display.current_selection(),
-                        entry_filter: if self.show_read_entries {
-                            EntryTypeFilter::All
-                        } else {
-                            EntryTypeFilter::UnreadOnly
-                        },
-                        amount: self.requested_entry_amount,
-                        additional_action: AdditionalAction::UpdateFeeds,
+                        filter: self.current_selection(),
+                        entry_filter: EntryTypeFilter::UnreadOnly,
+                        ..self.feeds_request(usize::MAX, None, AdditionalAction::None)
                     },
+                );
+            }
+
+            if ui
+                .add_enabled(
+                    !requests.has_request(ApiEndpoint::CreateShareLink) && !self.show_read_later,
+                    egui::Button::new("Share"),
                 )
+                .on_hover_text(format!(
+                    "Creates a link to the names and urls of the selected feeds, that works for {} days without logging in",
+                    SHARE_LINK_VALID_DAYS
+                ))
+                .clicked()
+            {
+                requests.new_request_with_json_body(
+                    ApiEndpoint::CreateShareLink,
+                    CreateShareLinkRequest {
+                        collection_id: self.collection_id,
+                        filter: self.current_selection(),
+                        valid_days: SHARE_LINK_VALID_DAYS,
+                    },
+                );
             }
 
             match self.feeds_display.show(ui) {
                 FeedListDisplayResponse::None => {} // Nothing to do
                 FeedListDisplayResponse::SelectionChanged => {
+                    // The user wants to see the selected feeds instead.
+                    self.show_read_later = false;
                     self.on_feed_selection_changed(requests);
                 }
+                FeedListDisplayResponse::FeedsReordered(changed) => {
+                    for (url, info) in changed {
+                        self.pending_feed_order_changes
+                            .push_back(SetFeedInfoRequestAndResponse {
+                                collection_id: self.collection_id,
+                                feed_url: url.clone(),
+                                info: info.clone(),
+                            });
+                        self.feeds_info.insert(url, info);
+                    }
+                    self.feeds_display.update_feeds_info(&self.feeds_info);
+                }
+                FeedListDisplayResponse::RefreshFeed(url) => requests.new_request_with_json_body(
+                    ApiEndpoint::Feeds,
+                    self.feeds_request(
+                        self.requested_entry_amount,
+                        None,
+                        AdditionalAction::UpdateSingle(url),
+                    ),
+                ),
             }
         });
     }
 
     pub fn show_entry_amount_display(&mut self, ui: &mut Ui, requests: &mut Requests) {
-        if self.available_entry_amount > self.feed_entries.len() {
+        if !self.has_more_entries {
             // We only display the "request more" button if there is actually more to request.
+            return;
+        }
+
+        if let Some(cursor) = self.next_cursor.clone() {
             if ui
                 .button(format!(
                     "{}/{} request more",
@@ -164,69 +925,112 @@ impl RssDisplay {
                 ))
                 .clicked()
             {
-                self.requested_entry_amount =
-                    self.feed_entries.len() + DEFAULT_ENTRY_REQUEST_AMOUNT;
+                // Only the next page is requested. The server continues where the last response stopped.
+                self.requested_entry_amount = self
+                    .feed_entries
+                    .len()
+                    .saturating_add(self.settings.entry_request_amount());
                 requests.new_request_with_json_body(
                     ApiEndpoint::Feeds,
-                    FeedsRequest {
-                        filter: self.feeds_display.current_selection(),
-                        entry_filter: if self.show_read_entries {
-                            EntryTypeFilter::All
-                        } else {
-                            EntryTypeFilter::UnreadOnly
-                        },
-                        amount: self.requested_entry_amount,
-                        additional_action: AdditionalAction::None,
-                    },
+                    self.feeds_request(
+                        self.settings.entry_request_amount(),
+                        Some(cursor),
+                        AdditionalAction::None,
+                    ),
                 )
             }
         }
     }
 
-    /// Request the first [`DEFAULT_ENTRY_REQUEST_AMOUNT`] entries of the selected feeds.
+    /// Request the first [UserSettings] `.entries_per_request` entries of the selected feeds.
+    /// The selection is also saved in the user settings, so it is restored on the next visit.
     fn on_feed_selection_changed(&mut self, requests: &mut Requests) {
         self.feed_entries.clear();
+        self.selected_entry = None;
+
+        let selection = self.current_selection();
+        if self.settings.last_selection != selection {
+            self.settings.last_selection = selection;
+            requests.new_request_with_json_body(ApiEndpoint::SetUserSettings, &self.settings);
+        }
 
+        self.requested_entry_amount = self.settings.entry_request_amount();
         requests.new_request_with_json_body(
             ApiEndpoint::Feeds,
-            FeedsRequest {
-                filter: self.feeds_display.current_selection(),
-                entry_filter: if self.show_read_entries {
-                    EntryTypeFilter::All
-                } else {
-                    EntryTypeFilter::UnreadOnly
-                },
-                amount: DEFAULT_ENTRY_REQUEST_AMOUNT,
-                additional_action: AdditionalAction::None,
-            },
+            self.feeds_request(self.requested_entry_amount, None, AdditionalAction::None),
         );
         self.available_entry_amount = 0;
+        self.has_more_entries = false;
+    }
+
+    /// Queues the links of the `entries` to be opened in new tabs, oldest first.
+    /// The entries are marked as read if the user wants that when opening entries.
+    fn open_entries(&mut self, mut entries: Vec<ComFeedEntry>) {
+        entries.sort_by_key(|entry| entry.pub_date);
+
+        if self.settings.mark_read_on_open {
+            self.pending_read_changes
+                .extend(entries.iter().map(|entry| SetEntryReadRequestAndResponse {
+                    collection_id: self.collection_id,
+                    feed_url: entry.feed_url.clone(),
+                    entry_key: entry.key.clone(),
+                    read: true,
+                }));
+        }
+
+        self.links_to_open.extend(entries);
+    }
+
+    /// Requests the shown entries and the unread total again, after the read state of many
+    /// entries changed at once.
+    fn refresh_entries(&mut self, requests: &mut Requests) {
+        requests.new_request_with_json_body(
+            ApiEndpoint::Feeds,
+            self.feeds_request(self.requested_entry_amount, None, AdditionalAction::None),
+        );
+        self.available_entry_amount = 0;
+        self.has_more_entries = false;
+        requests.new_request_with_json_body(
+            ApiEndpoint::UnreadTotal,
+            UnreadTotalRequest {
+                collection_id: self.collection_id,
+            },
+        );
     }
 
     pub fn show_feed_entries(&mut self, ui: &mut Ui, requests: &mut Requests) {
+        let mut selection_was_reset = false;
+
         if requests.has_request(ApiEndpoint::Feeds) {
             if let Some(response) = requests.ready(ApiEndpoint::Feeds) {
                 // TODO (Wybe 2022-07-16): Handle errors
                 // TODO (Wybe 2022-07-18): Reduce nesting
                 if let Response::Ok(body) = response {
                     if let Ok(feeds_response) = serde_json::from_str::<FeedsResponse>(&body) {
+                        self.last_entries_received = ui.ctx().input(|input| input.time);
+
                         if let Some(feeds_info) = feeds_response.feeds_info {
                             self.feeds_info = feeds_info;
-                            self.feeds_display.update_feeds_info(&self.feeds_info);
+                            selection_was_reset =
+                                self.feeds_display.update_feeds_info(&self.feeds_info);
                         }
 
+                        if let Some(total_entries) = feeds_response.total_entries {
+                            self.total_entries = total_entries;
+                        }
                         self.available_entry_amount = feeds_response.total_available;
-                        self.feed_entries.clear();
+                        self.has_more_entries = feeds_response.has_more;
+                        self.total_unread = feeds_response.total_unread;
+                        self.next_cursor = feeds_response.next_cursor;
 
-                        for entry in feeds_response.feed_entries {
-                            let feed_name = self
-                                .feeds_info
-                                .get(&entry.feed_url)
-                                .map(|feed| feed.name.as_str())
-                                .unwrap_or("");
+                        if feeds_response.requested_after.is_none() {
+                            // This is not a continuation of the entries we already have.
+                            self.feed_entries.clear();
+                        }
 
+                        for entry in feeds_response.feed_entries {
                             self.feed_entries
-                                .push(DisplayFeedEntry::new(&entry, feed_name));
+                                .push(DisplayFeedEntry::new(&entry, &self.feeds_info));
                         }
                     }
                 }
@@ -235,147 +1039,514 @@ impl RssDisplay {
             }
         }
 
-        if requests.has_request(ApiEndpoint::SetEntryRead) {
-            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::SetEntryRead) {
-                // `read` field was set successfully. Update the visuals to match.
-                if let Ok(response) = serde_json::from_str::<SetEntryReadRequestAndResponse>(&body)
+        if requests.has_request(ApiEndpoint::SetEntriesRead) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::SetEntriesRead) {
+                // `read` fields were set successfully. Update the visuals to match.
+                if let Ok(response) =
+                    serde_json::from_str::<SetEntriesReadRequestAndResponse>(&body)
                 {
-                    if let Some((index, _)) = self
-                        .feed_entries
-                        .iter_mut()
-                        .enumerate()
-                        .find(|(_, entry)| entry.key == response.entry_key)
-                        .take()
-                    {
-                        // If we are not displaying unread entries, we should remove it. Otherwise update it.
-                        if !self.show_read_entries && response.read {
-                            self.feed_entries.remove(index);
-                            // If we have removed the entry from this view, there will be one less entry available from the server
+                    let entry_filter = self.settings.shown_entries.entry_filter();
+                    let mut missing_entries = false;
+
+                    for changed in response {
+                        // The server sets the read state of all copies of the entry, also the ones
+                        // in other feeds. So we update all of them as well.
+                        // Entries that don't match the shown entries anymore are removed. Otherwise update them.
+                        if !self.show_read_later
+                            && !entry_filter.apply_to_state(changed.read, false)
+                        {
+                            let amount_before = self.feed_entries.len();
+                            self.feed_entries
+                                .retain(|entry| entry.key != changed.entry_key);
+                            // If we have removed entries from this view, there will be less entries available from the server
                             // if we were to re-request the view.
-                            self.available_entry_amount =
-                                self.available_entry_amount.saturating_sub(1);
+                            self.available_entry_amount = self
+                                .available_entry_amount
+                                .saturating_sub(amount_before - self.feed_entries.len());
                         } else {
-                            self.feed_entries[index].read = response.read;
+                            let mut found = false;
+                            for entry in self
+                                .feed_entries
+                                .iter_mut()
+                                .filter(|entry| entry.key == changed.entry_key)
+                            {
+                                entry.read = changed.read;
+                                found = true;
+                            }
+                            // For example after undoing "mark all read", when read entries are hidden.
+                            // Or after marking entries read, when only read entries are shown.
+                            missing_entries |= !found
+                                && (!changed.read
+                                    || self.settings.shown_entries == ShownEntries::Read);
                         }
                     }
+
+                    if missing_entries {
+                        self.refresh_entries(requests);
+                    } else {
+                        requests.new_request_with_json_body(
+                            ApiEndpoint::UnreadTotal,
+                            UnreadTotalRequest {
+                                collection_id: self.collection_id,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::SetEntryReadLater) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::SetEntryReadLater) {
+                if let Ok(changed) =
+                    serde_json::from_str::<SetEntryReadLaterRequestAndResponse>(&body)
+                {
+                    if self.show_read_later && !changed.read_later {
+                        let amount_before = self.feed_entries.len();
+                        self.feed_entries
+                            .retain(|entry| entry.key != changed.entry_key);
+                        self.available_entry_amount = self
+                            .available_entry_amount
+                            .saturating_sub(amount_before - self.feed_entries.len());
+                    } else {
+                        for entry in self
+                            .feed_entries
+                            .iter_mut()
+                            .filter(|entry| entry.key == changed.entry_key)
+                        {
+                            entry.read_later = changed.read_later;
+                        }
+                    }
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::UnreadLinks) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::UnreadLinks) {
+                if let Ok(feeds_response) = serde_json::from_str::<FeedsResponse>(&body) {
+                    let entries: Vec<ComFeedEntry> = feeds_response
+                        .feed_entries
+                        .into_iter()
+                        .filter(|entry| !entry.read && entry.link.is_some())
+                        .collect();
+
+                    if entries.len() > OPEN_ALL_UNREAD_CONFIRMATION_AMOUNT {
+                        self.links_to_confirm = entries;
+                    } else {
+                        self.open_entries(entries);
+                    }
+                }
+            }
+        }
+
+        if let Some(entry) = self.links_to_open.pop_front() {
+            if let Some(link) = entry.link {
+                ui.ctx().output_mut(|output| {
+                    output.open_url = Some(OpenUrl {
+                        url: link.clone_string(),
+                        new_tab: true,
+                    })
+                });
+            }
+            // The next link is opened in the next frame.
+            ui.ctx().request_repaint();
+        }
+
+        if requests.has_request(ApiEndpoint::MarkAllRead) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::MarkAllRead) {
+                if let Ok(response) = serde_json::from_str::<MarkAllReadResponse>(&body) {
+                    match response {
+                        MarkAllReadResponse::NeedsConfirmation { unread } => {
+                            self.mark_all_read_to_confirm = Some(unread);
+                        }
+                        MarkAllReadResponse::Marked(marked) => {
+                            self.marked_all_read = marked;
+                            self.refresh_entries(requests);
+                        }
+                    }
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::CreateShareLink) {
+            match requests.ready(ApiEndpoint::CreateShareLink) {
+                Some(Response::Ok(body)) => {
+                    if let Ok(link) = serde_json::from_str::<CreateShareLinkResponse>(&body) {
+                        self.share_link = Some(Ok(link));
+                    }
+                }
+                Some(Response::NotOk(_)) | Some(Response::Error) => {
+                    self.share_link = Some(Err(
+                        "Could not create a share link. They might be turned off on this server."
+                            .to_string(),
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        if selection_was_reset {
+            // The entries we received are for a feed or tag that no longer exists.
+            self.on_feed_selection_changed(requests);
+        }
+
+        if requests.has_request(ApiEndpoint::UserSettings) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::UserSettings) {
+                if let Ok(settings) = serde_json::from_str::<UserSettings>(&body) {
+                    self.on_settings_changed(settings, requests);
+                }
+            }
+        }
+
+        if requests.has_request(ApiEndpoint::SetUserSettings) {
+            // The settings were already applied when they were sent. Applying the response
+            // could undo changes that were made while the request was in flight.
+            let _ = requests.ready(ApiEndpoint::SetUserSettings);
+        }
+
+        if requests.has_request(ApiEndpoint::UnreadTotal) {
+            if let Some(Response::Ok(body)) = requests.ready(ApiEndpoint::UnreadTotal) {
+                if let Ok(response) = serde_json::from_str::<UnreadTotalResponse>(&body) {
+                    self.total_unread = response.total_unread;
                 }
             }
         }
 
         let text_style = egui::TextStyle::Body;
         let row_height = ui.text_style_height(&text_style);
+        let columns = EntryColumns::for_width(ui.available_width());
+        let title_width = ui.available_width() * WRAPPED_TITLE_WIDTH_FRACTION;
+
+        if self.settings.wrap_titles {
+            // Wrapped titles make the rows differ in height, so all rows are laid out instead
+            // of only the visible ones. Scrolling sideways would undo the wrapping.
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    self.show_entry_grid(ui, 0..self.feed_entries.len(), columns, title_width)
+                });
+        } else {
+            let mut scroll_area = egui::ScrollArea::both().auto_shrink([false, false]);
+            // Rows outside of the view are not laid out, so they can't scroll themselves into it.
+            if let Some(selected) = self.selected_entry.filter(|_| self.scroll_to_selected) {
+                if !self.visible_rows.contains(&selected) {
+                    let row_height_with_spacing = row_height + ui.spacing().item_spacing.y;
+                    scroll_area = scroll_area
+                        .vertical_scroll_offset(selected as f32 * row_height_with_spacing);
+                }
+            }
+            scroll_area.show_rows(ui, row_height, self.feed_entries.len(), |ui, row_range| {
+                self.visible_rows = row_range.clone();
+                self.show_entry_grid(ui, row_range, columns, title_width)
+            });
+        }
+        self.scroll_to_selected = false;
+        // A new request would replace the one in flight, and then its response would be lost.
+        if !self.pending_read_changes.is_empty()
+            && !requests.has_request(ApiEndpoint::SetEntriesRead)
+        {
+            requests.new_request_with_json_body(
+                ApiEndpoint::SetEntriesRead,
+                std::mem::take(&mut self.pending_read_changes),
+            );
+        }
+
+        if requests.has_request(ApiEndpoint::SetFeedOrder) {
+            // The new order was already shown when the feed was dropped.
+            let _ = requests.ready(ApiEndpoint::SetFeedOrder);
+        }
+        if !requests.has_request(ApiEndpoint::SetFeedOrder) {
+            if let Some(change) = self.pending_feed_order_changes.pop_front() {
+                requests.new_request_with_json_body(ApiEndpoint::SetFeedOrder, change);
+            }
+        }
+
+        if !requests.has_request(ApiEndpoint::SetEntryReadLater) {
+            if let Some(change) = self.pending_read_later_changes.pop_front() {
+                requests.new_request_with_json_body(ApiEndpoint::SetEntryReadLater, change);
+            }
+        }
+    }
+
+    /// Shows the entries in `row_range` as rows of the entry grid.
+    fn show_entry_grid(
+        &mut self,
+        ui: &mut Ui,
+        row_range: Range<usize>,
+        columns: EntryColumns,
+        title_width: f32,
+    ) {
         let unread_entry_text_color = ui.ctx().style().visuals.strong_text_color();
+        let selected_entry_color = ui.visuals().selection.bg_fill;
+        let highlighted_entry_text_color = ui.visuals().warn_fg_color;
 
-        let mut set_entry_read_request = None;
+        egui::Grid::new("feed-grid")
+            .striped(true)
+            .num_columns(columns.amount())
+            .start_row(row_range.start)
+            .show(ui, |ui| {
+                for (index, entry) in self
+                    .feed_entries
+                    .iter()
+                    .enumerate()
+                    .skip(row_range.start)
+                    //TODO (Wybe 2022-07-18): Vertical scroll bar changes size sometimes during scrolling, why?
+                    .take(row_range.end - row_range.start)
+                {
+                    let unread = !entry.read;
 
-        egui::ScrollArea::both()
-            .auto_shrink([false, false])
-            .show_rows(ui, row_height, self.feed_entries.len(), |ui, row_range| {
-                egui::Grid::new("feed-grid")
-                    .striped(true)
-                    .num_columns(5)
-                    .start_row(row_range.start)
-                    .show(ui, |ui| {
-                        for entry in self
-                            .feed_entries
-                            .iter()
-                            .skip(row_range.start)
-                            //TODO (Wybe 2022-07-18): Vertical scroll bar changes size sometimes during scrolling, why?
-                            .take(row_range.end - row_range.start)
-                        {
-                            let unread = !entry.read;
-
-                            let mut mark_read = !unread;
-                            ui.checkbox(
-                                &mut mark_read,
-                                highlighted_text(
-                                    &entry.display_title,
-                                    unread,
-                                    unread_entry_text_color,
-                                ),
-                            );
-
-                            if mark_read == unread {
-                                // User wants to mark this entry as read or unread.
-                                set_entry_read_request = Some(SetEntryReadRequestAndResponse {
-                                    feed_url: entry.feed_url.clone(),
-                                    entry_key: entry.key.clone(),
-                                    read: mark_read,
-                                });
-                            }
+                    let selected = self.selected_entry == Some(index);
+                    let title = |title: &str| {
+                        let title_color = if entry.highlighted {
+                            highlighted_entry_text_color
+                        } else {
+                            unread_entry_text_color
+                        };
+                        let text = highlighted_text(title, unread, title_color);
+                        if selected {
+                            text.background_color(selected_entry_color)
+                        } else {
+                            text
+                        }
+                    };
 
-                            ui.label(highlighted_text(
-                                &entry.pub_date_string,
-                                unread,
-                                unread_entry_text_color,
-                            ));
+                    let mut mark_read = !unread;
+                    let title_response = if self.settings.wrap_titles {
+                        ui.scope(|ui| {
+                            ui.set_max_width(title_width);
+                            ui.style_mut().wrap = Some(true);
+                            ui.checkbox(&mut mark_read, title(&entry.title))
+                        })
+                        .inner
+                    } else {
+                        ui.checkbox(&mut mark_read, title(&entry.display_title))
+                    };
+                    if selected && self.scroll_to_selected {
+                        title_response.scroll_to_me(None);
+                    }
+
+                    if mark_read == unread {
+                        // User wants to mark this entry as read or unread.
+                        self.pending_read_changes
+                            .push(entry.set_read_request(self.collection_id, mark_read));
+                    }
+
+                    ui.label(highlighted_text(
+                        &entry.pub_date_string,
+                        unread,
+                        unread_entry_text_color,
+                    ));
 
+                    if columns.feed_name {
+                        ui.horizontal(|ui| {
                             ui.label(highlighted_text(
                                 &entry.feed_name,
                                 unread,
                                 unread_entry_text_color,
                             ));
+                            for name in &entry.other_feed_names {
+                                ui.label(RichText::new(name).small().weak())
+                                    .on_hover_text("Also in this feed");
+                            }
+                        });
+                    }
 
-                            if let Some(link) = &entry.link {
-                                ui.add(NewTabHyperlink::from_label_and_url("Open", link));
-
-                                if !entry.read {
-                                    // Item not read, so we add an option to open and mark it "read" at the same time.
-                                    if ui
-                                        .add(NewTabHyperlink::from_label_and_url(
-                                            "Open mark read",
-                                            link,
-                                        ))
-                                        .clicked()
-                                        && !entry.read
-                                    {
-                                        // User wants to mark this entry as read.
-                                        set_entry_read_request =
-                                            Some(SetEntryReadRequestAndResponse {
-                                                feed_url: entry.feed_url.clone(),
-                                                entry_key: entry.key.clone(),
-                                                read: true,
-                                            });
-                                    }
-                                } else {
-                                    ui.label("");
-                                }
-                            } else {
-                                // No link, so add empty labels to skip these columns.
-                                ui.label("");
-                                ui.label("");
+                    if ui
+                        .selectable_label(entry.read_later, "Later")
+                        .on_hover_text("Read later")
+                        .clicked()
+                    {
+                        self.pending_read_later_changes.push_back(
+                            SetEntryReadLaterRequestAndResponse {
+                                collection_id: self.collection_id,
+                                feed_url: entry.feed_url.clone(),
+                                entry_key: entry.key.clone(),
+                                read_later: !entry.read_later,
+                            },
+                        );
+                    }
+
+                    if let Some(link) = &entry.link {
+                        // In one cell, so the grid keeps the same columns for entries
+                        // without a link.
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add(NewTabHyperlink::from_label_and_url("Open", link))
+                                .clicked()
+                                && unread
+                                && self.settings.mark_read_on_open
+                            {
+                                self.pending_read_changes
+                                    .push(entry.set_read_request(self.collection_id, true));
                             }
 
-                            ui.end_row();
-                        }
-                    });
+                            if columns.copy_link
+                                && ui
+                                    .small_button("Copy")
+                                    .on_hover_text("Copy the link")
+                                    .clicked()
+                            {
+                                ui.output_mut(|output| output.copied_text = link.to_string());
+                            }
+                        });
+                    }
+
+                    ui.end_row();
+                }
             });
+    }
 
-        if let Some(request) = set_entry_read_request {
-            requests.new_request_with_json_body(ApiEndpoint::SetEntryRead, request);
+    fn on_settings_changed(&mut self, settings: UserSettings, requests: &mut Requests) {
+        let entries_per_request_changed =
+            settings.entries_per_request != self.settings.entries_per_request;
+        let selection_changed = settings.last_selection != self.current_selection();
+        let shown_entries_changed = settings.shown_entries != self.settings.shown_entries;
+        let sort_order_changed = settings.sort_order != self.settings.sort_order;
+        self.settings = settings;
+
+        if selection_changed {
+            self.feeds_display
+                .set_selection(self.settings.last_selection.clone());
+        }
+
+        if entries_per_request_changed
+            || selection_changed
+            || shown_entries_changed
+            || sort_order_changed
+        {
+            // The entries we have were requested with the old amount, selection, entry filter,
+            // or sort order.
+            // This can happen right after logging in, when the first feeds request is still
+            // in flight. That request gets replaced, so we need to include the feeds info again.
+            if entries_per_request_changed || selection_changed {
+                self.requested_entry_amount = self.settings.entry_request_amount();
+            }
+            // Otherwise the amount the user has paged to so far is requested again, like when
+            // toggling "Hide duplicate entries".
+            requests.new_request_with_json_body(
+                ApiEndpoint::Feeds,
+                self.feeds_request(
+                    self.requested_entry_amount,
+                    None,
+                    AdditionalAction::IncludeFeedsInfo,
+                ),
+            );
+            self.available_entry_amount = 0;
+            self.has_more_entries = false;
         }
     }
 
     /// Call this after the user has logged in.
     pub fn on_login(&self, requests: &mut Requests) {
+        requests.new_request_without_body(ApiEndpoint::UserSettings);
+        requests.new_request_without_body(ApiEndpoint::Collections);
+
         // Do the first feeds request.
         // Because we have just logged in, we request to include the feeds info.
         requests.new_request_with_json_body(
             ApiEndpoint::Feeds,
-            FeedsRequest {
-                filter: self.feeds_display.current_selection(),
-                entry_filter: if self.show_read_entries {
-                    EntryTypeFilter::All
-                } else {
-                    EntryTypeFilter::UnreadOnly
-                },
-                amount: DEFAULT_ENTRY_REQUEST_AMOUNT,
-                additional_action: AdditionalAction::IncludeFeedsInfo,
-            },
+            self.feeds_request(
+                self.settings.entry_request_amount(),
+                None,
+                AdditionalAction::IncludeFeedsInfo,
+            ),
         );
     }
 }
 
+fn entries_per_request_text(entries_per_request: Option<usize>) -> String {
+    match entries_per_request {
+        Some(amount) => amount.to_string(),
+        None => "All".to_string(),
+    }
+}
+
+fn auto_refresh_text(auto_refresh_minutes: Option<u64>) -> String {
+    match auto_refresh_minutes {
+        Some(minutes) => format!("Every {} min", minutes),
+        None => "Off".to_string(),
+    }
+}
+
+fn update_progress_text(progress: Option<&UpdateProgress>) -> String {
+    match progress {
+        Some(progress) => format!("Updating {}/{}...", progress.completed, progress.total),
+        None => "Updating...".to_string(),
+    }
+}
+
+/// How many seconds are left before the next auto refresh. Zero or less if it is due.
+/// [None] if auto refresh is off.
+fn seconds_until_auto_refresh(
+    auto_refresh_minutes: Option<u64>,
+    last_entries_received: f64,
+    now: f64,
+) -> Option<f64> {
+    auto_refresh_minutes.map(|minutes| last_entries_received + (minutes * 60) as f64 - now)
+}
+
+/// Closes the side panel when the page width goes below [SIDEPANEL_COLLAPSE_WIDTH], and opens it
+/// when the width goes above it again. As long as the width doesn't cross it, the side panel
+/// stays the way the user left it.
+fn auto_collapse_sidepanel(open: bool, previous_width: f32, width: f32) -> bool {
+    let was_wide = previous_width >= SIDEPANEL_COLLAPSE_WIDTH;
+    let is_wide = width >= SIDEPANEL_COLLAPSE_WIDTH;
+
+    if was_wide == is_wide {
+        open
+    } else {
+        is_wide
+    }
+}
+
+/// Which of the optional columns of the entry list are shown. Every row has to show the same
+/// columns, otherwise the cells of the grid don't line up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntryColumns {
+    feed_name: bool,
+    /// Shown next to the "Open" link, so it doesn't take a column of its own.
+    copy_link: bool,
+}
+
+impl EntryColumns {
+    /// Leaves as much room for the titles as possible. The feed name can be found by
+    /// selecting the feed, and the link can still be opened.
+    fn compact() -> Self {
+        EntryColumns {
+            feed_name: false,
+            copy_link: false,
+        }
+    }
+
+    fn for_width(width: f32) -> Self {
+        if width < COMPACT_ENTRIES_WIDTH {
+            Self::compact()
+        } else {
+            EntryColumns {
+                feed_name: true,
+                copy_link: true,
+            }
+        }
+    }
+
+    /// Title, date, read later, and link are always shown.
+    fn amount(&self) -> usize {
+        4 + usize::from(self.feed_name)
+    }
+}
+
+/// For example `1203` becomes `1,203`.
+fn with_thousands_separators(number: usize) -> String {
+    let digits = number.to_string();
+    let mut result = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
 fn highlighted_text(text: &str, highlight: bool, highlight_color: Color32) -> RichText {
     let mut text = RichText::new(text);
     if highlight {
@@ -387,27 +1558,48 @@ fn highlighted_text(text: &str, highlight: bool, highlight_color: Color32) -> Ri
 /// The info used to display an entry, so that it doesn't need to be recalculated each frame.
 #[derive(Debug, Clone)]
 struct DisplayFeedEntry {
-    /// The feed title, but formatted for display.
+    /// The entry title, with the middle left out if it is too long.
     display_title: String,
+    /// The full entry title, for when titles are wrapped instead.
+    title: String,
     /// Key to use when sending update requests to the server, such as marking the entry as read.
     key: EntryKey,
     /// Name of the feed this entry belongs to.
     feed_name: String,
+    /// Names of the other feeds this entry is in, when duplicate entries are hidden.
+    other_feed_names: Vec<String>,
     feed_url: Url,
     link: Option<Url>,
     pub_date_string: String,
     read: bool,
+    read_later: bool,
+    /// Whether the title has one of the highlight keywords of the feed.
+    highlighted: bool,
 }
 
 impl DisplayFeedEntry {
-    fn new(entry: &ComFeedEntry, feed_name: &str) -> Self {
+    fn new(entry: &ComFeedEntry, feeds_info: &HashMap<Url, FeedInfo>) -> Self {
+        let feed_name = |url: &Url| {
+            feeds_info
+                .get(url)
+                .map(|feed| feed.name.as_str())
+                .unwrap_or("")
+        };
         let display_title = cut_middle_of_string_if_too_long(&entry.title, 60);
-        let feed_title = cut_middle_of_string_if_too_long(feed_name, 40);
+        let feed_title = cut_middle_of_string_if_too_long(feed_name(&entry.feed_url), 40);
+        let other_feed_names = entry
+            .sources
+            .iter()
+            .filter(|url| **url != entry.feed_url)
+            .map(|url| cut_middle_of_string_if_too_long(feed_name(url), 20))
+            .collect();
 
         DisplayFeedEntry {
             display_title,
+            title: entry.title.clone(),
             key: entry.key.clone(),
             feed_name: feed_title,
+            other_feed_names,
             feed_url: entry.feed_url.clone(),
             link: entry.link.clone(),
             pub_date_string: entry
@@ -416,6 +1608,21 @@ impl DisplayFeedEntry {
                 .format("%Y-%m-%d")
                 .to_string(),
             read: entry.read,
+            read_later: entry.read_later,
+            highlighted: entry.highlighted,
+        }
+    }
+
+    fn set_read_request(
+        &self,
+        collection_id: CollectionId,
+        read: bool,
+    ) -> SetEntryReadRequestAndResponse {
+        SetEntryReadRequestAndResponse {
+            collection_id,
+            feed_url: self.feed_url.clone(),
+            entry_key: self.key.clone(),
+            read,
         }
     }
 }
@@ -461,7 +1668,10 @@ pub fn cut_middle_of_string_if_too_long(input: &str, max_length: usize) -> Strin
 
 #[cfg(test)]
 mod tests {
-    use crate::rss_collection::cut_middle_of_string_if_too_long;
+    use crate::rss_collection::{
+        auto_collapse_sidepanel, cut_middle_of_string_if_too_long, seconds_until_auto_refresh,
+        with_thousands_separators, EntryColumns,
+    };
     use pretty_assertions::assert_eq;
     use rstest::rstest;
 
@@ -478,4 +1688,58 @@ mod tests {
         let result = cut_middle_of_string_if_too_long(input, max_length);
         assert_eq!(&result, expected);
     }
+
+    #[rstest]
+    // Opened by the user on a narrow page.
+    #[case(true, 500.0, 600.0, true)]
+    // Closed by the user on a wide page.
+    #[case(false, 1200.0, 1100.0, false)]
+    #[case(true, 1200.0, 500.0, false)]
+    #[case(false, 500.0, 1200.0, true)]
+    fn test_auto_collapse_sidepanel(
+        #[case] open: bool,
+        #[case] previous_width: f32,
+        #[case] width: f32,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            auto_collapse_sidepanel(open, previous_width, width),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(400.0, 4)]
+    #[case(699.0, 4)]
+    #[case(700.0, 5)]
+    #[case(1200.0, 5)]
+    fn test_entry_columns_for_width(#[case] width: f32, #[case] expected_amount: usize) {
+        assert_eq!(EntryColumns::for_width(width).amount(), expected_amount);
+    }
+
+    #[rstest]
+    #[case(None, 0.0, 10_000.0, None)]
+    #[case(Some(5), 100.0, 160.0, Some(240.0))]
+    #[case(Some(5), 100.0, 400.0, Some(0.0))]
+    #[case(Some(5), 100.0, 500.0, Some(-100.0))]
+    fn test_seconds_until_auto_refresh(
+        #[case] auto_refresh_minutes: Option<u64>,
+        #[case] last_entries_received: f64,
+        #[case] now: f64,
+        #[case] expected: Option<f64>,
+    ) {
+        assert_eq!(
+            seconds_until_auto_refresh(auto_refresh_minutes, last_entries_received, now),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(0, "0")]
+    #[case(999, "999")]
+    #[case(1203, "1,203")]
+    #[case(1234567, "1,234,567")]
+    fn test_with_thousands_separators(#[case] number: usize, #[case] expected: &str) {
+        assert_eq!(with_thousands_separators(number), expected);
+    }
 }
@@ -0,0 +1,227 @@
+//! Endpoints for managing the users of the server. Only available to users with
+//! [crate::users::UserInfo] `.is_admin`.
+
+use crate::auth::AuthData;
+use crate::rss_collection::RssCollections;
+use crate::user_settings::UserSettingsCollection;
+use crate::users::UserId;
+use crate::{Authenticated, SaveInRonFile};
+use actix_web::{post, web, HttpResponse, Responder};
+use log::{info, warn};
+use rss_com_lib::message_body::{
+    AdminDeleteUserRequestAndResponse, AdminUserInfo, AdminUsersResponse,
+};
+
+#[post("/admin/users")]
+pub async fn get_users(
+    auth: Authenticated,
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    if !auth_data.is_admin(auth.user_id()) {
+        warn!("User `{}` tried to list the users", auth.user_name());
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let collections = collections.read().unwrap();
+    let mut users: AdminUsersResponse = auth_data
+        .users()
+        .into_iter()
+        .map(|(id, name, is_admin)| AdminUserInfo {
+            id: id.0,
+            name,
+            is_admin,
+            feeds: collections
+                .get(&id)
+                .map(|user_collections| {
+                    user_collections
+                        .values()
+                        .map(|collection| collection.len())
+                        .sum()
+                })
+                .unwrap_or_default(),
+        })
+        .collect();
+    users.sort_by_key(|user| user.id);
+
+    HttpResponse::Ok().json(users)
+}
+
+#[post("/admin/delete_user")]
+pub async fn delete_user(
+    request: web::Json<AdminDeleteUserRequestAndResponse>,
+    auth: Authenticated,
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+    settings: web::Data<UserSettingsCollection>,
+) -> impl Responder {
+    if !auth_data.is_admin(auth.user_id()) {
+        warn!("User `{}` tried to delete a user", auth.user_name());
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let id = UserId(request.id);
+    if id == *auth.user_id() {
+        // Otherwise the last admin could lock everyone out.
+        return HttpResponse::BadRequest().body("You can't delete yourself");
+    }
+
+    if !auth_data.delete_user(&id) {
+        return HttpResponse::NotFound().finish();
+    }
+    collections.write().unwrap().remove(&id);
+    settings.write().unwrap().remove(&id);
+
+    info!("User `{}` deleted user {}", auth.user_name(), id.0);
+
+    // Deleting users is rare, so everything is saved right away instead of periodically.
+    auth_data.save();
+    collections.save();
+    settings.save();
+
+    HttpResponse::Ok().json(request.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admin::{delete_user, get_users};
+    use crate::auth::{AuthData, AuthenticationResult};
+    use crate::auth_middleware::AuthenticationInfo;
+    use crate::rss_collection::{RssCollections, UserCollections};
+    use crate::user_settings::UserSettingsCollection;
+    use crate::users::{UserId, UserInfo};
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpMessage};
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::{AdminDeleteUserRequestAndResponse, UserSettings};
+    use std::rc::Rc;
+
+    const ADMIN: UserId = UserId(1);
+    const USER: UserId = UserId(2);
+
+    /// An admin and a normal user, that both have collections and settings.
+    fn data() -> (
+        web::Data<AuthData>,
+        web::Data<RssCollections>,
+        web::Data<UserSettingsCollection>,
+    ) {
+        // The default has the admin.
+        let mut auth_data = AuthData::default();
+        auth_data.new_user(
+            USER,
+            UserInfo {
+                name: "user".to_string(),
+                password: "password".to_string(),
+                is_admin: false,
+            },
+        );
+        let collections = RssCollections::default();
+        let settings = UserSettingsCollection::default();
+        for user in [ADMIN, USER] {
+            collections
+                .write()
+                .unwrap()
+                .insert(user, UserCollections::default());
+            settings
+                .write()
+                .unwrap()
+                .insert(user, UserSettings::default());
+        }
+
+        (
+            web::Data::new(auth_data),
+            web::Data::new(collections),
+            web::Data::new(settings),
+        )
+    }
+
+    /// Sends the `request` as if `user` is logged in.
+    async fn call(
+        data: &(
+            web::Data<AuthData>,
+            web::Data<RssCollections>,
+            web::Data<UserSettingsCollection>,
+        ),
+        user: UserId,
+        request: TestRequest,
+    ) -> ServiceResponse {
+        let (auth_data, collections, settings) = data;
+        let app = init_service(
+            App::new()
+                .app_data(auth_data.clone())
+                .app_data(collections.clone())
+                .app_data(settings.clone())
+                // Instead of the authentication middleware, which needs a session cookie.
+                .wrap_fn(move |request, service| {
+                    request
+                        .extensions_mut()
+                        .insert::<AuthenticationInfo>(Rc::new(AuthenticationResult::for_user(
+                            user,
+                            &format!("user {}", user.0),
+                        )));
+                    service.call(request)
+                })
+                .service(get_users)
+                .service(delete_user),
+        )
+        .await;
+        call_service(&app, request.to_request()).await
+    }
+
+    fn delete(id: UserId) -> TestRequest {
+        TestRequest::post()
+            .uri("/admin/delete_user")
+            .set_json(AdminDeleteUserRequestAndResponse { id: id.0 })
+    }
+
+    #[actix_web::test]
+    async fn test_only_admins_can_list_and_delete_users() {
+        // Given
+        let data = data();
+
+        // When
+        let list = call(&data, USER, TestRequest::post().uri("/admin/users")).await;
+        let deleted = call(&data, USER, delete(ADMIN)).await;
+
+        // Then
+        assert_eq!(list.status(), StatusCode::FORBIDDEN);
+        assert_eq!(deleted.status(), StatusCode::FORBIDDEN);
+        assert!(data.0.has_user(&ADMIN));
+    }
+
+    #[actix_web::test]
+    async fn test_admin_can_not_delete_themselves() {
+        // Given
+        let data = data();
+
+        // When
+        let response = call(&data, ADMIN, delete(ADMIN)).await;
+
+        // Then
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(data.0.has_user(&ADMIN));
+        assert!(data.1.read().unwrap().contains_key(&ADMIN));
+    }
+
+    #[actix_web::test]
+    async fn test_deleted_user_loses_their_collections_and_settings() {
+        // Given
+        let data = data();
+
+        // When
+        let response = call(&data, ADMIN, delete(USER)).await;
+        let again = call(&data, ADMIN, delete(USER)).await;
+
+        // Then
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!data.0.has_user(&USER));
+        assert!(!data.1.read().unwrap().contains_key(&USER));
+        assert!(!data.2.read().unwrap().contains_key(&USER));
+        // The other users are left alone.
+        assert!(data.1.read().unwrap().contains_key(&ADMIN));
+        assert!(data.2.read().unwrap().contains_key(&ADMIN));
+        assert_eq!(again.status(), StatusCode::NOT_FOUND);
+    }
+}
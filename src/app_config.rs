@@ -1,5 +1,17 @@
-use crate::{cookie, SaveInRonFile};
+use crate::feed_requester::FeedTimeouts;
+use crate::metrics::MetricsToken;
+use crate::request_log::SlowRequestThreshold;
+use crate::rss_collection::{EntryRetention, StripQueryParams, UserLimits};
+use crate::share_link::ShareLinks;
+use crate::{cookie, FeedRequester, SaveInRonFile};
+use log::warn;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// [cookie::Key::derive_from] needs at least this many bytes.
+const MIN_SESSION_KEY_LENGTH: usize = 32;
 
 /// If a value is not found in the saved config, serde will use the default value.
 #[derive(Serialize, Deserialize)]
@@ -8,6 +20,10 @@ pub struct ApplicationConfig {
     /// Hostname that the server will be located at.
     /// Used for when absolute urls need to be generated.
     pub hostname: String,
+    /// Ip address the server will listen on. `0.0.0.0` listens on all network interfaces.
+    /// Use `127.0.0.1` to only accept connections from the same machine, for example from a
+    /// reverse proxy.
+    pub bind_address: String,
     /// Port the server will listen to.
     pub port: u32,
     /// When the application is behind a proxy, requests might have a prefix.
@@ -20,11 +36,204 @@ pub struct ApplicationConfig {
 
     /// The master key for creating session cookies.
     pub session_key: Vec<u8>,
+    /// Maximum amount of days a user stays logged in. Must be more than 0.
+    /// If the session state changes, this time is reset. (But the session state doesn't change
+    /// after the first login, so that won't be a thing)
+    pub session_ttl_days: i64,
+
+    /// PEM file with the certificate chain for serving HTTPS.
+    /// If both this and `tls_key_path` are set, the server serves HTTPS itself.
+    /// Otherwise it serves plain HTTP, and should be behind a proxy that does HTTPS, because the
+    /// session cookies are only sent over secure connections.
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM file with the private key belonging to `tls_cert_path`.
+    pub tls_key_path: Option<PathBuf>,
+    /// Whether the session cookies are only sent over HTTPS.
+    /// Only turn this off for local development without HTTPS, otherwise anyone that can see
+    /// the traffic can take over the sessions of users.
+    pub cookie_secure: bool,
+
+    /// Maximum size of a single feed download, in bytes.
+    /// Downloads that are larger than this are aborted, and reported as an error.
+    pub max_feed_bytes: usize,
+    /// Maximum time in seconds to wait for the connection to a feed's server to be established.
+    pub feed_connect_timeout_secs: u64,
+    /// Maximum time in seconds to wait between receiving two pieces of a feed.
+    /// Protects against servers that send their response very slowly.
+    pub feed_read_timeout_secs: u64,
+    /// Whether feeds may be downloaded from private and internal addresses,
+    /// such as `localhost`, `192.168.x.x` or `169.254.169.254`.
+    /// Leave this off if users you don't fully trust can add feeds, otherwise they can use the
+    /// server to reach services on the internal network.
+    pub allow_private_feed_hosts: bool,
+    /// Maximum time in seconds to download a feed when it is added, or tested before adding it.
+    pub new_feed_timeout_secs: u64,
+    /// Maximum time in seconds to download a feed when updating it.
+    /// Used for both the background updates and the updates that users ask for.
+    pub feed_update_timeout_secs: u64,
+    /// A feed that was downloaded less than this many seconds ago is not downloaded again,
+    /// for example when a user refreshes twice in a row. 0 turns this off.
+    pub feed_cache_secs: u64,
+
+    /// Maximum amount of entries kept per feed. When there are more, the oldest read entries are removed.
+    /// Unread entries are never removed. 0 means there is no maximum.
+    pub max_entries_per_feed: usize,
+    /// Read entries that were published longer than this many days ago are removed.
+    /// Unread entries are never removed. 0 means read entries are kept forever.
+    pub read_entry_retention_days: u32,
+
+    /// Query parameters that are removed from entry links before they are sent to the client.
+    /// Useful for tracking parameters that feeds add to their links.
+    /// A name that ends in `*` matches all parameters that start with that name.
+    /// The stored entries keep their original link.
+    pub strip_query_params: Vec<String>,
+
+    /// Maximum amount of feeds a user can have, over all their collections.
+    /// Useful when people you don't know can get an account. [None] means there is no maximum.
+    pub max_feeds_per_user: Option<usize>,
+    /// Maximum amount of tags a feed can have. [None] means there is no maximum.
+    pub max_tags_per_feed: Option<usize>,
+
+    /// If set, the `/metrics` endpoint requires an `Authorization: Bearer <token>` header
+    /// with this token. Otherwise anyone that can reach the server can read the metrics.
+    pub metrics_token: Option<String>,
+
+    /// Whether users can create links to a selection of their feeds, that anyone with the link
+    /// can open without logging in. The links only show the names and urls of the feeds, and
+    /// stop working after at most 30 days. Absolute links are made with the `hostname` and
+    /// `route_prefix`.
+    pub allow_share_links: bool,
+
+    /// Format of the log file. The terminal log is always readable for humans.
+    pub log_format: LogFormat,
+    /// Api requests that take longer than this many seconds are logged as a warning,
+    /// with the user that made them. 0 turns this off.
+    pub slow_request_warning_secs: u64,
+
+    /// Whether collections of users that don't exist are removed at startup.
+    /// Otherwise they are only reported in the log.
+    pub prune_orphan_collections: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line of text per message, the same as in the terminal.
+    #[default]
+    Pretty,
+    /// One json object per line, with the `timestamp`, `level`, `target`, `thread` and `message`.
+    /// For log collectors such as Loki or Elasticsearch.
+    Json,
 }
 
 impl ApplicationConfig {
-    pub fn binding_ip(&self) -> String {
-        format!("0.0.0.0:{}", self.port)
+    /// Replaces the session key if it is empty, too short, or an obvious placeholder.
+    /// Anyone that knows the session key can forge a login session for any user.
+    /// Returns true if the key was replaced.
+    pub fn ensure_secure_session_key(&mut self) -> bool {
+        let too_short = self.session_key.len() < MIN_SESSION_KEY_LENGTH;
+        // For example a key of all zeroes.
+        let placeholder = self
+            .session_key
+            .iter()
+            .all(|byte| Some(byte) == self.session_key.first());
+
+        if too_short || placeholder {
+            warn!("The configured session key is insecure. Generating a new one. Everyone will have to log in again.");
+            self.session_key = cookie::Key::generate().master().to_vec();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The address the server listens on, made from the `bind_address` and `port`.
+    pub fn binding_ip(&self) -> Result<SocketAddr, String> {
+        let ip: IpAddr = self.bind_address.parse().map_err(|_| {
+            format!(
+                "`bind_address` `{}` is not an ip address",
+                self.bind_address
+            )
+        })?;
+        let port = u16::try_from(self.port)
+            .map_err(|_| format!("`port` {} is not a valid port", self.port))?;
+
+        Ok(SocketAddr::new(ip, port))
+    }
+
+    /// How long a login session lasts, from `session_ttl_days`.
+    pub fn session_ttl(&self) -> Result<time::Duration, String> {
+        if self.session_ttl_days > 0 {
+            Ok(time::Duration::days(self.session_ttl_days))
+        } else {
+            Err(format!(
+                "`session_ttl_days` {} is not more than 0",
+                self.session_ttl_days
+            ))
+        }
+    }
+
+    /// The certificate and key path, if both are configured.
+    pub fn tls_paths(&self) -> Option<(&Path, &Path)> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+            _ => None,
+        }
+    }
+
+    pub fn feed_requester(&self) -> FeedRequester {
+        FeedRequester::new(
+            self.max_feed_bytes,
+            Duration::from_secs(self.feed_connect_timeout_secs),
+            Duration::from_secs(self.feed_read_timeout_secs),
+            self.allow_private_feed_hosts,
+            Duration::from_secs(self.feed_cache_secs),
+        )
+    }
+
+    pub fn feed_timeouts(&self) -> FeedTimeouts {
+        FeedTimeouts {
+            new_feed: Duration::from_secs(self.new_feed_timeout_secs),
+            update: Duration::from_secs(self.feed_update_timeout_secs),
+        }
+    }
+
+    pub fn strip_query_params(&self) -> StripQueryParams {
+        StripQueryParams(self.strip_query_params.clone())
+    }
+
+    pub fn user_limits(&self) -> UserLimits {
+        UserLimits {
+            max_feeds_per_user: self.max_feeds_per_user,
+            max_tags_per_feed: self.max_tags_per_feed,
+        }
+    }
+
+    pub fn metrics_token(&self) -> MetricsToken {
+        MetricsToken(self.metrics_token.clone())
+    }
+
+    pub fn slow_request_threshold(&self) -> SlowRequestThreshold {
+        SlowRequestThreshold(
+            (self.slow_request_warning_secs > 0)
+                .then(|| Duration::from_secs(self.slow_request_warning_secs)),
+        )
+    }
+
+    pub fn share_links(&self) -> ShareLinks {
+        ShareLinks::new(
+            self.allow_share_links,
+            &self.session_key,
+            format!("https://{}{}", self.hostname, self.route_prefix),
+        )
+    }
+
+    pub fn entry_retention(&self) -> EntryRetention {
+        EntryRetention {
+            max_entries_per_feed: (self.max_entries_per_feed > 0)
+                .then_some(self.max_entries_per_feed),
+            max_read_age: (self.read_entry_retention_days > 0)
+                .then(|| chrono::Duration::days(self.read_entry_retention_days.into())),
+        }
     }
 }
 
@@ -32,10 +241,36 @@ impl Default for ApplicationConfig {
     fn default() -> Self {
         Self {
             hostname: "localhost".to_string(),
+            bind_address: "0.0.0.0".to_string(),
             port: 8443,
             route_prefix: "".to_string(),
             // If no key is supplied, generate one.
             session_key: cookie::Key::generate().master().to_vec(),
+            session_ttl_days: 14,
+            tls_cert_path: None,
+            tls_key_path: None,
+            cookie_secure: true,
+            max_feed_bytes: 10 * 1024 * 1024,
+            feed_connect_timeout_secs: 5,
+            feed_read_timeout_secs: 5,
+            allow_private_feed_hosts: false,
+            new_feed_timeout_secs: 10,
+            feed_update_timeout_secs: 20,
+            feed_cache_secs: 5 * 60,
+            max_entries_per_feed: 1000,
+            read_entry_retention_days: 365,
+            strip_query_params: vec![
+                "utm_*".to_string(),
+                "fbclid".to_string(),
+                "gclid".to_string(),
+            ],
+            max_feeds_per_user: None,
+            max_tags_per_feed: None,
+            metrics_token: None,
+            allow_share_links: false,
+            log_format: LogFormat::Pretty,
+            slow_request_warning_secs: 10,
+            prune_orphan_collections: false,
         }
     }
 }
@@ -43,3 +278,86 @@ impl Default for ApplicationConfig {
 impl SaveInRonFile for ApplicationConfig {
     const FILE_NAME: &'static str = "app_config.ron";
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::app_config::ApplicationConfig;
+    use crate::request_log::SlowRequestThreshold;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn test_insecure_session_keys_are_replaced() {
+        let cases = [
+            (vec![], true),
+            (vec![7; 16], true),
+            (vec![0; 64], true),
+            ((0..64).collect(), false),
+        ];
+
+        for (key, expected_replaced) in cases {
+            // Given
+            let mut config = ApplicationConfig {
+                session_key: key.clone(),
+                ..Default::default()
+            };
+
+            // When
+            let replaced = config.ensure_secure_session_key();
+
+            // Then
+            assert_eq!(replaced, expected_replaced, "key: {:?}", key);
+            assert_eq!(config.session_key != key, expected_replaced);
+            assert!(config.session_key.len() >= 32);
+        }
+    }
+
+    #[test]
+    fn test_binding_ip_uses_bind_address_and_port() {
+        let config = |bind_address: &str, port: u32| ApplicationConfig {
+            bind_address: bind_address.to_string(),
+            port,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config("127.0.0.1", 8080).binding_ip(),
+            Ok("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(
+            config("::1", 8080).binding_ip(),
+            Ok("[::1]:8080".parse().unwrap())
+        );
+        assert!(config("localhost", 8080).binding_ip().is_err());
+        assert!(config("0.0.0.0", 70000).binding_ip().is_err());
+    }
+
+    #[test]
+    fn test_session_ttl_must_be_positive() {
+        let config = |session_ttl_days: i64| ApplicationConfig {
+            session_ttl_days,
+            ..Default::default()
+        };
+
+        assert_eq!(config(30).session_ttl(), Ok(time::Duration::days(30)));
+        assert!(config(0).session_ttl().is_err());
+        assert!(config(-1).session_ttl().is_err());
+    }
+
+    #[test]
+    fn test_zero_slow_request_warning_secs_turns_the_warning_off() {
+        let config = |slow_request_warning_secs: u64| ApplicationConfig {
+            slow_request_warning_secs,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config(3).slow_request_threshold(),
+            SlowRequestThreshold(Some(Duration::from_secs(3)))
+        );
+        assert_eq!(
+            config(0).slow_request_threshold(),
+            SlowRequestThreshold(None)
+        );
+    }
+}
@@ -49,6 +49,60 @@ impl AuthData {
         }
     }
 
+    pub fn user_count(&self) -> usize {
+        self.users.read().unwrap().len()
+    }
+
+    pub fn is_admin(&self, id: &UserId) -> bool {
+        self.users
+            .read()
+            .unwrap()
+            .get(id)
+            .is_some_and(|info| info.is_admin)
+    }
+
+    pub fn has_admin(&self) -> bool {
+        self.users
+            .read()
+            .unwrap()
+            .values()
+            .any(|info| info.is_admin)
+    }
+
+    /// Id, name and admin flag of all users. Without the passwords.
+    pub fn users(&self) -> Vec<(UserId, String, bool)> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, info)| (*id, info.name.clone(), info.is_admin))
+            .collect()
+    }
+
+    /// The password of the user with `id`, if that user is called `name`.
+    pub fn password(&self, id: &UserId, name: &str) -> Option<String> {
+        self.users
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|info| info.name == name)
+            .map(|info| info.password.clone())
+    }
+
+    /// Replaces all users, for restoring a [backup](crate::backup).
+    pub fn replace_users(&self, users: Users) {
+        *self.users.write().unwrap() = users;
+    }
+
+    pub fn has_user(&self, id: &UserId) -> bool {
+        self.users.read().unwrap().contains_key(id)
+    }
+
+    /// Returns false if the user did not exist.
+    pub fn delete_user(&self, id: &UserId) -> bool {
+        self.users.write().unwrap().remove(id).is_some()
+    }
+
     pub fn validate_password(&self, user_name: &str, password: &str) -> Option<UserId> {
         let users = self.users.read().unwrap();
 
@@ -76,6 +130,8 @@ impl Default for AuthData {
             UserInfo {
                 name: "test".to_string(),
                 password: "testing".to_string(),
+                // So there is always a user that can manage the others.
+                is_admin: true,
             },
         );
 
@@ -93,6 +149,17 @@ pub struct AuthenticationResult {
 }
 
 impl AuthenticationResult {
+    /// For tests of endpoints, which don't go through the authentication middleware.
+    #[cfg(test)]
+    pub fn for_user(id: UserId, name: &str) -> Self {
+        AuthenticationResult {
+            user: UserRequestInfo {
+                id,
+                name: name.to_string(),
+            },
+        }
+    }
+
     pub fn user_id(&self) -> &UserId {
         &self.user.id
     }
@@ -0,0 +1,391 @@
+//! A backup of the whole server as a single json document: the users, their settings and their
+//! collections. Unlike the RON files, the format is versioned, so a backup can be restored on
+//! another machine, or into a newer version of the server. Only available to admins.
+
+use crate::auth::AuthData;
+use crate::error::Error;
+use crate::rss_collection::{RssCollections, UserCollections};
+use crate::user_settings::UserSettingsCollection;
+use crate::users::{UserId, UserInfo, Users};
+use crate::{Authenticated, SaveInRonFile, VERSION};
+use actix_web::http::header::ContentDisposition;
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use rss_com_lib::message_body::{AdminBackupRequest, AdminRestoreResponse, UserSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Version of the [Backup] format. Increase this when the format changes in a way that older
+/// servers can't read, and convert the older formats in [Backup::users].
+///
+/// - 1: The first version.
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+/// A backup contains all entries of all feeds, so it can be much larger than other requests.
+pub const MAX_BACKUP_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct BackupUser {
+    id: UserId,
+    name: String,
+    /// [None] if the backup was made without passwords.
+    password: Option<String>,
+    is_admin: bool,
+}
+
+/// How a [Backup] is written. Borrows the collections and settings, so they don't have to be
+/// copied.
+#[derive(Serialize)]
+struct BackupRef<'a> {
+    format_version: u32,
+    server_version: &'static str,
+    created: DateTime<Utc>,
+    users: Vec<BackupUser>,
+    collections: &'a HashMap<UserId, UserCollections>,
+    settings: &'a HashMap<UserId, UserSettings>,
+}
+
+/// The feeds in the collections are read the same way as from the RON files, so fields that
+/// older versions didn't have get their default. The entry keys of older versions are migrated
+/// after restoring, see [RssCollections::migrate_entry_keys].
+#[derive(Deserialize)]
+pub struct Backup {
+    format_version: u32,
+    /// Version of the server that made the backup.
+    server_version: String,
+    created: DateTime<Utc>,
+    users: Vec<BackupUser>,
+    collections: HashMap<UserId, UserCollections>,
+    /// Users that never changed their settings don't have an entry.
+    #[serde(default)]
+    settings: HashMap<UserId, UserSettings>,
+}
+
+impl Backup {
+    /// Checks whether the backup can be restored, and makes the users to restore.
+    /// Users that have no password in the backup keep the password they have on this server,
+    /// which is looked up with `current_password(id, name)`.
+    fn users(
+        &self,
+        current_password: impl Fn(&UserId, &str) -> Option<String>,
+    ) -> Result<Users, String> {
+        if self.format_version > BACKUP_FORMAT_VERSION {
+            return Err(format!(
+                "The backup has format version {}, this server only knows up to version {}. Restore it with a newer version of the server",
+                self.format_version, BACKUP_FORMAT_VERSION
+            ));
+        }
+        if !self.users.iter().any(|user| user.is_admin) {
+            // Otherwise nobody could manage the server after restoring.
+            return Err("The backup has no admin users".to_string());
+        }
+
+        let mut users = Users::default();
+        let mut names = HashSet::new();
+        for user in &self.users {
+            if !names.insert(&user.name) {
+                return Err(format!(
+                    "User name `{}` is in the backup more than once",
+                    user.name
+                ));
+            }
+            let Some(password) = user
+                .password
+                .clone()
+                .or_else(|| current_password(&user.id, &user.name))
+            else {
+                return Err(format!(
+                    "User `{}` has no password in the backup, and does not exist on this server",
+                    user.name
+                ));
+            };
+
+            let info = UserInfo {
+                name: user.name.clone(),
+                password,
+                is_admin: user.is_admin,
+            };
+            if users.insert(user.id, info).is_some() {
+                return Err(format!(
+                    "User id {} is in the backup more than once",
+                    user.id.0
+                ));
+            }
+        }
+
+        if let Some(orphan) = self
+            .collections
+            .keys()
+            .chain(self.settings.keys())
+            .find(|id| !users.contains_key(id))
+        {
+            return Err(format!(
+                "The backup has feeds or settings of user id {}, who is not in the backup",
+                orphan.0
+            ));
+        }
+
+        Ok(users)
+    }
+}
+
+/// Everything on the server as a json file download.
+#[post("/admin/backup")]
+pub async fn create_backup(
+    request: web::Json<AdminBackupRequest>,
+    auth: Authenticated,
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+    settings: web::Data<UserSettingsCollection>,
+) -> impl Responder {
+    if !auth_data.is_admin(auth.user_id()) {
+        warn!("User `{}` tried to make a backup", auth.user_name());
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let mut users: Vec<BackupUser> = auth_data
+        .users()
+        .into_iter()
+        .map(|(id, name, is_admin)| {
+            let password = request
+                .include_passwords
+                .then(|| auth_data.password(&id, &name))
+                .flatten();
+            BackupUser {
+                id,
+                name,
+                password,
+                is_admin,
+            }
+        })
+        .collect();
+    users.sort_by_key(|user| user.id.0);
+
+    info!(
+        "User `{}` made a backup, {} passwords",
+        auth.user_name(),
+        if request.include_passwords {
+            "with"
+        } else {
+            "without"
+        }
+    );
+
+    let created = Utc::now();
+    let collections = collections.read().unwrap();
+    let settings = settings.read().unwrap();
+    HttpResponse::Ok()
+        .insert_header(ContentDisposition::attachment(format!(
+            "rss_r_backup_{}.json",
+            created.format("%Y-%m-%d")
+        )))
+        .json(BackupRef {
+            format_version: BACKUP_FORMAT_VERSION,
+            server_version: VERSION,
+            created,
+            users,
+            collections: &collections,
+            settings: &settings,
+        })
+}
+
+/// Replaces all users, settings and collections with the ones in the [Backup].
+/// Nothing is changed if the backup can't be restored.
+///
+/// Takes the raw payload instead of json, so the body is only read after checking that the user
+/// is an admin. A backup can be up to [MAX_BACKUP_BYTES], much more than other requests.
+#[post("/admin/restore")]
+pub async fn restore(
+    request: HttpRequest,
+    payload: web::Payload,
+    auth: Authenticated,
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+    settings: web::Data<UserSettingsCollection>,
+) -> impl Responder {
+    if !auth_data.is_admin(auth.user_id()) {
+        warn!("User `{}` tried to restore a backup", auth.user_name());
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let body = match payload.to_bytes_limited(MAX_BACKUP_BYTES).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(error)) => return HttpResponse::from_error(error),
+        Err(_) => {
+            return HttpResponse::PayloadTooLarge().body(format!(
+                "A backup can be at most {} MiB",
+                MAX_BACKUP_BYTES / 1024 / 1024
+            ))
+        }
+    };
+    let backup: Backup = match serde_json::from_slice(&body) {
+        Ok(backup) => backup,
+        Err(error) => {
+            // The same response as the other endpoints give, see `error::json_config`.
+            warn!("Malformed request body for `{}`: {}", request.path(), error);
+            return HttpResponse::from_error(Error::MalformedRequestBody(error.to_string()));
+        }
+    };
+    let users = match backup.users(|id, name| auth_data.password(id, name)) {
+        Ok(users) => users,
+        Err(message) => {
+            warn!(
+                "User `{}` tried to restore a backup that can't be restored: {}",
+                auth.user_name(),
+                message
+            );
+            return HttpResponse::BadRequest().body(message);
+        }
+    };
+
+    info!(
+        "User `{}` restores the backup of {}, made by version {}",
+        auth.user_name(),
+        backup.created,
+        backup.server_version
+    );
+    let response = AdminRestoreResponse {
+        users: users.len(),
+        feeds: backup
+            .collections
+            .values()
+            .flat_map(|user_collections| user_collections.values())
+            .map(|collection| collection.len())
+            .sum(),
+    };
+
+    auth_data.replace_users(users);
+    *collections.write().unwrap() = backup.collections;
+    collections.migrate_entry_keys();
+    *settings.write().unwrap() = backup.settings;
+
+    // Saved right away, like when deleting a user.
+    auth_data.save();
+    collections.save();
+    settings.save();
+
+    HttpResponse::Ok().json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backup::{Backup, BackupRef, BackupUser, BACKUP_FORMAT_VERSION};
+    use crate::rss_collection::UserCollections;
+    use crate::users::UserId;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::UserSettings;
+    use std::collections::HashMap;
+
+    fn user(id: u32, name: &str, password: Option<&str>, is_admin: bool) -> BackupUser {
+        BackupUser {
+            id: UserId(id),
+            name: name.to_string(),
+            password: password.map(str::to_string),
+            is_admin,
+        }
+    }
+
+    fn backup(users: Vec<BackupUser>, collection_users: &[u32]) -> Backup {
+        let collections = collection_users
+            .iter()
+            .map(|id| (UserId(*id), UserCollections::default()))
+            .collect();
+        let settings = HashMap::new();
+        let json = serde_json::to_string(&BackupRef {
+            format_version: BACKUP_FORMAT_VERSION,
+            server_version: "0.0.1",
+            created: Utc::now(),
+            users,
+            collections: &collections,
+            settings: &settings,
+        })
+        .unwrap();
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_backup_can_be_read_back() {
+        // Given
+        let settings = HashMap::from([(UserId(2), UserSettings::default())]);
+        let collections = HashMap::from([(UserId(2), UserCollections::default())]);
+        let written = BackupRef {
+            format_version: BACKUP_FORMAT_VERSION,
+            server_version: "0.0.1",
+            created: Utc::now(),
+            users: vec![user(2, "admin", Some("secret"), true)],
+            collections: &collections,
+            settings: &settings,
+        };
+
+        // When
+        let json = serde_json::to_string(&written).unwrap();
+        let read: Backup = serde_json::from_str(&json).unwrap();
+
+        // Then
+        assert_eq!(read.users, written.users);
+        assert_eq!(read.settings, settings);
+        assert_eq!(read.created, written.created);
+        let users = read.users(|_, _| None).unwrap();
+        assert_eq!(users[&UserId(2)].password, "secret");
+    }
+
+    #[test]
+    fn test_users_without_password_keep_their_current_password() {
+        // Given
+        let backup = backup(
+            vec![
+                user(1, "admin", None, true),
+                user(2, "new", Some("new password"), false),
+            ],
+            &[1],
+        );
+        let current_password = |id: &UserId, name: &str| {
+            (*id == UserId(1) && name == "admin").then(|| "current password".to_string())
+        };
+
+        // When
+        let users = backup.users(current_password).unwrap();
+
+        // Then
+        assert_eq!(users[&UserId(1)].password, "current password");
+        assert_eq!(users[&UserId(2)].password, "new password");
+    }
+
+    #[test]
+    fn test_backups_that_cant_be_restored_are_refused() {
+        let no_password = |_: &UserId, _: &str| None;
+
+        let mut newer = backup(vec![user(1, "admin", Some("a"), true)], &[]);
+        newer.format_version = BACKUP_FORMAT_VERSION + 1;
+        assert!(newer.users(no_password).is_err());
+
+        let no_admin = backup(vec![user(1, "user", Some("a"), false)], &[]);
+        assert!(no_admin.users(no_password).is_err());
+
+        let unknown_user = backup(vec![user(1, "admin", None, true)], &[]);
+        assert!(unknown_user.users(no_password).is_err());
+
+        let duplicate_id = backup(
+            vec![
+                user(1, "admin", Some("a"), true),
+                user(1, "other", Some("b"), false),
+            ],
+            &[],
+        );
+        assert!(duplicate_id.users(no_password).is_err());
+
+        let duplicate_name = backup(
+            vec![
+                user(1, "admin", Some("a"), true),
+                user(2, "admin", Some("b"), false),
+            ],
+            &[],
+        );
+        assert!(duplicate_name.users(no_password).is_err());
+
+        let orphan_collections = backup(vec![user(1, "admin", Some("a"), true)], &[2]);
+        assert!(orphan_collections.users(no_password).is_err());
+    }
+}
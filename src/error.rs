@@ -1,20 +1,52 @@
-use actix_web::{http::StatusCode, HttpResponse};
+use actix_web::error::JsonPayloadError;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use log::warn;
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Authentication failure")]
     AuthenticationError,
+    /// The body of the request could not be read as the json the endpoint expects.
+    #[error("Malformed request body: {0}")]
+    MalformedRequestBody(String),
+}
+
+/// Body of the error responses that clients may want to read, like [Error::MalformedRequestBody].
+#[derive(Serialize, Debug)]
+struct ErrorResponse {
+    error: String,
 }
 
 impl actix_web::error::ResponseError for Error {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        match self {
+            Error::AuthenticationError => {
+                HttpResponse::build(self.status_code()).body(self.to_string())
+            }
+            Error::MalformedRequestBody(_) => {
+                HttpResponse::build(self.status_code()).json(ErrorResponse {
+                    error: self.to_string(),
+                })
+            }
+        }
     }
 
     fn status_code(&self) -> StatusCode {
         match self {
             Error::AuthenticationError => StatusCode::UNAUTHORIZED,
+            Error::MalformedRequestBody(_) => StatusCode::BAD_REQUEST,
         }
     }
 }
+
+/// Makes endpoints that take a [web::Json] body answer with an [ErrorResponse] when the body
+/// doesn't fit, instead of the plain text of actix. That way someone working on a client
+/// can see what is wrong, and the log shows which endpoint got a body it didn't expect.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|error: JsonPayloadError, request: &HttpRequest| {
+        warn!("Malformed request body for `{}`: {}", request.path(), error);
+        Error::MalformedRequestBody(error.to_string()).into()
+    })
+}
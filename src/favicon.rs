@@ -0,0 +1,107 @@
+//! Favicons of the sites the feeds belong to, so the feed list is easier to scan.
+
+use crate::feed_requester::Favicon;
+use crate::rss_collection::RssCollections;
+use crate::{Authenticated, FeedRequester};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{get, web, HttpResponse, Responder};
+use log::info;
+use rss_com_lib::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+const FAVICON_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Favicons rarely change, so the browser can keep them for a week.
+const FAVICON_MAX_AGE_SECONDS: u32 = 60 * 60 * 24 * 7;
+
+/// The favicons per host. [None] if the site has no favicon, so it isn't downloaded again.
+/// Only kept in memory, downloading them again after a restart is cheap enough.
+#[derive(Default)]
+pub struct FaviconCache(RwLock<HashMap<String, Option<Favicon>>>);
+
+#[derive(Deserialize, Debug)]
+pub struct FaviconQuery {
+    host: String,
+}
+
+/// The favicon of the site with the given host, for example `/api/favicon?host=example.com`.
+/// Only hosts of the feeds of the user can be requested, so the server can't be used to
+/// download from arbitrary sites.
+#[get("/favicon")]
+pub async fn get_favicon(
+    query: web::Query<FaviconQuery>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+    cache: web::Data<FaviconCache>,
+    requester: web::Data<FeedRequester>,
+) -> impl Responder {
+    // Checked before the cache, so users can't see which sites the others follow.
+    let Some(site_url) = site_url_of_host(&collections, &auth, &query.host) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    if let Some(cached) = cache.0.read().unwrap().get(&query.host) {
+        return favicon_response(cached.as_ref());
+    }
+
+    let favicon = match requester
+        .request_favicon(&site_url, FAVICON_REQUEST_TIMEOUT)
+        .await
+    {
+        Ok(favicon) => Some(favicon),
+        Err(error) => {
+            info!("No favicon for `{}`: {}", query.host, error);
+            None
+        }
+    };
+
+    let response = favicon_response(favicon.as_ref());
+    cache.0.write().unwrap().insert(query.host.clone(), favicon);
+
+    response
+}
+
+/// The website of a feed of the user with the given host.
+fn site_url_of_host(collections: &RssCollections, auth: &Authenticated, host: &str) -> Option<Url> {
+    let collections = collections.read().unwrap();
+    let feeds = collections
+        .get(auth.user_id())?
+        .values()
+        .flat_map(|collection| collection.iter());
+
+    for (feed_url, feed) in feeds {
+        if let Some(home_url) = &feed.info().home_url {
+            if home_url.host() == Some(host) {
+                return Some(home_url.clone());
+            }
+        }
+        if feed_url.host() == Some(host) {
+            // The feed has no home url, so try the root of the site instead.
+            let scheme = feed_url.as_str().split("://").next().unwrap_or("https");
+            return Some(Url::new(format!("{}://{}", scheme, host)));
+        }
+    }
+
+    None
+}
+
+fn favicon_response(favicon: Option<&Favicon>) -> HttpResponse {
+    let mut response = match favicon {
+        Some(_) => HttpResponse::Ok(),
+        None => HttpResponse::NotFound(),
+    };
+    // Also cache missing favicons, so the browser doesn't ask again for every page load.
+    response.insert_header(CacheControl(vec![
+        CacheDirective::Private,
+        CacheDirective::MaxAge(FAVICON_MAX_AGE_SECONDS),
+    ]));
+
+    match favicon {
+        Some(favicon) => response
+            .content_type(favicon.content_type.as_str())
+            .body(favicon.bytes.clone()),
+        None => response.finish(),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::full_error_to_string;
+use rss_com_lib::rss_feed::FetchErrorKind;
+use thiserror::Error;
+
+/// Why downloading a feed failed. Keeps apart the problems a user can do something about
+/// (a wrong url, a site that moved its feed) from the ones that are probably temporary
+/// (timeouts, a server that is down).
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FeedFetchError {
+    #[error("The feed took too long to download")]
+    Timeout,
+    /// Also used for hosts that are refused because they are private addresses.
+    #[error("Could not look up the host of the feed: {0}")]
+    Dns(String),
+    #[error("Could not connect to the server of the feed: {0}")]
+    Connect(String),
+    #[error("The server of the feed responded with status {0}")]
+    HttpStatus(u16),
+    #[error("Could not read the feed: {0}")]
+    Parse(String),
+    /// The server sent a web page, for example a "rate limited" page.
+    #[error("Expected a feed but got HTML (status {status})")]
+    NotAFeed { status: u16 },
+    #[error("Feed is larger than the maximum of {max_bytes} bytes")]
+    TooLarge { max_bytes: usize },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl FeedFetchError {
+    pub fn kind(&self) -> FetchErrorKind {
+        match self {
+            FeedFetchError::Timeout => FetchErrorKind::Timeout,
+            FeedFetchError::Dns(_) => FetchErrorKind::Dns,
+            FeedFetchError::Connect(_) => FetchErrorKind::Connect,
+            FeedFetchError::HttpStatus(status) => FetchErrorKind::HttpStatus(*status),
+            FeedFetchError::Parse(_) => FetchErrorKind::Parse,
+            FeedFetchError::NotAFeed { .. } => FetchErrorKind::NotAFeed,
+            FeedFetchError::TooLarge { .. } => FetchErrorKind::TooLarge,
+            FeedFetchError::Other(_) => FetchErrorKind::Other,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FeedFetchError {
+    fn from(error: reqwest::Error) -> Self {
+        // The underlying reason, such as the host being refused, is in the sources.
+        let message = full_error_to_string(&error);
+
+        if error.is_timeout() {
+            FeedFetchError::Timeout
+        } else if let Some(status) = error.status() {
+            FeedFetchError::HttpStatus(status.as_u16())
+        } else if error.is_connect() {
+            // Reqwest doesn't have a separate kind for failed lookups, they are connect errors
+            // that mention dns.
+            if message.contains("dns error") {
+                FeedFetchError::Dns(message)
+            } else {
+                FeedFetchError::Connect(message)
+            }
+        } else if error.is_body() {
+            // The connection broke while the feed was being downloaded.
+            FeedFetchError::Connect(message)
+        } else {
+            FeedFetchError::Other(message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feed_fetch_error::FeedFetchError;
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::rss_feed::FetchErrorKind;
+
+    #[test]
+    fn test_kind_keeps_the_http_status() {
+        // Given
+        let error = FeedFetchError::HttpStatus(404);
+
+        // Then
+        assert_eq!(error.kind(), FetchErrorKind::HttpStatus(404));
+        assert_eq!(
+            error.to_string(),
+            "The server of the feed responded with status 404"
+        );
+    }
+}
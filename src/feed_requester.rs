@@ -1,61 +1,246 @@
-use actix_web_lab::__reexports::futures_util::future;
-use reqwest::ClientBuilder;
-use rss_com_lib::rss_feed::{FeedEntries, FeedEntry};
+use crate::feed_fetch_error::FeedFetchError;
+use crate::fetch_timings::fetch_timings;
+use crate::metrics::metrics;
+use actix_web_lab::__reexports::futures_util::{stream, StreamExt};
+use encoding_rs::{Encoding, UTF_8};
+use log::warn;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{redirect, ClientBuilder};
+use rss_com_lib::rss_feed::{FeedEntries, FeedEntry, ParseDiagnostics};
 use rss_com_lib::Url;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Same maximum as the default redirect policy of reqwest.
+const MAX_REDIRECTS: usize = 10;
+/// Favicons are small, anything larger than this is not a favicon.
+const MAX_FAVICON_BYTES: usize = 100_000;
+/// Web pages are only downloaded to find the favicon link in them.
+const MAX_PAGE_BYTES: usize = 1_000_000;
+/// How many feeds are downloaded at the same time, so that updating or adding a lot of feeds
+/// doesn't open hundreds of connections at once.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+/// Feeds can have a lot of categories, only the most common ones are suggested.
+const MAX_SUGGESTED_TAGS: usize = 10;
+
+/// How long to wait for feed downloads.
+#[derive(Clone, Copy, Debug)]
+pub struct FeedTimeouts {
+    /// When adding or testing a new feed. The user is waiting for this, so it is shorter.
+    pub new_feed: core::time::Duration,
+    /// When updating feeds that are already followed, both in the background and when the user
+    /// asks for it.
+    pub update: core::time::Duration,
+}
 
 pub struct FeedRequester {
     reqwest_client: reqwest::Client,
+    /// Downloads of feeds that are larger than this are aborted.
+    max_feed_bytes: usize,
+    /// Whether feeds may be downloaded from private and internal addresses, such as `localhost`.
+    allow_private_hosts: bool,
+    /// Feeds that were downloaded successfully less than `cache_duration` ago, with the moment
+    /// they were downloaded. Only kept in memory.
+    cache: Mutex<HashMap<Url, (Instant, Feed)>>,
+    cache_duration: core::time::Duration,
 }
 
-impl Default for FeedRequester {
-    fn default() -> Self {
+impl FeedRequester {
+    /// The `connect_timeout` and `read_timeout` apply to every request, on top of the total
+    /// timeout given per request. They make sure slow hosts (for example ones that accept the
+    /// connection and then send the bytes one at a time) are dropped quickly.
+    ///
+    /// If `allow_private_hosts` is false, feeds on private and internal addresses are refused.
+    /// Otherwise users could use the server to reach services that are not reachable from the
+    /// outside.
+    ///
+    /// Feeds that were downloaded less than `cache_duration` ago are not downloaded again, but
+    /// served from memory. That way refreshing twice in a row, or testing a feed and then adding
+    /// it, doesn't download it twice.
+    pub fn new(
+        max_feed_bytes: usize,
+        connect_timeout: core::time::Duration,
+        read_timeout: core::time::Duration,
+        allow_private_hosts: bool,
+        cache_duration: core::time::Duration,
+    ) -> Self {
+        let mut builder = ClientBuilder::new()
+            .connect_timeout(connect_timeout)
+            .read_timeout(read_timeout)
+            // Sets the `Accept-Encoding` header, and decompresses the responses. Feeds are text,
+            // so this saves a lot of bandwidth.
+            .gzip(true)
+            .deflate(true)
+            .brotli(true);
+
+        if !allow_private_hosts {
+            builder = builder
+                // Reqwest connects to the addresses this resolver returns. So there is no chance for
+                // the host to resolve to a different (private) address between checking and connecting.
+                .dns_resolver(Arc::new(PublicAddressResolver))
+                // Redirects to ip addresses don't go through the resolver, so they are checked here.
+                .redirect(redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= MAX_REDIRECTS {
+                        attempt.error("Too many redirects")
+                    } else if let Some(ip) = literal_ip_address(attempt.url()) {
+                        if is_private_address(ip) {
+                            attempt.error(private_address_message(ip))
+                        } else {
+                            attempt.follow()
+                        }
+                    } else {
+                        attempt.follow()
+                    }
+                }));
+        }
+
         FeedRequester {
-            reqwest_client: ClientBuilder::new()
-                .build()
-                .expect("Could not build reqwest client"),
+            reqwest_client: builder.build().expect("Could not build reqwest client"),
+            max_feed_bytes,
+            allow_private_hosts,
+            cache: Mutex::new(HashMap::new()),
+            cache_duration,
         }
     }
-}
 
-impl FeedRequester {
-    /// Downloads all the feeds concurrently.
+    /// Downloads all the feeds concurrently, at most [MAX_CONCURRENT_REQUESTS] at a time.
     pub async fn request_feeds(
         &self,
         urls: &HashSet<Url>,
         timeout: core::time::Duration,
-    ) -> HashMap<Url, Result<Feed, Box<dyn Error>>> {
-        let results =
-            future::join_all(urls.iter().map(|url| self.request_feed(url, timeout))).await;
+    ) -> HashMap<Url, Result<Feed, FeedFetchError>> {
+        self.request_feeds_reporting(urls, timeout, |_, _| {}).await
+    }
 
-        results.into_iter().collect()
+    /// Same as [FeedRequester::request_feeds], but calls `report` with the url and whether it
+    /// went well as soon as each feed is done. For showing the progress of long updates.
+    pub async fn request_feeds_reporting(
+        &self,
+        urls: &HashSet<Url>,
+        timeout: core::time::Duration,
+        report: impl Fn(&Url, bool),
+    ) -> HashMap<Url, Result<Feed, FeedFetchError>> {
+        stream::iter(urls.iter().map(|url| self.request_feed(url, timeout)))
+            .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+            .inspect(|(url, result)| report(url, result.is_ok()))
+            .collect()
+            .await
     }
 
     pub async fn request_feed(
         &self,
         url: &Url,
         timeout: core::time::Duration,
-    ) -> (Url, Result<Feed, Box<dyn Error>>) {
-        (url.clone(), self.download_feed(url, timeout).await)
+    ) -> (Url, Result<Feed, FeedFetchError>) {
+        if let Some(feed) = self.cached_feed(url) {
+            return (url.clone(), Ok(feed));
+        }
+
+        let start = Instant::now();
+        let result = self.download_feed(url, timeout).await;
+        let duration = start.elapsed();
+
+        let metrics = metrics();
+        metrics.feed_fetch_duration.observe(duration.as_secs_f64());
+        metrics.feed_fetches.inc();
+        if let Err(error) = &result {
+            metrics
+                .feed_fetch_failures
+                .with_label_values(&[error.kind().label()])
+                .inc();
+        }
+
+        fetch_timings().record(url, duration, result.is_ok());
+        // Feeds that come this close to the timeout will probably hit it sooner or later.
+        if duration * 5 > timeout * 4 {
+            warn!(
+                "Downloading `{}` took {:.1}s, close to the timeout of {:.1}s",
+                url,
+                duration.as_secs_f64(),
+                timeout.as_secs_f64()
+            );
+        }
+
+        if let Ok(feed) = &result {
+            self.cache_feed(url, feed);
+        }
+
+        (url.clone(), result)
+    }
+
+    fn cached_feed(&self, url: &Url) -> Option<Feed> {
+        self.cache
+            .lock()
+            .unwrap()
+            .get(url)
+            .filter(|(downloaded, _)| downloaded.elapsed() < self.cache_duration)
+            .map(|(_, feed)| feed.clone())
+    }
+
+    fn cache_feed(&self, url: &Url, feed: &Feed) {
+        if self.cache_duration.is_zero() {
+            return;
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        // Forget the expired feeds, so the cache doesn't grow with every feed that was ever downloaded.
+        cache.retain(|_, (downloaded, _)| downloaded.elapsed() < self.cache_duration);
+        cache.insert(url.clone(), (Instant::now(), feed.clone()));
     }
 
     async fn download_feed(
         &self,
         url: &Url,
         timeout: core::time::Duration,
-    ) -> Result<Feed, Box<dyn Error>> {
-        // TODO (Wybe 2022-07-18): Sanitize url.
-        let content = self
+    ) -> Result<Feed, FeedFetchError> {
+        if let Ok(parsed_url) = reqwest::Url::parse(url.as_str()) {
+            self.refuse_private_ip_address(&parsed_url)?;
+        }
+
+        let mut response = self
             .reqwest_client
             .get(url.clone_string())
             .timeout(timeout)
             .send()
-            .await?
-            .bytes()
             .await?;
 
-        let raw_feed = feed_rs::parser::parse(&content[..])?;
+        let status = response.status();
+        // Some sites return a 200 with an html page (for example a "rate limited" page)
+        // instead of the feed. We want to report that as an error, instead of as an empty feed.
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let is_html = content_type
+            .as_deref()
+            .map(is_html_content_type)
+            .unwrap_or(false);
+
+        let content = self
+            .read_body_with_limit(&mut response, self.max_feed_bytes)
+            .await?;
+        let content = decode_to_utf8(
+            content,
+            content_type.as_deref().and_then(charset_of_content_type),
+        );
+
+        let raw_feed = match parse_feed(&content[..]) {
+            Ok(raw_feed) if !(is_html && raw_feed.entries.is_empty()) => raw_feed,
+            // An error page, such as a 404, is not worth reporting as a broken feed.
+            _ if !status.is_success() => return Err(FeedFetchError::HttpStatus(status.as_u16())),
+            Err(error) if !is_html => return Err(FeedFetchError::Parse(error.to_string())),
+            _ => {
+                return Err(FeedFetchError::NotAFeed {
+                    status: status.as_u16(),
+                })
+            }
+        };
 
         let entries = FeedEntries::new(
             raw_feed
@@ -66,15 +251,995 @@ impl FeedRequester {
         );
 
         let feed = Feed {
+            parse_diagnostics: parse_diagnostics(&raw_feed, &entries, &content),
+            home_url: home_url(&raw_feed.links, url),
+            recommended_refresh_interval_minutes: recommended_refresh_interval_minutes(
+                &raw_feed, &content,
+            ),
+            suggested_tags: suggested_tags(&raw_feed),
+            description: raw_feed
+                .description
+                .as_ref()
+                .map(|text| text.content.trim().to_string())
+                .filter(|description| !description.is_empty()),
+            icon_url: icon_url(&raw_feed),
+            language: raw_feed.language,
             title: raw_feed.title.map(|text| text.content).unwrap_or_default(),
             entries,
         };
 
         Ok(feed)
     }
+
+    /// Downloads the favicon of a site. Uses the `<link rel="icon">` of the page if it has one,
+    /// and `/favicon.ico` otherwise.
+    pub async fn request_favicon(
+        &self,
+        site_url: &Url,
+        timeout: core::time::Duration,
+    ) -> Result<Favicon, Box<dyn Error>> {
+        let page_url = reqwest::Url::parse(site_url.as_str())?;
+        self.refuse_private_ip_address(&page_url)?;
+
+        let linked_icon_url = match self.download(&page_url, timeout, MAX_PAGE_BYTES).await {
+            Ok((_, page)) => find_icon_href(&String::from_utf8_lossy(&page))
+                .and_then(|href| page_url.join(&href).ok()),
+            // There can still be a `/favicon.ico`.
+            Err(_) => None,
+        };
+        let icon_url = match linked_icon_url {
+            Some(icon_url) => icon_url,
+            None => page_url.join("/favicon.ico")?,
+        };
+        self.refuse_private_ip_address(&icon_url)?;
+
+        let (content_type, bytes) = self.download(&icon_url, timeout, MAX_FAVICON_BYTES).await?;
+        let content_type = content_type.unwrap_or_else(|| "image/x-icon".to_string());
+        if !content_type.starts_with("image/") {
+            return Err(format!("Expected a favicon but got `{}`", content_type).into());
+        }
+
+        Ok(Favicon {
+            content_type,
+            bytes,
+        })
+    }
+
+    /// Downloads the body of a successful response, and its content type.
+    async fn download(
+        &self,
+        url: &reqwest::Url,
+        timeout: core::time::Duration,
+        max_bytes: usize,
+    ) -> Result<(Option<String>, Vec<u8>), Box<dyn Error>> {
+        let mut response = self
+            .reqwest_client
+            .get(url.clone())
+            .timeout(timeout)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let content = self.read_body_with_limit(&mut response, max_bytes).await?;
+
+        Ok((content_type, content))
+    }
+
+    /// Urls with an ip address instead of a host name don't go through the dns resolver,
+    /// so they are checked here.
+    fn refuse_private_ip_address(&self, url: &reqwest::Url) -> Result<(), FeedFetchError> {
+        if self.allow_private_hosts {
+            return Ok(());
+        }
+
+        match literal_ip_address(url) {
+            Some(ip) if is_private_address(ip) => {
+                Err(FeedFetchError::Dns(private_address_message(ip)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the response body chunk by chunk, so we can stop as soon as it is larger than
+    /// `max_bytes`. Otherwise a huge (or endless) response could use up all the memory of
+    /// the server.
+    async fn read_body_with_limit(
+        &self,
+        response: &mut reqwest::Response,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, FeedFetchError> {
+        let too_large_error = || FeedFetchError::TooLarge { max_bytes };
+
+        // For compressed responses, reqwest removes the `Content-Length`, and the chunks are
+        // already decompressed. So the limit applies to the decompressed size.
+
+        if let Some(length) = response.content_length() {
+            if length > max_bytes as u64 {
+                return Err(too_large_error());
+            }
+        }
+
+        let mut content = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if content.len() + chunk.len() > max_bytes {
+                return Err(too_large_error());
+            }
+            content.extend_from_slice(&chunk);
+        }
+
+        Ok(content)
+    }
 }
 
+#[derive(Clone)]
 pub struct Feed {
     pub title: String,
+    /// The website the feed belongs to.
+    pub home_url: Option<Url>,
+    /// How often the feed says it is updated.
+    pub recommended_refresh_interval_minutes: Option<u64>,
     pub entries: FeedEntries,
+    pub parse_diagnostics: ParseDiagnostics,
+    /// The categories of the feed, to suggest as tags when adding it. See [suggested_tags].
+    pub suggested_tags: Vec<String>,
+    pub description: Option<String>,
+    pub language: Option<String>,
+    pub icon_url: Option<Url>,
+}
+
+pub struct Favicon {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Finds the `href` of the first `<link>` with `icon` in its `rel`, such as `rel="icon"` and
+/// `rel="shortcut icon"`.
+fn find_icon_href(html: &str) -> Option<String> {
+    // Only ascii characters are lowercased, so the positions stay the same as in `html`.
+    let lowercase = html.to_ascii_lowercase();
+
+    lowercase.match_indices("<link").find_map(|(start, _)| {
+        let end = start + lowercase[start..].find('>')?;
+        let tag = &lowercase[start..end];
+
+        let is_icon = tag[attribute_value_range(tag, "rel")?]
+            .split_ascii_whitespace()
+            .any(|rel| rel == "icon");
+        if !is_icon {
+            return None;
+        }
+
+        // The href is taken from the original html, because urls are case-sensitive.
+        let href = attribute_value_range(tag, "href")?;
+        Some(html[start + href.start..start + href.end].to_string())
+    })
+}
+
+/// Where the value of an attribute is in a html tag, without the quotes around it.
+fn attribute_value_range(tag: &str, name: &str) -> Option<Range<usize>> {
+    let after_equals = tag.match_indices(name).find_map(|(index, _)| {
+        // Skip attributes that only end with the name, like `data-rel`.
+        if !tag[..index].ends_with(|c: char| c.is_ascii_whitespace()) {
+            return None;
+        }
+        let after_name = &tag[index + name.len()..];
+        let after_equals = after_name.trim_start().strip_prefix('=')?;
+        Some(tag.len() - after_equals.len())
+    })?;
+
+    let value_start = tag.len() - tag[after_equals..].trim_start().len();
+    let value_end = match tag[value_start..].chars().next()? {
+        quote @ ('"' | '\'') => {
+            let value_end = value_start + 1 + tag[value_start + 1..].find(quote)?;
+            return Some(value_start + 1..value_end);
+        }
+        _ => tag[value_start..]
+            .find(|c: char| c.is_ascii_whitespace())
+            .map(|length| value_start + length)
+            .unwrap_or(tag.len()),
+    };
+
+    Some(value_start..value_end)
+}
+
+/// Parses the feed without making up ids for the entries that have none. Otherwise feed-rs
+/// generates one from the link and title, or a random one if there is no link. Then the guid of
+/// an entry would change with its title, or even on every download.
+fn parse_feed(content: &[u8]) -> feed_rs::parser::ParseFeedResult<feed_rs::model::Feed> {
+    feed_rs::parser::Builder::new()
+        .id_generator(|_, _, _| String::new())
+        .build()
+        .parse(content)
+}
+
+/// Finds the link to the website among the channel-level links of a feed.
+/// Atom feeds also link to themselves with `rel="self"`, those links are skipped.
+fn home_url(links: &[feed_rs::model::Link], feed_url: &Url) -> Option<Url> {
+    links
+        .iter()
+        .filter(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+        .filter_map(|link| Url::parse_and_normalize(&link.href).ok())
+        .find(|url| url != feed_url)
+}
+
+/// The icon of the feed, or otherwise its logo.
+fn icon_url(raw_feed: &feed_rs::model::Feed) -> Option<Url> {
+    raw_feed
+        .icon
+        .iter()
+        .chain(raw_feed.logo.iter())
+        .find_map(|image| Url::parse_and_normalize(&image.uri).ok())
+}
+
+/// The categories of the feed itself, followed by the most common categories of its entries.
+/// At most [MAX_SUGGESTED_TAGS].
+fn suggested_tags(raw_feed: &feed_rs::model::Feed) -> Vec<String> {
+    let tag = |category: &feed_rs::model::Category| {
+        category
+            .label
+            .as_deref()
+            .unwrap_or(&category.term)
+            .trim()
+            .to_string()
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    for category in &raw_feed.categories {
+        let tag = tag(category);
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    let mut entry_tag_counts: HashMap<String, usize> = HashMap::new();
+    for category in raw_feed.entries.iter().flat_map(|entry| &entry.categories) {
+        let tag = tag(category);
+        if !tag.is_empty() && !tags.contains(&tag) {
+            *entry_tag_counts.entry(tag).or_default() += 1;
+        }
+    }
+    let mut entry_tags: Vec<(String, usize)> = entry_tag_counts.into_iter().collect();
+    entry_tags.sort_by(|(tag, count), (other_tag, other_count)| {
+        other_count.cmp(count).then(tag.cmp(other_tag))
+    });
+
+    tags.extend(entry_tags.into_iter().map(|(tag, _)| tag));
+    tags.truncate(MAX_SUGGESTED_TAGS);
+    tags
+}
+
+/// How often the feed says it should be downloaded. From the `<ttl>` of rss feeds, or otherwise
+/// the `<sy:updatePeriod>` and `<sy:updateFrequency>` of the syndication module.
+/// Feed-rs doesn't parse the syndication module, so those are looked up in the raw `content`.
+fn recommended_refresh_interval_minutes(
+    raw_feed: &feed_rs::model::Feed,
+    content: &[u8],
+) -> Option<u64> {
+    if let Some(ttl) = raw_feed.ttl.filter(|ttl| *ttl > 0) {
+        return Some(ttl as u64);
+    }
+
+    let content = String::from_utf8_lossy(content);
+    let element_text = |name: &str| {
+        let start = content.find(&format!("<sy:{}>", name))? + name.len() + "<sy:>".len();
+        let end = start + content[start..].find('<')?;
+        Some(content[start..end].trim().to_string())
+    };
+
+    let period_minutes: u64 = match element_text("updatePeriod")?.as_str() {
+        "hourly" => 60,
+        "daily" => 60 * 24,
+        "weekly" => 60 * 24 * 7,
+        "monthly" => 60 * 24 * 30,
+        "yearly" => 60 * 24 * 365,
+        _ => return None,
+    };
+    // The amount of updates per period. Defaults to 1 according to the specification.
+    let frequency = element_text("updateFrequency")
+        .and_then(|frequency| frequency.parse::<u64>().ok())
+        .filter(|frequency| *frequency > 0)
+        .unwrap_or(1);
+
+    Some(period_minutes / frequency)
+}
+
+/// What was lost or filled in while reading the `raw_feed` into the `entries`.
+/// Feed-rs doesn't report the items it couldn't read, so the items are also counted in the
+/// raw `content`. Json feeds have no tags to count, for those only duplicates are noticed.
+fn parse_diagnostics(
+    raw_feed: &feed_rs::model::Feed,
+    entries: &FeedEntries,
+    content: &[u8],
+) -> ParseDiagnostics {
+    let content = String::from_utf8_lossy(content);
+    let raw_items = ["<item>", "<item ", "<entry>", "<entry "]
+        .iter()
+        .map(|tag| content.matches(tag).count())
+        .sum::<usize>()
+        .max(raw_feed.entries.len());
+
+    ParseDiagnostics {
+        entries_parsed: entries.len(),
+        entries_skipped: raw_items.saturating_sub(entries.len()),
+        entries_without_date: raw_feed
+            .entries
+            .iter()
+            .filter(|entry| entry.published.is_none() && entry.updated.is_none())
+            .count(),
+        entries_without_title: raw_feed
+            .entries
+            .iter()
+            .filter(|entry| entry.title.is_none())
+            .count(),
+    }
+}
+
+/// Resolves host names like the system resolver does, but refuses hosts that resolve to
+/// private or internal addresses.
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            // The port is not used by reqwest, it only needs the ip addresses.
+            let addresses: Vec<SocketAddr> = actix_web::rt::task::spawn_blocking(move || {
+                (host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(|addrs| addrs.collect())
+            })
+            .await??;
+
+            if let Some(private) = addresses
+                .iter()
+                .find(|address| is_private_address(address.ip()))
+            {
+                return Err(private_address_message(private.ip()).into());
+            }
+
+            let addrs: Addrs = Box::new(addresses.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Returns the ip address of the url, if the url contains an ip address instead of a host name.
+fn literal_ip_address(url: &reqwest::Url) -> Option<IpAddr> {
+    // Ipv6 addresses are surrounded by square brackets in urls.
+    url.host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+fn private_address_message(ip: IpAddr) -> String {
+    format!(
+        "Feed host resolves to `{}`, which is a private or internal address",
+        ip
+    )
+}
+
+/// Whether the address is not reachable from the public internet. Such as loopback, local
+/// network and link-local (which includes cloud metadata services) addresses.
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared address space for carrier-grade NAT, 100.64.0.0/10.
+                || (octets[0] == 100 && (octets[1] & 0b1100_0000) == 64)
+                // "This network", 0.0.0.0/8.
+                || octets[0] == 0
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local addresses, fc00::/7.
+                || (segments[0] & 0xfe00) == 0xfc00
+                // Link-local addresses, fe80::/10.
+                || (segments[0] & 0xffc0) == 0xfe80
+                || ip
+                    .to_ipv4_mapped()
+                    .is_some_and(|ip| is_private_address(IpAddr::V4(ip)))
+        }
+    }
+}
+
+/// Checks whether the value of a `Content-Type` header indicates an html page.
+fn is_html_content_type(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .map(|mime| mime.trim().eq_ignore_ascii_case("text/html"))
+        .unwrap_or(false)
+}
+
+/// The `charset` parameter of a `Content-Type` header, for example `iso-8859-1` in
+/// `application/rss+xml; charset=iso-8859-1`.
+fn charset_of_content_type(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// The `encoding` in the xml declaration at the start of the `content`, for example
+/// `windows-1252` in `<?xml version="1.0" encoding="windows-1252"?>`.
+fn xml_declared_encoding(content: &[u8]) -> Option<String> {
+    // The declaration only contains ascii, so the start of the content can be read as such,
+    // whatever the encoding of the rest is.
+    let start = String::from_utf8_lossy(&content[..content.len().min(200)]).into_owned();
+    let declaration = &start[start.find("<?xml")?..];
+    let declaration = &declaration[..declaration.find("?>")?];
+    let value = declaration[declaration.find("encoding")? + "encoding".len()..]
+        .trim_start()
+        .strip_prefix('=')?
+        .trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+
+    Some(value[..value.find(quote)?].to_string())
+}
+
+/// Converts the `content` to utf-8, if the xml declaration or the `header_charset` say it
+/// is in another encoding, like `iso-8859-1` or `windows-1252`.
+/// Feed-rs reads everything as utf-8, which turns accented characters into garbage otherwise.
+///
+/// The xml declaration goes first, because servers often send a default charset that doesn't
+/// match the feed, while the declaration is written together with the feed.
+fn decode_to_utf8(content: Vec<u8>, header_charset: Option<&str>) -> Vec<u8> {
+    let declared = xml_declared_encoding(&content);
+    let label = declared.clone().or(header_charset.map(str::to_string));
+    let Some(encoding) = label.and_then(|label| Encoding::for_label(label.as_bytes())) else {
+        return content;
+    };
+    if encoding == UTF_8 {
+        return content;
+    }
+
+    let (decoded, _, _) = encoding.decode(&content);
+    // Otherwise the declaration would still claim the old encoding.
+    match declared {
+        Some(declared) => decoded.replacen(&declared, "UTF-8", 1).into_bytes(),
+        None => decoded.into_owned().into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feed_fetch_error::FeedFetchError;
+    use crate::feed_requester::{
+        charset_of_content_type, decode_to_utf8, find_icon_href, home_url, icon_url,
+        is_html_content_type, is_private_address, parse_diagnostics, parse_feed,
+        recommended_refresh_interval_minutes, suggested_tags, xml_declared_encoding, FeedRequester,
+    };
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use rss_com_lib::rss_feed::{FeedEntries, FeedEntry, ParseDiagnostics};
+    use rss_com_lib::Url;
+    use std::io::{Read, Write};
+    use std::net::{IpAddr, TcpListener};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Starts a server that answers a single request with a body that never ends.
+    fn serve_endless_body() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+
+                // Without a `Content-Length` header, the body lasts until the connection closes.
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml\r\n\r\n");
+                let chunk = [b'a'; 1024];
+                // Keep writing until the client hangs up.
+                while stream.write_all(&chunk).is_ok() {}
+            }
+        });
+
+        Url::new(format!("http://{}/feed.xml", address))
+    }
+
+    /// Starts a server that answers every request with the same small feed.
+    /// Returns the url of the feed, and how many requests the server has received.
+    fn serve_feed_and_count_requests() -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let request_count = Arc::new(AtomicUsize::new(0));
+
+        let count = request_count.clone();
+        std::thread::spawn(move || {
+            let feed = r#"<rss version="2.0"><channel><title>Cached</title></channel></rss>"#;
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                count.fetch_add(1, Ordering::SeqCst);
+
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        feed.len(),
+                        feed
+                    )
+                    .as_bytes(),
+                );
+            }
+        });
+
+        (
+            Url::new(format!("http://{}/feed.xml", address)),
+            request_count,
+        )
+    }
+
+    #[actix_web::test]
+    async fn test_feed_is_served_from_cache_within_cache_duration() {
+        // Given
+        let (url, request_count) = serve_feed_and_count_requests();
+        let requester = FeedRequester::new(
+            10_000,
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            true,
+            Duration::from_secs(60),
+        );
+
+        // When
+        let (_, first) = requester.request_feed(&url, Duration::from_secs(5)).await;
+        let (_, second) = requester.request_feed(&url, Duration::from_secs(5)).await;
+
+        // Then
+        assert_eq!(first.unwrap().title, "Cached");
+        assert_eq!(second.unwrap().title, "Cached");
+        assert_eq!(request_count.load(Ordering::SeqCst), 1);
+    }
+
+    /// Starts a server that answers a single request with a gzip compressed feed.
+    fn serve_gzip_feed() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+
+                let feed =
+                    r#"<rss version="2.0"><channel><title>Compressed</title></channel></rss>"#;
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(feed.as_bytes()).unwrap();
+                let body = encoder.finish().unwrap();
+
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .as_bytes(),
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+
+        Url::new(format!("http://{}/feed.xml", address))
+    }
+
+    #[actix_web::test]
+    async fn test_gzip_compressed_feed_is_decompressed() {
+        // Given
+        let url = serve_gzip_feed();
+        let requester = FeedRequester::new(
+            10_000,
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            true,
+            Duration::ZERO,
+        );
+
+        // When
+        let (_, result) = requester.request_feed(&url, Duration::from_secs(5)).await;
+
+        // Then
+        assert_eq!(result.unwrap().title, "Compressed");
+    }
+
+    #[actix_web::test]
+    async fn test_download_is_aborted_when_body_exceeds_limit() {
+        // Given
+        let url = serve_endless_body();
+        let requester = FeedRequester::new(
+            10_000,
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            true,
+            Duration::ZERO,
+        );
+
+        // When
+        let (_, result) = requester.request_feed(&url, Duration::from_secs(10)).await;
+
+        // Then
+        let error = result.err().expect("Download should have been aborted");
+        assert_eq!(
+            error.to_string(),
+            "Feed is larger than the maximum of 10000 bytes"
+        );
+    }
+
+    #[test]
+    fn test_is_html_content_type() {
+        assert!(is_html_content_type("text/html"));
+        assert!(is_html_content_type("text/html; charset=utf-8"));
+        assert!(is_html_content_type("Text/HTML"));
+        assert!(!is_html_content_type("application/rss+xml"));
+        assert!(!is_html_content_type("application/xml; charset=utf-8"));
+        assert!(!is_html_content_type(""));
+    }
+
+    #[test]
+    fn test_home_url_skips_link_to_the_feed_itself() {
+        // Given
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+                <title>Example</title>
+                <id>https://example.com/</id>
+                <updated>2024-09-01T12:00:00Z</updated>
+                <link rel="self" href="https://example.com/feed.xml"/>
+                <link rel="alternate" href="https://Example.com/"/>
+            </feed>"#;
+        let raw_feed = feed_rs::parser::parse(atom.as_bytes()).unwrap();
+
+        // When
+        let home = home_url(
+            &raw_feed.links,
+            &Url::new("https://example.com/feed.xml".to_string()),
+        );
+
+        // Then
+        assert_eq!(home, Some(Url::new("https://example.com".to_string())));
+    }
+
+    #[test]
+    fn test_charset_of_content_type() {
+        assert_eq!(
+            charset_of_content_type("application/rss+xml; charset=ISO-8859-1"),
+            Some("ISO-8859-1")
+        );
+        assert_eq!(
+            charset_of_content_type("text/xml;Charset=\"utf-8\""),
+            Some("utf-8")
+        );
+        assert_eq!(charset_of_content_type("application/rss+xml"), None);
+    }
+
+    #[test]
+    fn test_xml_declared_encoding() {
+        assert_eq!(
+            xml_declared_encoding(br#"<?xml version="1.0" encoding="windows-1252"?><rss/>"#),
+            Some("windows-1252".to_string())
+        );
+        assert_eq!(
+            xml_declared_encoding(b"<?xml version='1.0' encoding = 'ISO-8859-1' ?><rss/>"),
+            Some("ISO-8859-1".to_string())
+        );
+        assert_eq!(
+            xml_declared_encoding(br#"<?xml version="1.0"?><rss/>"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_latin_1_feed_is_decoded() {
+        // Given
+        let mut latin_1 = br#"<?xml version="1.0" encoding="ISO-8859-1"?>
+            <rss version="2.0">
+                <channel>
+                    <title>Example</title>
+                    <item>
+                        <title>Caf"#
+            .to_vec();
+        // An `é` in latin-1. In utf-8 this byte on its own is invalid.
+        latin_1.push(0xE9);
+        latin_1.extend_from_slice(
+            br#"</title>
+                    </item>
+                </channel>
+            </rss>"#,
+        );
+
+        // When
+        let content = decode_to_utf8(latin_1, None);
+
+        // Then
+        let raw_feed = feed_rs::parser::parse(&content[..]).unwrap();
+        let (_, entry) = FeedEntry::from_raw_feed_entry(&raw_feed.entries[0]);
+        assert_eq!(entry.title, "Café");
+    }
+
+    #[test]
+    fn test_utf_8_feed_is_left_as_is() {
+        // Given
+        let utf_8 = r#"<?xml version="1.0" encoding="UTF-8"?><rss><title>Café</title></rss>"#
+            .as_bytes()
+            .to_vec();
+
+        // Then
+        assert_eq!(decode_to_utf8(utf_8.clone(), None), utf_8);
+        assert_eq!(decode_to_utf8(utf_8.clone(), Some("utf-8")), utf_8);
+        // The declaration is trusted over a wrong default charset of the server.
+        assert_eq!(decode_to_utf8(utf_8.clone(), Some("iso-8859-1")), utf_8);
+    }
+
+    #[test]
+    fn test_parse_diagnostics_counts_skipped_and_incomplete_items() {
+        // Given
+        let rss = r#"<?xml version="1.0" encoding="utf-8"?>
+            <rss version="2.0">
+                <channel>
+                    <title>Example</title>
+                    <link>https://example.com/</link>
+                    <item>
+                        <title>First</title>
+                        <guid>https://example.com/?p=1</guid>
+                        <pubDate>Tue, 10 Sep 2024 12:00:00 GMT</pubDate>
+                    </item>
+                    <item>
+                        <description>No title and no date</description>
+                        <guid>https://example.com/?p=2</guid>
+                    </item>
+                    <item>
+                        <title>Same guid as the first</title>
+                        <guid>https://example.com/?p=1</guid>
+                        <pubDate>Tue, 10 Sep 2024 13:00:00 GMT</pubDate>
+                    </item>
+                </channel>
+            </rss>"#;
+        let raw_feed = parse_feed(rss.as_bytes()).unwrap();
+        let entries = FeedEntries::new(
+            raw_feed
+                .entries
+                .iter()
+                .map(FeedEntry::from_raw_feed_entry)
+                .collect(),
+        );
+
+        // When
+        let diagnostics = parse_diagnostics(&raw_feed, &entries, rss.as_bytes());
+
+        // Then
+        assert_eq!(
+            diagnostics,
+            ParseDiagnostics {
+                entries_parsed: 2,
+                entries_skipped: 1,
+                entries_without_date: 1,
+                entries_without_title: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_entry_without_guid_or_link_keeps_its_key() {
+        // Given
+        let rss = r#"<?xml version="1.0" encoding="utf-8"?>
+            <rss version="2.0">
+                <channel>
+                    <title>Example</title>
+                    <item>
+                        <title>No guid and no link</title>
+                    </item>
+                </channel>
+            </rss>"#;
+        let key = || {
+            let raw_feed = parse_feed(rss.as_bytes()).unwrap();
+            let (key, entry) = FeedEntry::from_raw_feed_entry(&raw_feed.entries[0]);
+            assert_eq!(entry.guid, None);
+            key
+        };
+
+        // When
+        let first = key();
+        let second = key();
+
+        // Then
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_icon_url_falls_back_to_the_logo() {
+        // Given
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+                <title>Example</title>
+                <id>https://example.com/</id>
+                <updated>2024-09-01T12:00:00Z</updated>
+                <logo>https://example.com/logo.png</logo>
+            </feed>"#;
+        let raw_feed = feed_rs::parser::parse(atom.as_bytes()).unwrap();
+
+        // When
+        let icon = icon_url(&raw_feed);
+
+        // Then
+        assert_eq!(
+            icon,
+            Some(Url::new("https://example.com/logo.png".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_suggested_tags_start_with_the_feed_categories() {
+        // Given
+        let rss = r#"<?xml version="1.0" encoding="utf-8"?>
+            <rss version="2.0">
+                <channel>
+                    <title>Example</title>
+                    <link>https://example.com/</link>
+                    <category>Technology</category>
+                    <item>
+                        <title>First</title>
+                        <category>Rust</category>
+                        <category>Technology</category>
+                    </item>
+                    <item>
+                        <title>Second</title>
+                        <category>Rust</category>
+                        <category>Linux</category>
+                    </item>
+                </channel>
+            </rss>"#;
+        let raw_feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+
+        // When
+        let tags = suggested_tags(&raw_feed);
+
+        // Then
+        assert_eq!(tags, vec!["Technology", "Rust", "Linux"]);
+    }
+
+    #[test]
+    fn test_recommended_refresh_interval_from_ttl() {
+        // Given
+        let rss = r#"<?xml version="1.0" encoding="utf-8"?>
+            <rss version="2.0">
+                <channel>
+                    <title>Example</title>
+                    <link>https://example.com/</link>
+                    <ttl>60</ttl>
+                </channel>
+            </rss>"#;
+        let raw_feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+
+        // When
+        let interval = recommended_refresh_interval_minutes(&raw_feed, rss.as_bytes());
+
+        // Then
+        assert_eq!(interval, Some(60));
+    }
+
+    #[test]
+    fn test_recommended_refresh_interval_from_syndication_module() {
+        // Given
+        let rss = r#"<?xml version="1.0" encoding="utf-8"?>
+            <rss version="2.0" xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">
+                <channel>
+                    <title>Example</title>
+                    <link>https://example.com/</link>
+                    <sy:updatePeriod>daily</sy:updatePeriod>
+                    <sy:updateFrequency>2</sy:updateFrequency>
+                </channel>
+            </rss>"#;
+        let raw_feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+
+        // When
+        let interval = recommended_refresh_interval_minutes(&raw_feed, rss.as_bytes());
+
+        // Then
+        assert_eq!(interval, Some(60 * 12));
+    }
+
+    #[test]
+    fn test_find_icon_href() {
+        let cases = [
+            (
+                r#"<head><link rel="stylesheet" href="style.css"><LINK REL="Shortcut Icon" HREF="/Static/Icon.png"></head>"#,
+                Some("/Static/Icon.png"),
+            ),
+            (
+                r#"<link href='https://cdn.example.com/favicon.ico' rel='icon' />"#,
+                Some("https://cdn.example.com/favicon.ico"),
+            ),
+            (r#"<link rel=icon href=favicon.png>"#, Some("favicon.png")),
+            (r#"<link rel="apple-touch-icon" href="/apple.png">"#, None),
+            (r#"<link data-rel="icon" href="/not-an-icon.png">"#, None),
+            ("<html><body>No icons here</body></html>", None),
+        ];
+
+        for (html, expected) in cases {
+            assert_eq!(find_icon_href(html).as_deref(), expected, "{}", html);
+        }
+    }
+
+    #[test]
+    fn test_is_private_address() {
+        let private = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+        ];
+        let public = ["1.1.1.1", "93.184.216.34", "100.128.0.1", "2606:4700::1111"];
+
+        for address in private {
+            assert!(
+                is_private_address(address.parse::<IpAddr>().unwrap()),
+                "{} should be private",
+                address
+            );
+        }
+        for address in public {
+            assert!(
+                !is_private_address(address.parse::<IpAddr>().unwrap()),
+                "{} should be public",
+                address
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_private_ip_address_is_refused() {
+        // Given
+        let requester = FeedRequester::new(
+            10_000,
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            false,
+            Duration::ZERO,
+        );
+
+        // When
+        let (_, result) = requester
+            .request_feed(
+                &Url::new("http://127.0.0.1:1/feed.xml".to_string()),
+                Duration::from_secs(5),
+            )
+            .await;
+
+        // Then
+        let error = result.err().expect("Request should have been refused");
+        assert_eq!(
+            error,
+            FeedFetchError::Dns(
+                "Feed host resolves to `127.0.0.1`, which is a private or internal address"
+                    .to_string()
+            )
+        );
+    }
 }
@@ -0,0 +1,102 @@
+//! How long the last downloads of each feed took, to spot feeds that are consistently slow
+//! instead of consistently broken.
+
+use crate::rss_collection::RssCollections;
+use crate::Authenticated;
+use actix_web::{post, web, HttpResponse, Responder};
+use chrono::Utc;
+use rss_com_lib::message_body::{FetchTiming, FetchTimingsRequest, FetchTimingsResponse};
+use rss_com_lib::Url;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+/// How many downloads are remembered per feed.
+const TIMINGS_PER_FEED: usize = 5;
+
+/// Only kept in memory. After a restart the timings fill up again with the next updates.
+#[derive(Default, Debug)]
+pub struct FetchTimings(RwLock<HashMap<Url, VecDeque<FetchTiming>>>);
+
+impl FetchTimings {
+    pub fn record(&self, url: &Url, duration: Duration, success: bool) {
+        let mut timings = self.0.write().unwrap();
+        let feed_timings = timings.entry(url.clone()).or_default();
+        if feed_timings.len() >= TIMINGS_PER_FEED {
+            feed_timings.pop_front();
+        }
+        feed_timings.push_back(FetchTiming {
+            finished: Utc::now(),
+            duration_millis: duration.as_millis().try_into().unwrap_or(u64::MAX),
+            success,
+        });
+    }
+
+    fn get(&self, url: &Url) -> Option<Vec<FetchTiming>> {
+        self.0
+            .read()
+            .unwrap()
+            .get(url)
+            .map(|timings| timings.iter().cloned().collect())
+    }
+}
+
+/// Global for the same reason as the [crate::metrics::metrics]: the background feed updates
+/// don't have access to the web app data.
+pub fn fetch_timings() -> &'static FetchTimings {
+    static FETCH_TIMINGS: OnceLock<FetchTimings> = OnceLock::new();
+    FETCH_TIMINGS.get_or_init(FetchTimings::default)
+}
+
+/// Only the timings of the feeds of the user, so users can't see what others follow.
+#[post("/fetch_timings")]
+pub async fn get_fetch_timings(
+    request: web::Json<FetchTimingsRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let collections = collections.read().unwrap();
+    let Some(collection) = collections
+        .get(auth.user_id())
+        .and_then(|user_collections| user_collections.get(&request.collection_id))
+    else {
+        // The collection does not exist for this user.
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let timings: FetchTimingsResponse = collection
+        .keys()
+        .filter_map(|url| Some((url.clone(), fetch_timings().get(url)?)))
+        .collect();
+
+    HttpResponse::Ok().json(timings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fetch_timings::{FetchTimings, TIMINGS_PER_FEED};
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::Url;
+    use std::time::Duration;
+
+    #[test]
+    fn test_only_the_last_timings_are_kept() {
+        // Given
+        let timings = FetchTimings::default();
+        let url = Url::new("https://example.com/feed".to_string());
+
+        // When
+        for seconds in 0..(TIMINGS_PER_FEED as u64 + 2) {
+            timings.record(&url, Duration::from_secs(seconds), true);
+        }
+
+        // Then
+        let durations: Vec<u64> = timings
+            .get(&url)
+            .unwrap()
+            .iter()
+            .map(|timing| timing.duration_millis)
+            .collect();
+        assert_eq!(durations, vec![2000, 3000, 4000, 5000, 6000]);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::auth::AuthData;
+use crate::rss_collection::RssCollections;
+use crate::VERSION;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use rss_com_lib::message_body::{VersionResponse, API_SCHEMA_VERSION};
+use serde::Serialize;
+use std::sync::RwLock;
+
+/// Keeps track of when the background feed update last finished.
+/// So monitoring can tell the difference between "the server is running" and
+/// "the server is running, but the background updates are stuck".
+#[derive(Default, Debug)]
+pub struct BackgroundUpdateStatus {
+    last_finished_update: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl BackgroundUpdateStatus {
+    pub fn set_update_finished(&self) {
+        *self.last_finished_update.write().unwrap() = Some(Utc::now());
+    }
+
+    pub fn last_finished_update(&self) -> Option<DateTime<Utc>> {
+        *self.last_finished_update.read().unwrap()
+    }
+}
+
+/// Response for `/health`.
+#[derive(Serialize, Debug)]
+struct HealthResponse {
+    version: &'static str,
+    users: usize,
+    /// Amount of feeds over all users. A feed that multiple users follow is counted multiple times.
+    feeds: usize,
+    /// [None] if the first background update is still running.
+    last_background_update: Option<DateTime<Utc>>,
+}
+
+/// Does not require authentication, so it can be used by uptime monitors.
+#[get("/health")]
+pub async fn health(
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+    update_status: web::Data<BackgroundUpdateStatus>,
+) -> impl Responder {
+    let feeds = collections
+        .read()
+        .unwrap()
+        .values()
+        .flat_map(|user_collections| user_collections.values())
+        .map(|collection| collection.len())
+        .sum();
+
+    HttpResponse::Ok().json(HealthResponse {
+        version: VERSION,
+        users: auth_data.user_count(),
+        feeds,
+        last_background_update: update_status.last_finished_update(),
+    })
+}
+
+/// Does not require authentication, so the client can check it before the user logs in.
+#[post("/version")]
+pub async fn version() -> impl Responder {
+    HttpResponse::Ok().json(VersionResponse {
+        crate_version: VERSION.to_string(),
+        api_schema_version: API_SCHEMA_VERSION,
+    })
+}
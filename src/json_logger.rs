@@ -0,0 +1,111 @@
+//! Logger that writes one json object per line, so the logs can be read by log collectors.
+
+use chrono::{SecondsFormat, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use simplelog::{Config, SharedLogger};
+use std::io::Write;
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct JsonLogLine<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    thread: String,
+    message: String,
+}
+
+pub struct JsonLogger<W: Write + Send + 'static> {
+    level: LevelFilter,
+    writable: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn new(level: LevelFilter, writable: W) -> Box<Self> {
+        Box::new(JsonLogger {
+            level,
+            writable: Mutex::new(writable),
+        })
+    }
+}
+
+impl<W: Write + Send + 'static> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut writable = self.writable.lock().unwrap();
+        // There is nowhere to report it if logging itself goes wrong.
+        let _ = writeln!(writable, "{}", json_line(record));
+    }
+
+    fn flush(&self) {
+        let _ = self.writable.lock().unwrap().flush();
+    }
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+fn json_line(record: &Record<'_>) -> String {
+    let thread = std::thread::current();
+    let line = JsonLogLine {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        level: record.level().as_str(),
+        target: record.target(),
+        thread: thread
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:?}", thread.id())),
+        message: record.args().to_string(),
+    };
+
+    // Serializing strings can't fail.
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json_logger::json_line;
+    use log::{Level, Record};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_json_line() {
+        // When
+        // In one expression, because the `format_args!` only lives until the end of it.
+        let line = json_line(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("rss_r::feed_requester")
+                .args(format_args!("Feed `{}` is \"slow\"", "https://example.com"))
+                .build(),
+        );
+
+        // Then
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["target"], "rss_r::feed_requester");
+        assert_eq!(parsed["message"], "Feed `https://example.com` is \"slow\"");
+        assert!(parsed["timestamp"].is_string());
+        assert!(parsed["thread"].is_string());
+        assert!(!line.contains('\n'));
+    }
+}
@@ -1,69 +1,105 @@
 #![deny(unsafe_code)]
 #![warn(rust_2018_idioms, clippy::all)]
 
+mod admin;
 mod app_config;
 mod auth;
 mod auth_middleware;
+mod backup;
 mod error;
+mod favicon;
+mod feed_fetch_error;
 mod feed_requester;
+mod fetch_timings;
+mod health;
+mod json_logger;
+mod metrics;
 mod persistence;
+mod request_log;
 mod rss_collection;
+mod share_link;
+mod tls;
+mod update_status;
+mod user_settings;
 mod users;
 
-use crate::app_config::ApplicationConfig;
+use crate::app_config::{ApplicationConfig, LogFormat};
 use crate::auth::{AuthData, AUTH_COOKIE_NAME};
 use crate::auth_middleware::{AuthenticateMiddlewareFactory, Authenticated};
 use crate::cookie::SameSite;
+use crate::favicon::FaviconCache;
+use crate::feed_fetch_error::FeedFetchError;
 use crate::feed_requester::FeedRequester;
-use crate::persistence::SaveInRonFile;
-use crate::rss_collection::RssCollections;
+use crate::health::BackgroundUpdateStatus;
+use crate::json_logger::JsonLogger;
+use crate::persistence::{persistence_dir, SaveInRonFile, PERSISTENCE_DIR_ENV_VAR};
+use crate::rss_collection::{EntryRetention, RssCollections};
+use crate::update_status::UpdateStatuses;
+use crate::user_settings::UserSettingsCollection;
 use crate::users::UserInfo;
 use actix_files::Files;
 use actix_identity::IdentityMiddleware;
 use actix_session::config::{CookieContentSecurity, PersistentSession, SessionLifecycle};
 use actix_session::storage::CookieSessionStore;
 use actix_session::SessionMiddleware;
-use actix_web::middleware::Logger;
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::middleware::{from_fn, DefaultHeaders, Logger};
 use actix_web::rt::spawn;
 use actix_web::web::Data;
 use actix_web::{cookie, web, App, HttpServer};
+use chrono::Utc;
 use log::{info, warn, LevelFilter};
 use simplelog::{
-    format_description, ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode,
-    WriteLogger,
+    format_description, ColorChoice, CombinedLogger, ConfigBuilder, SharedLogger, TermLogger,
+    TerminalMode, WriteLogger,
 };
-use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
 use std::fs::{create_dir_all, OpenOptions};
-use std::hash::{Hash, Hasher};
 use std::time::Duration;
 
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-/// Maximum time a user will stay logged in if the session state does not change.
-/// If the session state changes, this time will be reset. (But I don't think I change the session state after the first login, so that won't be a thing)
-const SESSION_TIME_TO_LIVE: time::Duration = time::Duration::days(14);
-
 /// How often the feed collections will be saved, if they have changed in the meantime.
 const COLLECTIONS_SAVE_INTERVAL: Duration = Duration::from_secs(120);
 
-/// How often we will update all of the user's feed collections in the background.
+/// How often a feed is downloaded in the background, unless the user has set a different
+/// interval for that feed.
 const FEED_UPDATE_INTERVAL: Duration = Duration::from_secs(3600 * 12);
+/// How often the background update checks which feeds are due for an update.
+const FEED_UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 10);
 
 /// TODO (Wybe 2022-07-10): Add some small banner that says this site uses cookies to authenticate? or is it not needed for authentication cookies.
 /// TODO (Wybe 2022-07-12): Rss apparently sometimes allows getting push notifications, via a "Cloud" element in the feed. Is it worth it to implement this?
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    configure_logging();
+    // Loaded before logging is configured, because it contains the log format.
+    let mut app_config = ApplicationConfig::load_or_default();
+    configure_logging(app_config.log_format);
 
     info!("Starting {} v{}", PACKAGE_NAME, VERSION);
+    let data_dir = std::env::current_dir()
+        .map(|working_dir| working_dir.join(persistence_dir()))
+        .unwrap_or_else(|_| persistence_dir().to_path_buf());
+    info!(
+        "Saving data in `{}` (set `{}` to change this)",
+        data_dir.display(),
+        PERSISTENCE_DIR_ENV_VAR
+    );
 
-    let app_config = ApplicationConfig::load_or_default();
+    app_config.ensure_secure_session_key();
     app_config.save();
+    ApplicationConfig::warn_if_readable_by_others();
     let auth_master_key = cookie::Key::derive_from(app_config.session_key.as_slice());
 
     let auth_data = AuthData::load_or_default();
     auth_data.save();
+    AuthData::warn_if_readable_by_others();
+    if !auth_data.has_admin() {
+        warn!(
+            "There are no admin users. Set `is_admin: true` for a user in `{}` to be able to manage users.",
+            AuthData::FILE_NAME
+        );
+    }
 
     // TODO (Wybe 2022-07-12): Is it a problem to store the auth data as web data?
     //                         all services would be able to access it. But the services
@@ -71,64 +107,169 @@ async fn main() -> std::io::Result<()> {
     //                         It does increase the probability of mistakes to slip in i think.
     let web_auth_data = web::Data::new(auth_data);
 
-    // TODO (Wybe 2022-07-16): Check whether all users that have a collection actually exist.
-    let rss_collections = RssCollections::load_or_default();
+    let rss_collections = RssCollections::load_or_migrate();
+    rss_collections.migrate_entry_keys();
+    check_orphan_collections(
+        &rss_collections,
+        &web_auth_data,
+        app_config.prune_orphan_collections,
+    );
     let web_rss_collections = web::Data::new(rss_collections);
 
-    let binding_ip = app_config.binding_ip();
+    let web_user_settings = web::Data::new(UserSettingsCollection::load_or_default());
+
+    let binding_ip = app_config
+        .binding_ip()
+        .unwrap_or_else(|error| panic!("Invalid server address: {}", error));
+    let session_ttl = app_config
+        .session_ttl()
+        .unwrap_or_else(|error| panic!("Invalid session time to live: {}", error));
+    let cookie_secure = app_config.cookie_secure;
+    if !cookie_secure {
+        warn!("`cookie_secure` is off, so session cookies are also sent over plain HTTP. Only use this for local development!");
+    }
     info!(
         "Starting Http server at `{}`, with hostname `{}` and prefix `{}`",
         binding_ip, app_config.hostname, app_config.route_prefix
     );
 
     spawn_periodic_saving_task(web_rss_collections.clone(), COLLECTIONS_SAVE_INTERVAL);
-    spawn_periodic_feed_update_task(web_rss_collections.clone(), FEED_UPDATE_INTERVAL);
+    let web_update_status = web::Data::new(BackgroundUpdateStatus::default());
+
+    spawn_periodic_feed_update_task(
+        web_rss_collections.clone(),
+        web_update_status.clone(),
+        app_config.feed_requester(),
+        app_config.feed_timeouts().update,
+        app_config.entry_retention(),
+        FEED_UPDATE_CHECK_INTERVAL,
+    );
 
     let collections_save_on_application_close = web_rss_collections.clone();
 
-    HttpServer::new(move || {
+    // Created here, so the server factory below doesn't take ownership of the config.
+    let route_prefix = app_config.route_prefix.clone();
+    // Api requests are logged by `request_log::log_requests` instead, with the user that made them.
+    let api_path_regex = format!("^{}/api/", route_prefix);
+    let web_feed_requester = web::Data::new(app_config.feed_requester());
+    let web_feed_timeouts = web::Data::new(app_config.feed_timeouts());
+    let web_favicon_cache = web::Data::new(FaviconCache::default());
+    let web_strip_query_params = web::Data::new(app_config.strip_query_params());
+    let web_user_limits = web::Data::new(app_config.user_limits());
+    let web_metrics_token = web::Data::new(app_config.metrics_token());
+    let web_share_links = web::Data::new(app_config.share_links());
+    let web_slow_request_threshold = web::Data::new(app_config.slow_request_threshold());
+    let web_update_statuses = web::Data::new(UpdateStatuses::default());
+
+    let server = HttpServer::new(move || {
         let session_middleware =
             SessionMiddleware::builder(CookieSessionStore::default(), auth_master_key.clone())
                 .session_lifecycle(SessionLifecycle::PersistentSession(
-                    PersistentSession::default().session_ttl(SESSION_TIME_TO_LIVE),
+                    PersistentSession::default().session_ttl(session_ttl),
                 ))
                 .cookie_content_security(CookieContentSecurity::Private)
                 .cookie_same_site(SameSite::Strict)
                 .cookie_http_only(true)
-                .cookie_secure(true)
+                .cookie_secure(cookie_secure)
                 .cookie_name(AUTH_COOKIE_NAME.to_string())
                 .build();
-
-        App::new().wrap(Logger::default()).service(
-            web::scope(&app_config.route_prefix)
+        let logger = Logger::default().exclude_regex(api_path_regex.clone());
+
+        App::new().wrap(logger).service(
+            web::scope(&route_prefix)
+                .app_data(web_auth_data.clone())
+                .app_data(web_rss_collections.clone())
+                .app_data(web_update_status.clone())
+                .app_data(web_metrics_token.clone())
+                .app_data(web_share_links.clone())
+                .app_data(web_slow_request_threshold.clone())
                 .service(web::redirect("/", "app/index.html"))
                 .service(web::redirect("/app/", "index.html"))
                 // This serves the static files of the rss_r_web webassembly application.
-                .service(Files::new("/app", "static"))
+                // `no-cache` makes browsers check the ETag before using their cached copy, so users
+                // get the new client right after an update, and a cheap `304` otherwise.
+                // The `.wasm` file gets the `application/wasm` type from its extension.
+                .service(
+                    web::scope("/app")
+                        .wrap(DefaultHeaders::new().add((CACHE_CONTROL, "no-cache")))
+                        .service(
+                            Files::new("", "static")
+                                .use_etag(true)
+                                .use_last_modified(true),
+                        ),
+                )
+                .service(health::health)
+                .service(metrics::metrics_endpoint)
+                .service(share_link::get_shared_feeds)
                 .service(
                     web::scope("/api")
-                        .app_data(web_auth_data.clone())
-                        .app_data(web_rss_collections.clone())
-                        .app_data(Data::new(FeedRequester::default()))
+                        .app_data(web_user_settings.clone())
+                        .app_data(web_feed_requester.clone())
+                        .app_data(web_feed_timeouts.clone())
+                        .app_data(web_favicon_cache.clone())
+                        .app_data(web_strip_query_params.clone())
+                        .app_data(web_user_limits.clone())
+                        .app_data(web_update_statuses.clone())
+                        .app_data(error::json_config())
                         .wrap(AuthenticateMiddlewareFactory)
                         .wrap(IdentityMiddleware::default())
                         // Session middleware has to be added _after_ identity middleware.
                         .wrap(session_middleware)
+                        // Added last, so it also counts requests that the other middleware rejects.
+                        .wrap(from_fn(metrics::count_api_requests))
+                        // Outermost, so the logged time includes all the other middleware.
+                        .wrap(from_fn(request_log::log_requests))
+                        .service(health::version)
                         .service(auth::test_auth_cookie)
                         .service(auth::login)
                         .service(auth::logout)
                         .service(rss_collection::is_url_an_rss_feed)
                         .service(rss_collection::get_feeds)
                         .service(rss_collection::add_feed)
+                        .service(rss_collection::add_feeds)
+                        .service(rss_collection::import_url_list)
                         .service(rss_collection::set_entry_read)
-                        .service(rss_collection::set_feed_info),
+                        .service(rss_collection::set_entries_read)
+                        .service(rss_collection::set_entry_read_later)
+                        .service(rss_collection::mark_all_read)
+                        .service(rss_collection::reset_read)
+                        .service(rss_collection::get_unread_total)
+                        .service(rss_collection::set_feed_info)
+                        .service(rss_collection::export_feed)
+                        .service(rss_collection::import_feed)
+                        .service(rss_collection::merge_feeds)
+                        .service(share_link::create_share_link)
+                        .service(favicon::get_favicon)
+                        .service(fetch_timings::get_fetch_timings)
+                        .service(update_status::get_update_status)
+                        .service(rss_collection::get_collections)
+                        .service(rss_collection::create_collection)
+                        .service(rss_collection::delete_collection)
+                        .service(admin::get_users)
+                        .service(admin::delete_user)
+                        .service(backup::create_backup)
+                        .service(backup::restore)
+                        .service(user_settings::get_user_settings)
+                        .service(user_settings::set_user_settings),
                 ),
         )
     })
-    .server_hostname(&app_config.hostname)
-    .bind(binding_ip)?
-    .run()
-    .await?;
+    .server_hostname(&app_config.hostname);
+
+    let server = if let Some((cert_path, key_path)) = app_config.tls_paths() {
+        info!("Serving HTTPS, with certificate `{}`", cert_path.display());
+        let tls_config = tls::load_rustls_config(cert_path, key_path)
+            .unwrap_or_else(|error| panic!("Could not load the TLS certificate: {}", error));
+        server.bind_rustls(binding_ip, tls_config)?
+    } else {
+        if app_config.tls_cert_path.is_some() || app_config.tls_key_path.is_some() {
+            warn!("Only one of `tls_cert_path` and `tls_key_path` is set. Both are needed to serve HTTPS.");
+        }
+        info!("Serving plain HTTP. Logging in only works behind a proxy that serves HTTPS.");
+        server.bind(binding_ip)?
+    };
+
+    server.run().await?;
 
     // Make sure we don't loose anything that happened since the last save.
     collections_save_on_application_close.save();
@@ -136,45 +277,76 @@ async fn main() -> std::io::Result<()> {
     Ok(())
 }
 
+/// Reports the collections of users that don't exist, and removes them if `prune` is set.
+fn check_orphan_collections(collections: &RssCollections, auth_data: &AuthData, prune: bool) {
+    let orphans = collections.orphaned_users(|user| auth_data.has_user(user));
+    if orphans.is_empty() {
+        return;
+    }
+
+    let ids: Vec<u32> = orphans.iter().map(|user| user.0).collect();
+    if prune {
+        {
+            let mut collections = collections.write().unwrap();
+            for user in &orphans {
+                collections.remove(user);
+            }
+        }
+        collections.save();
+        warn!(
+            "Removed the collections of users that don't exist: {:?}",
+            ids
+        );
+    } else {
+        warn!(
+            "There are collections of users that don't exist: {:?}. Set `prune_orphan_collections: true` in `{}` to remove them.",
+            ids,
+            ApplicationConfig::FILE_NAME
+        );
+    }
+}
+
 fn spawn_periodic_saving_task(collections: Data<RssCollections>, interval: Duration) {
     spawn(async move {
         let mut save_interval = actix_web::rt::time::interval(interval);
 
-        let mut hasher = DefaultHasher::new();
-        collections.hash(&mut hasher);
-        let mut last_save_hash = hasher.finish();
+        let mut last_saved_version = collections.version();
 
         loop {
             save_interval.tick().await;
 
-            let mut hasher = DefaultHasher::new();
-            collections.hash(&mut hasher);
-            let new_hash = hasher.finish();
+            // Read before saving, so changes that are made during the save are saved next time.
+            let version = collections.version();
 
-            if new_hash != last_save_hash {
+            if version != last_saved_version {
                 // Collections have changed. Save them.
                 collections.save();
-                last_save_hash = new_hash;
+                last_saved_version = version;
             }
         }
     });
 }
 
-/// Will periodically update the feeds.
+/// Will periodically update the feeds that are due for an update.
 /// Will do the first update when this funcion is called.
-fn spawn_periodic_feed_update_task(collections: Data<RssCollections>, interval: Duration) {
+fn spawn_periodic_feed_update_task(
+    collections: Data<RssCollections>,
+    update_status: Data<BackgroundUpdateStatus>,
+    feed_requester: FeedRequester,
+    timeout: Duration,
+    retention: EntryRetention,
+    interval: Duration,
+) {
     spawn(async move {
         let mut update_interval = actix_web::rt::time::interval(interval);
-        let feed_requester = FeedRequester::default();
-        // The timeout for background updates can be a lot higher than when a user is waiting.
-        let timeout = Duration::from_secs(20);
 
         loop {
             // The first time we get here, `tick` will immediately pass. This means we update
             // on the start of the program.
             update_interval.tick().await;
 
-            update_all_collections(&collections, &feed_requester, timeout).await;
+            update_all_collections(&collections, &feed_requester, timeout, retention).await;
+            update_status.set_update_finished();
         }
     });
 }
@@ -183,41 +355,69 @@ async fn update_all_collections(
     collections: &Data<RssCollections>,
     requester: &FeedRequester,
     timeout: Duration,
+    retention: EntryRetention,
 ) {
-    info!("Updating feeds in the background.");
-
     let mut feed_urls = HashSet::new();
     {
         let collections = collections.read().unwrap();
-
-        for (_, collection) in collections.iter() {
-            feed_urls.extend(collection.keys().cloned())
+        let now = Utc::now();
+        let default_interval = chrono::Duration::from_std(FEED_UPDATE_INTERVAL).unwrap();
+
+        for collection in collections
+            .values()
+            .flat_map(|user_collections| user_collections.values())
+        {
+            // A feed that multiple users follow is downloaded if it is due for any of them.
+            feed_urls.extend(
+                collection
+                    .iter()
+                    .filter(|(_, feed)| feed.info().is_due_for_update(now, default_interval))
+                    .map(|(url, _)| url.clone()),
+            )
         }
     } // Lock on `RssCollections` is dropped here, so that it isn't held while the http requests are made (which can take quite a while).
 
+    if feed_urls.is_empty() {
+        return;
+    }
+
+    info!("Updating {} feeds in the background.", feed_urls.len());
+
     let feed_requests = requester.request_feeds(&feed_urls, timeout).await;
-    // TODO (2024-09-03): Set the "last update went ok" flag to false if we can't get the feed.
-    // TODO (2024-09-03): Merge this code with the "update all feeds" requests.
 
     {
         let mut collections = collections.write().unwrap();
 
-        for (_, collection) in collections.iter_mut() {
+        for collection in collections
+            .values_mut()
+            .flat_map(|user_collections| user_collections.values_mut())
+        {
             for url in &feed_urls {
                 if let Some(feed) = collection.get_mut(url) {
                     // Feed exists in the users collection.
                     if let Some(maybe_feed_update) = feed_requests.get(url) {
+                        if let Ok(feed_update) = maybe_feed_update {
+                            feed.update_info(feed_update);
+                        }
                         let maybe_entries = maybe_feed_update
                             .as_ref()
                             .map(|feed| feed.entries.clone())
-                            .map_err(full_error_to_string);
+                            .map_err(FeedFetchError::clone);
                         feed.update_entries(maybe_entries);
+
+                        if let Ok(feed_update) = maybe_feed_update {
+                            feed.prune(
+                                retention.max_entries_per_feed,
+                                retention.max_read_age,
+                                &feed_update.entries,
+                            );
+                        }
                     } else {
                         // Feed is in the users collection, but the update request did not return a result.
-                        feed.update_entries(Err(
+                        feed.update_entries(Err(FeedFetchError::Other(
                             "Feed update was requested, but the function did not return anything."
                                 .to_string(),
-                        ));
+                        )));
                     }
                 }
             }
@@ -227,7 +427,7 @@ async fn update_all_collections(
     info!("Done updating feeds in the background.")
 }
 
-fn configure_logging() {
+fn configure_logging(file_format: LogFormat) {
     let log_dir = "log";
 
     // The logged time is by default in UTC.
@@ -262,18 +462,17 @@ fn configure_logging() {
         .open(&file_name)
         .unwrap_or_else(|_| panic!("Could not open `{}` for writing", file_name));
 
-    let file_logger = WriteLogger::new(log_level, config, log_file);
+    let file_logger: Box<dyn SharedLogger> = match file_format {
+        LogFormat::Pretty => WriteLogger::new(log_level, config, log_file),
+        LogFormat::Json => JsonLogger::new(log_level, log_file),
+    };
 
     // We log both to the terminal, and to a file.
     CombinedLogger::init(vec![term_logger, file_logger]).unwrap();
 }
 
 /// Prints the error to string, including any `source` errors available.
-///
-/// We allow the clippy borrowed_box lint here, because we actually cannot follow it's advice of passing `&dyn Error`, because this causes a
-/// "type is not sized" at the call site of this function..
-#[allow(clippy::borrowed_box)]
-pub fn full_error_to_string(error: &Box<dyn std::error::Error>) -> String {
+pub fn full_error_to_string(error: &dyn std::error::Error) -> String {
     let mut message = error.to_string();
 
     let mut maybe_source = error.source();
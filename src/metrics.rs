@@ -0,0 +1,152 @@
+//! Prometheus metrics, served in the text exposition format at `/metrics`.
+
+use crate::auth::AuthData;
+use crate::rss_collection::RssCollections;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::middleware::Next;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use log::warn;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::OnceLock;
+
+pub struct Metrics {
+    registry: Registry,
+    pub feed_fetches: IntCounter,
+    /// Labeled with the [FetchErrorKind](rss_com_lib::rss_feed::FetchErrorKind) of the failure.
+    pub feed_fetch_failures: IntCounterVec,
+    /// In seconds.
+    pub feed_fetch_duration: Histogram,
+    /// Labeled with the route of the endpoint, for example `/api/feeds`.
+    pub api_requests: IntCounterVec,
+    users: IntGauge,
+    feeds: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let feed_fetches =
+            IntCounter::new("rss_r_feed_fetches_total", "Amount of feed downloads").unwrap();
+        let feed_fetch_failures = IntCounterVec::new(
+            Opts::new(
+                "rss_r_feed_fetch_failures_total",
+                "Amount of feed downloads that went wrong",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+        let feed_fetch_duration = Histogram::with_opts(HistogramOpts::new(
+            "rss_r_feed_fetch_duration_seconds",
+            "How long feed downloads take",
+        ))
+        .unwrap();
+        let api_requests = IntCounterVec::new(
+            Opts::new("rss_r_api_requests_total", "Amount of api requests"),
+            &["endpoint"],
+        )
+        .unwrap();
+        let users = IntGauge::new("rss_r_users", "Amount of users").unwrap();
+        let feeds = IntGauge::new("rss_r_feeds", "Amount of feeds over all users").unwrap();
+
+        registry.register(Box::new(feed_fetches.clone())).unwrap();
+        registry
+            .register(Box::new(feed_fetch_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(feed_fetch_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(api_requests.clone())).unwrap();
+        registry.register(Box::new(users.clone())).unwrap();
+        registry.register(Box::new(feeds.clone())).unwrap();
+
+        Metrics {
+            registry,
+            feed_fetches,
+            feed_fetch_failures,
+            feed_fetch_duration,
+            api_requests,
+            users,
+            feeds,
+        }
+    }
+}
+
+/// The metrics are global, because they are updated from places that don't have access to
+/// the web app data, like the background feed updates.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// If set, `/metrics` requires an `Authorization: Bearer <token>` header with this token.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsToken(pub Option<String>);
+
+#[get("/metrics")]
+pub async fn metrics_endpoint(
+    request: HttpRequest,
+    token: web::Data<MetricsToken>,
+    auth_data: web::Data<AuthData>,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    if let Some(token) = &token.0 {
+        let expected = format!("Bearer {}", token);
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .is_some_and(|value| value.as_bytes() == expected.as_bytes());
+        if !authorized {
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    let metrics = metrics();
+    metrics.users.set(auth_data.user_count() as i64);
+    let feeds: usize = collections
+        .read()
+        .unwrap()
+        .values()
+        .flat_map(|user_collections| user_collections.values())
+        .map(|collection| collection.len())
+        .sum();
+    metrics.feeds.set(feeds as i64);
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(error) = encoder.encode(&metrics.registry.gather(), &mut body) {
+        warn!("Could not encode metrics: {}", error);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(body)
+}
+
+/// Counts the requests per api endpoint.
+pub async fn count_api_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let result = next.call(request).await;
+
+    // The matched route is used instead of the path, so that requests for random paths
+    // don't each create a new label.
+    let endpoint = match &result {
+        Ok(response) => response
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unknown".to_string()),
+        // For example when the user is not logged in.
+        Err(_) => "error".to_string(),
+    };
+    metrics().api_requests.with_label_values(&[&endpoint]).inc();
+
+    result
+}
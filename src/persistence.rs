@@ -3,10 +3,35 @@ use ron::ser::{to_string_pretty, PrettyConfig};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-/// TODO (Wybe 2022-07-12): Make target directory configurable. And add warning that that directory should only be readable/writable by this program.
-const PERSISTENCE_DIR: &str = "persistence";
+/// Environment variable with the directory to save to. For when the working directory is not
+/// where the data should go, as with systemd or Docker.
+/// This is not part of the [crate::app_config::ApplicationConfig], because that is saved in
+/// this directory as well.
+/// TODO (Wybe 2022-07-12): Add warning that that directory should only be readable/writable by this program.
+pub const PERSISTENCE_DIR_ENV_VAR: &str = "RSS_R_DATA_DIR";
+/// Relative to the working directory.
+const DEFAULT_PERSISTENCE_DIR: &str = "persistence";
+/// Where tests save to, so they never overwrite the data of a server that runs from the same
+/// directory. Relative to the directory of the crate, which is where the tests run.
+#[cfg(test)]
+const TEST_PERSISTENCE_DIR: &str = "target/test_persistence";
+
+/// The directory all [SaveInRonFile]s are saved in.
+/// Read from [PERSISTENCE_DIR_ENV_VAR] the first time this is called.
+pub fn persistence_dir() -> &'static Path {
+    static PERSISTENCE_DIR: OnceLock<PathBuf> = OnceLock::new();
+    #[cfg(test)]
+    PERSISTENCE_DIR.get_or_init(|| PathBuf::from(TEST_PERSISTENCE_DIR));
+    PERSISTENCE_DIR.get_or_init(|| {
+        std::env::var_os(PERSISTENCE_DIR_ENV_VAR)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_PERSISTENCE_DIR))
+    })
+}
 
 pub trait SaveInRonFile: Sized + Default + Serialize + DeserializeOwned {
     /// File that the object should be saved to.
@@ -20,11 +45,11 @@ pub trait SaveInRonFile: Sized + Default + Serialize + DeserializeOwned {
     fn save(&self) {
         info!("Saving {}", Self::FILE_NAME);
 
-        let mut path = PathBuf::from(PERSISTENCE_DIR);
+        let mut path = persistence_dir().to_path_buf();
         fs::create_dir_all(&path).unwrap_or_else(|_| {
             panic!(
                 "Could not create persistence directory: `{}`",
-                PERSISTENCE_DIR
+                path.display()
             )
         });
 
@@ -46,7 +71,7 @@ pub trait SaveInRonFile: Sized + Default + Serialize + DeserializeOwned {
 
     /// TODO (Wybe 2022-07-12): Handle and log errors.
     fn load() -> Option<Self> {
-        let mut path = PathBuf::from(PERSISTENCE_DIR);
+        let mut path = persistence_dir().to_path_buf();
         path.push(Self::FILE_NAME);
 
         if let Ok(contents) = fs::read_to_string(path) {
@@ -61,4 +86,34 @@ pub trait SaveInRonFile: Sized + Default + Serialize + DeserializeOwned {
     fn load_or_default() -> Self {
         Self::load().unwrap_or_default()
     }
+
+    /// Logs a warning if other users on the system can read the file.
+    /// Use this for files that contain secrets, like passwords or keys.
+    fn warn_if_readable_by_others() {
+        let mut path = persistence_dir().to_path_buf();
+        path.push(Self::FILE_NAME);
+
+        if let Ok(metadata) = fs::metadata(&path) {
+            if is_readable_by_others(&metadata) {
+                warn!(
+                    "`{}` can be read by other users on this system. It contains secrets, so it should only be readable by this program. For example: `chmod 600 {}`",
+                    path.display(),
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_readable_by_others(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    // Any permission for the group or for others.
+    metadata.permissions().mode() & 0o077 != 0
+}
+
+/// Other platforms don't have the same permission bits, so we can't easily check this.
+#[cfg(not(unix))]
+fn is_readable_by_others(_metadata: &fs::Metadata) -> bool {
+    false
 }
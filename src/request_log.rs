@@ -0,0 +1,82 @@
+//! Logs every api request with the user that made it and how long it took, so that "the app is
+//! slow" can be traced back to a user and endpoint. For example the long "Update all feeds"
+//! requests.
+
+use crate::auth_middleware::AuthenticationInfo;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage};
+use log::{info, warn};
+use std::time::{Duration, Instant};
+
+/// Requests that take longer than this are logged as a warning. [None] never warns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlowRequestThreshold(pub Option<Duration>);
+
+impl SlowRequestThreshold {
+    fn is_slow(&self, duration: Duration) -> bool {
+        self.0.is_some_and(|threshold| duration > threshold)
+    }
+}
+
+/// Relies on [SlowRequestThreshold] to be in the web apps data.
+pub async fn log_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let path = request.path().to_string();
+    let threshold = request
+        .app_data::<web::Data<SlowRequestThreshold>>()
+        .map(|threshold| *threshold.get_ref())
+        .unwrap_or_default();
+
+    let result = next.call(request).await;
+    let duration = start.elapsed();
+
+    // The user is only known after the authentication middleware has run.
+    let (status, user) = match &result {
+        Ok(response) => (
+            response.status(),
+            response
+                .request()
+                .extensions()
+                .get::<AuthenticationInfo>()
+                .map(|auth| format!("user `{}`", auth.user_name())),
+        ),
+        Err(error) => (error.as_response_error().status_code(), None),
+    };
+    let message = format!(
+        "{} {} by {}: {} in {} ms",
+        method,
+        path,
+        user.as_deref().unwrap_or("anonymous"),
+        status.as_u16(),
+        duration.as_millis()
+    );
+
+    if threshold.is_slow(duration) {
+        warn!("Slow request. {}", message);
+    } else {
+        info!("{}", message);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request_log::SlowRequestThreshold;
+    use std::time::Duration;
+
+    #[test]
+    fn test_only_requests_over_the_threshold_are_slow() {
+        let threshold = SlowRequestThreshold(Some(Duration::from_secs(5)));
+
+        assert!(!threshold.is_slow(Duration::from_secs(5)));
+        assert!(threshold.is_slow(Duration::from_millis(5001)));
+        assert!(!SlowRequestThreshold(None).is_slow(Duration::from_secs(3600)));
+    }
+}
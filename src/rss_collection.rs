@@ -1,31 +1,163 @@
+use crate::feed_fetch_error::FeedFetchError;
+use crate::feed_requester::{Feed, FeedTimeouts};
+use crate::update_status::UpdateStatuses;
 use crate::users::UserId;
-use crate::{full_error_to_string, Authenticated, FeedRequester, SaveInRonFile};
-use actix_web::{post, web, HttpResponse, Responder};
+use crate::{Authenticated, FeedRequester, SaveInRonFile};
+use actix_web::http::header::{ContentDisposition, EntityTag, IfNoneMatch, ETAG};
+use actix_web::{post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
 use log::info;
 use rss_com_lib::message_body::{
-    AddFeedRequest, AdditionalAction, ComFeedEntry, EntryTypeFilter, FeedsFilter, FeedsRequest,
-    FeedsResponse, IsUrlAnRssFeedRequest, IsUrlAnRssFeedResponse, SetEntryReadRequestAndResponse,
-    SetFeedInfoRequestAndResponse,
+    AddFeedRequest, AddFeedsResponse, AdditionalAction, CollectionId, CollectionInfo,
+    CollectionsResponse, ComFeedEntry, CreateCollectionRequest, DeleteCollectionRequestAndResponse,
+    EntryCursor, EntryTypeFilter, ExportFeedRequest, ExportFeedResponse, FeedsFilter, FeedsRequest,
+    FeedsResponse, ImportFeedRequest, ImportFeedResponse, ImportUrlListRequest, ImportedEntry,
+    IsUrlAnRssFeedRequest, IsUrlAnRssFeedResponse, MarkAllReadRequest, MarkAllReadResponse,
+    MergeFeedsRequest, MergeFeedsResponse, PreviewEntry, ResetReadRequest, ResetReadResponse,
+    SetEntriesReadRequestAndResponse, SetEntryReadLaterRequestAndResponse,
+    SetEntryReadRequestAndResponse, SetFeedInfoRequestAndResponse, SortOrder, UnreadTotalRequest,
+    UnreadTotalResponse,
+};
+use rss_com_lib::rss_feed::{
+    EntryKey, FeedEntries, FeedEntry, FeedInfo, UpdateStatus, ENTRY_KEY_SCHEME_VERSION,
 };
-use rss_com_lib::rss_feed::{FeedEntries, FeedInfo};
 use rss_com_lib::Url;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{hash_map, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::sync::RwLock;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LockResult, OnceLock, RwLock, RwLockWriteGuard};
 
-const NEW_FEED_REQUEST_TIMEOUT: Duration = core::time::Duration::from_secs(10);
+/// All collections of all users, behind a single lock.
+///
+/// Lock ordering: there is only this one lock, and nothing takes it twice.
+/// [SaveInRonFile::save] (through the serde implementation of [RwLock]) takes the read lock
+/// itself, and releases it before returning. So it must be called without holding a guard on
+/// the collections. Taking the read lock again while already holding it can deadlock, when a
+/// writer is waiting in between.
+#[derive(Default, Deserialize, Debug)]
+#[serde(from = "StoredRssCollections")]
+pub struct RssCollections {
+    collections: RwLock<HashMap<UserId, UserCollections>>,
+    /// Goes up every time the write lock is taken, so the periodic saving can tell whether
+    /// anything changed without going through all the data. Not saved.
+    version: AtomicU64,
+}
 
-#[derive(Default, Serialize, Deserialize, Debug)]
-pub struct RssCollections(RwLock<HashMap<UserId, RssCollection>>);
+/// How [RssCollections] is saved: only the collections, without the `version`.
+#[derive(Deserialize)]
+#[serde(rename = "RssCollections")]
+struct StoredRssCollections(RwLock<HashMap<UserId, UserCollections>>);
+
+impl From<StoredRssCollections> for RssCollections {
+    fn from(stored: StoredRssCollections) -> Self {
+        RssCollections {
+            collections: stored.0,
+            version: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Serialize for RssCollections {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The same as the derived implementation of `StoredRssCollections`, so the file format
+        // didn't change when the `version` was added.
+        serializer.serialize_newtype_struct("RssCollections", &self.collections)
+    }
+}
+
+impl RssCollections {
+    /// Takes the write lock, and counts it as a change. Used instead of [RwLock::write] through
+    /// [std::ops::Deref], so no change can be missed.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, HashMap<UserId, UserCollections>>> {
+        // Increased before the change is made. If the saving reads the new version before the
+        // change is done, the save waits for the write lock to be released. So the saved data
+        // always contains the changes of the version it read.
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.collections.write()
+    }
+
+    /// See [RssCollections::write].
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// The users that have collections, but for which `user_exists` returns false.
+    /// For example because they were removed from the users file by hand.
+    pub fn orphaned_users(&self, user_exists: impl Fn(&UserId) -> bool) -> Vec<UserId> {
+        let mut orphans: Vec<UserId> = self
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|user| !user_exists(user))
+            .copied()
+            .collect();
+        orphans.sort_by_key(|user| user.0);
+        orphans
+    }
+
+    /// Loads the collections, or converts the file from before users could have multiple
+    /// collections. The feeds from that file end up in the default collection of each user.
+    pub fn load_or_migrate() -> Self {
+        if let Some(collections) = Self::load() {
+            return collections;
+        }
+
+        match LegacyRssCollections::load() {
+            Some(legacy) => {
+                info!(
+                    "Moving the feeds from `{}` to `{}`",
+                    LegacyRssCollections::FILE_NAME,
+                    Self::FILE_NAME
+                );
+                let collections = Self::from(legacy);
+                collections.save();
+                collections
+            }
+            None => Self::default(),
+        }
+    }
+
+    /// Makes sure the keys of all entries are generated with the current [ENTRY_KEY_SCHEME_VERSION].
+    /// Call this after loading the collections from disk.
+    pub fn migrate_entry_keys(&self) {
+        let mut collections = self.write().unwrap();
+
+        for collection in collections
+            .values_mut()
+            .flat_map(|user_collections| user_collections.values_mut())
+        {
+            for feed in collection.values_mut() {
+                feed.migrate_entry_keys();
+            }
+        }
+    }
+}
+
+impl From<LegacyRssCollections> for RssCollections {
+    fn from(legacy: LegacyRssCollections) -> Self {
+        let collections = legacy
+            .0
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(user, LegacyRssCollection(feeds))| {
+                let mut user_collections = UserCollections::default();
+                user_collections.insert(
+                    CollectionId::default(),
+                    RssCollection {
+                        name: DEFAULT_COLLECTION_NAME.to_string(),
+                        feeds,
+                    },
+                );
+                (user, user_collections)
+            })
+            .collect();
 
-impl Hash for RssCollections {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        let collections = self.read().unwrap();
-        for (user, collection) in collections.iter() {
-            user.hash(state);
-            collection.hash(state);
+        RssCollections {
+            collections: RwLock::new(collections),
+            version: AtomicU64::new(0),
         }
     }
 }
@@ -33,42 +165,155 @@ impl Hash for RssCollections {
 /// TODO (Wybe 2022-09-25): Implement that this is saved every minute or so if it has changed. But not every time a request comes through.
 ///   Also, it should be saved when the server is stopped, for example by pressing Ctrl+C.
 impl SaveInRonFile for RssCollections {
-    const FILE_NAME: &'static str = "collections.ron";
+    const FILE_NAME: &'static str = "user_collections.ron";
 }
 
 impl std::ops::Deref for RssCollections {
-    type Target = RwLock<HashMap<UserId, RssCollection>>;
+    type Target = RwLock<HashMap<UserId, UserCollections>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.collections
     }
 }
 
 impl std::ops::DerefMut for RssCollections {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.collections
+    }
+}
+
+/// The format of the collections file from before users could have multiple collections.
+/// Only used to convert that file to [RssCollections].
+#[derive(Default, Serialize, Deserialize, Debug)]
+struct LegacyRssCollections(RwLock<HashMap<UserId, LegacyRssCollection>>);
+
+impl SaveInRonFile for LegacyRssCollections {
+    const FILE_NAME: &'static str = "collections.ron";
+}
+
+#[derive(Default, Serialize, Deserialize, Debug)]
+struct LegacyRssCollection(HashMap<Url, RssFeed>);
+
+const DEFAULT_COLLECTION_NAME: &str = "Default";
+/// How many entries [is_url_an_rss_feed] sends along, to keep the response small.
+const FEED_PREVIEW_ENTRIES: usize = 5;
+
+/// All the collections of a single user.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct UserCollections(HashMap<CollectionId, RssCollection>);
+
+impl UserCollections {
+    /// The default collection always exists, so it is created when it is asked for.
+    /// Other collections have to be created with [UserCollections::create] first.
+    fn get_or_create(&mut self, id: CollectionId) -> Option<&mut RssCollection> {
+        if id == CollectionId::default() {
+            Some(self.entry(id).or_insert_with(|| RssCollection {
+                name: DEFAULT_COLLECTION_NAME.to_string(),
+                feeds: HashMap::new(),
+            }))
+        } else {
+            self.get_mut(&id)
+        }
+    }
+
+    /// Creates a new, empty, collection and returns its id.
+    fn create(&mut self, name: String) -> CollectionId {
+        let id = self
+            .keys()
+            .max()
+            .map(|max| CollectionId(max.0 + 1))
+            .unwrap_or(CollectionId(1));
+        self.insert(
+            id,
+            RssCollection {
+                name,
+                feeds: HashMap::new(),
+            },
+        );
+        id
+    }
+
+    /// Sorted by id, so the default collection is always first.
+    fn infos(&self) -> Vec<CollectionInfo> {
+        let mut infos: Vec<CollectionInfo> = self
+            .iter()
+            .map(|(id, collection)| CollectionInfo {
+                id: *id,
+                name: collection.name.clone(),
+            })
+            .collect();
+
+        if !self.contains_key(&CollectionId::default()) {
+            infos.push(CollectionInfo {
+                id: CollectionId::default(),
+                name: DEFAULT_COLLECTION_NAME.to_string(),
+            });
+        }
+
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+
+    /// Amount of feeds over all collections. A feed that is in multiple collections is counted
+    /// multiple times.
+    fn feed_amount(&self) -> usize {
+        self.values().map(|collection| collection.len()).sum()
+    }
+}
+
+impl std::ops::Deref for UserCollections {
+    type Target = HashMap<CollectionId, RssCollection>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for UserCollections {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
-pub struct RssCollection(HashMap<Url, RssFeed>);
+pub struct RssCollection {
+    name: String,
+    feeds: HashMap<Url, RssFeed>,
+}
 
 impl RssCollection {
-    /// Returns the entries, and how many there were in total.
+    /// Returns the entries, how many there were in total, and the cursor to request the next
+    /// entries with if there are more entries available.
+    ///
+    /// If `dedupe` is true, entries that appear in multiple feeds are only returned once.
+    /// See [merge_duplicate_entries].
+    #[allow(clippy::too_many_arguments)]
     fn get_sorted_com_entries_with_filter(
         &self,
         amount: usize,
         feed_filter: FeedsFilter,
         entry_filter: EntryTypeFilter,
-    ) -> (Vec<ComFeedEntry>, usize) {
+        after: Option<&EntryCursor>,
+        dedupe: bool,
+        published_after: Option<DateTime<Utc>>,
+        sort_order: SortOrder,
+    ) -> (Vec<ComFeedEntry>, usize, Option<EntryCursor>) {
         let mut entries: Vec<ComFeedEntry> = match feed_filter {
             FeedsFilter::All => self
                 .iter()
                 .flat_map(|(url, feed)| {
                     feed.entries
                         .iter()
-                        .filter(|(_, entry)| entry_filter.apply(entry))
-                        .map(|(key, entry)| ComFeedEntry::new(url.clone(), key.clone(), entry))
+                        .filter(|(_, entry)| dedupe || entry_filter.apply(entry))
+                        .map(|(key, entry)| {
+                            ComFeedEntry::new(
+                                url.clone(),
+                                key.clone(),
+                                entry,
+                                &feed.info,
+                                sort_order,
+                            )
+                        })
                 })
                 .collect(),
             FeedsFilter::Tag(tag) => self
@@ -77,16 +322,32 @@ impl RssCollection {
                 .flat_map(|(url, feed)| {
                     feed.entries
                         .iter()
-                        .filter(|(_, entry)| entry_filter.apply(entry))
-                        .map(|(key, entry)| ComFeedEntry::new(url.clone(), key.clone(), entry))
+                        .filter(|(_, entry)| dedupe || entry_filter.apply(entry))
+                        .map(|(key, entry)| {
+                            ComFeedEntry::new(
+                                url.clone(),
+                                key.clone(),
+                                entry,
+                                &feed.info,
+                                sort_order,
+                            )
+                        })
                 })
                 .collect(),
             FeedsFilter::Single(url) => {
                 if let Some(feed) = self.get(&url) {
                     feed.entries
                         .iter()
-                        .filter(|(_, entry)| entry_filter.apply(entry))
-                        .map(|(key, entry)| ComFeedEntry::new(url.clone(), key.clone(), entry))
+                        .filter(|(_, entry)| dedupe || entry_filter.apply(entry))
+                        .map(|(key, entry)| {
+                            ComFeedEntry::new(
+                                url.clone(),
+                                key.clone(),
+                                entry,
+                                &feed.info,
+                                sort_order,
+                            )
+                        })
                         .collect()
                 } else {
                     vec![]
@@ -94,93 +355,585 @@ impl RssCollection {
             }
         };
 
-        entries.sort();
+        if dedupe {
+            // The entry filter can only be applied after merging, because the read state of
+            // a merged entry depends on all of its copies.
+            entries = merge_duplicate_entries(entries);
+            entries.retain(|entry| entry_filter.apply_to_state(entry.read, entry.read_later));
+        }
+
+        if let Some(published_after) = published_after {
+            // Entries without a publication date have a date far in the past,
+            // so they are left out as well.
+            entries.retain(|entry| entry.pub_date > published_after);
+        }
+
         let total = entries.len();
+        if amount == 0 {
+            // Only the total is needed, so there is nothing to sort.
+            return (Vec::new(), total, None);
+        }
+
+        // Filter before sorting, so we don't sort entries that have already been sent.
+        if let Some(cursor) = after {
+            entries.retain(|entry| cursor.is_before(entry));
+        }
 
-        entries.truncate(amount);
-        (entries, total)
+        entries.sort();
+
+        let next_cursor = if entries.len() > amount {
+            entries.truncate(amount);
+            entries.last().map(EntryCursor::from_entry)
+        } else {
+            None
+        };
+
+        (entries, total, next_cursor)
     }
-}
 
-impl Hash for RssCollection {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        // The hashmap always returns the values in the same order, unless it has been changed.
-        // Which is exactly what we want, because the hash is used for change detection.
+    /// Applies the read state of the `entries` to the entries of the feed with the same key.
+    /// Like [set_entry_read], the copies in other feeds get the same read state.
+    /// Returns [None] if the feed is not in this collection.
+    fn import_read_state(
+        &mut self,
+        feed_url: &Url,
+        entries: &[ImportedEntry],
+    ) -> Option<ImportFeedResponse> {
+        let feed = self.get(feed_url)?;
+        let (matched, unmatched): (Vec<&ImportedEntry>, Vec<&ImportedEntry>) = entries
+            .iter()
+            .partition(|imported| feed.entries.contains_key(&imported.key));
+
+        for imported in &matched {
+            for feed in self.values_mut() {
+                if let Some(entry) = feed.entries.get_mut(&imported.key) {
+                    entry.read = imported.read;
+                }
+            }
+        }
+
+        Some(ImportFeedResponse {
+            matched: matched.len(),
+            unmatched: unmatched.len(),
+        })
+    }
+
+    /// Moves the read state and "read later" list of the feed with `merge` onto the same entries
+    /// of the feed with `keep`, and removes `merge`. Entries are matched by key, or otherwise by
+    /// link, because the key can differ when the feeds give their entries differently.
+    /// An entry that is read or on the "read later" list in either feed, stays that way.
+    /// Entries that `keep` doesn't have are dropped.
+    /// Returns [None] if either feed is not in this collection, or they are the same feed.
+    fn merge_feeds(&mut self, keep: &Url, merge: &Url) -> Option<MergeFeedsResponse> {
+        if keep == merge || !self.contains_key(keep) {
+            return None;
+        }
+        let merged = self.remove(merge)?;
+
+        let keep_entries = &self.get(keep)?.entries;
+        let keys_by_link: HashMap<&Url, &EntryKey> = keep_entries
+            .iter()
+            .filter_map(|(key, entry)| Some((entry.link.as_ref()?, key)))
+            .collect();
+        let carried_over: Vec<(EntryKey, &FeedEntry)> = merged
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.read || entry.read_later)
+            .filter_map(|(key, entry)| {
+                let key = if keep_entries.contains_key(key) {
+                    key
+                } else {
+                    keys_by_link.get(entry.link.as_ref()?).copied()?
+                };
+                Some((key.clone(), entry))
+            })
+            .collect();
+
+        for (key, entry) in &carried_over {
+            if entry.read {
+                self.set_entry_read(keep, key, true);
+            }
+            if entry.read_later {
+                self.set_entry_read_later(keep, key, true);
+            }
+        }
+
+        Some(MergeFeedsResponse {
+            carried_over: carried_over.len(),
+        })
+    }
+
+    /// Sets the read state of the entry with `key` in the feed with `feed_url`.
+    /// The same entry can be in multiple feeds. All the copies get the same read state,
+    /// so that de-duplicated entries stay in sync.
+    /// Returns false if the feed is not in this collection, or does not have the entry.
+    fn set_entry_read(&mut self, feed_url: &Url, key: &EntryKey, read: bool) -> bool {
+        let Some(feed) = self.get(feed_url) else {
+            return false;
+        };
+        if !feed.entries.contains_key(key) {
+            return false;
+        }
+
+        for feed in self.values_mut() {
+            if let Some(entry) = feed.entries.get_mut(key) {
+                entry.read = read;
+            }
+        }
+
+        true
+    }
+
+    /// Puts the entry with `key` in the feed with `feed_url` on the "read later" list, or takes
+    /// it off. Like [set_entry_read], all the copies of the entry get the same state.
+    /// Returns false if the feed is not in this collection, or does not have the entry.
+    fn set_entry_read_later(&mut self, feed_url: &Url, key: &EntryKey, read_later: bool) -> bool {
+        let Some(feed) = self.get(feed_url) else {
+            return false;
+        };
+        if !feed.entries.contains_key(key) {
+            return false;
+        }
+
+        for feed in self.values_mut() {
+            if let Some(entry) = feed.entries.get_mut(key) {
+                entry.read_later = read_later;
+            }
+        }
+
+        true
+    }
+
+    /// Marks all entries of the feed with `feed_url` as unread. The "read later" list is left
+    /// alone. Like [set_entry_read], the copies of the entries in other feeds become unread too.
+    /// Returns how many entries were read before, or [None] if the feed is not in this collection.
+    fn reset_read(&mut self, feed_url: &Url) -> Option<usize> {
+        let read_keys: Vec<EntryKey> = self
+            .get(feed_url)?
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.read)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &read_keys {
+            self.set_entry_read(feed_url, key, false);
+        }
+
+        Some(read_keys.len())
+    }
+
+    /// Marks the entries of the feeds in the filter of the `request` as read.
+    /// Does nothing if the request [needs confirmation](MarkAllReadRequest::needs_confirmation),
+    /// only counts how many entries would be marked.
+    fn mark_all_read(&mut self, request: &MarkAllReadRequest) -> MarkAllReadResponse {
+        let filter = &request.filter;
+        let mut marked: HashMap<EntryKey, Url> = HashMap::new();
         for (url, feed) in self.iter() {
-            url.hash(state);
-            feed.hash(state);
+            let matches = match filter {
+                FeedsFilter::All => true,
+                FeedsFilter::Tag(tag) => feed.info.tags.contains(tag),
+                FeedsFilter::Single(single_url) => single_url == url,
+            };
+            if !matches {
+                continue;
+            }
+
+            for (key, _) in feed.entries.iter().filter(|(_, entry)| !entry.read) {
+                marked.entry(key.clone()).or_insert_with(|| url.clone());
+            }
+        }
+
+        if request.needs_confirmation() {
+            return MarkAllReadResponse::NeedsConfirmation {
+                unread: marked.len(),
+            };
+        }
+
+        MarkAllReadResponse::Marked(
+            marked
+                .into_iter()
+                .filter(|(key, url)| self.set_entry_read(url, key, true))
+                .map(|(entry_key, feed_url)| SetEntryReadRequestAndResponse {
+                    collection_id: request.collection_id,
+                    feed_url,
+                    entry_key,
+                    read: true,
+                })
+                .collect(),
+        )
+    }
+
+    /// Amount of entries over all feeds. Entries that are in multiple feeds are counted once.
+    fn entry_total(&self) -> usize {
+        self.values()
+            .flat_map(|feed| feed.entries.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Amount of unread entries over all feeds.
+    /// Entries that are in multiple feeds are counted once, and only if none of the copies
+    /// is read. The same as [merge_duplicate_entries] does.
+    fn unread_total(&self) -> usize {
+        let mut unread_keys = HashSet::new();
+        let mut read_keys = HashSet::new();
+
+        for (key, entry) in self.values().flat_map(|feed| feed.entries.iter()) {
+            if entry.read {
+                read_keys.insert(key);
+            } else {
+                unread_keys.insert(key);
+            }
+        }
+
+        unread_keys.difference(&read_keys).count()
+    }
+}
+
+/// Merges entries with the same key (and thus the same title and link) that come from
+/// different feeds. This happens for example when following both the main feed of a site
+/// and one of its category feeds.
+///
+/// The copy from the feed with the lowest url is kept, so that the result is always the same.
+/// The merged entry counts as read if any of the copies is read. [set_entry_read] updates all
+/// copies, so normally they all have the same read state. The same goes for `read_later`.
+/// The `sources` of the merged entry are the feeds of all copies.
+fn merge_duplicate_entries(entries: Vec<ComFeedEntry>) -> Vec<ComFeedEntry> {
+    let mut merged: HashMap<EntryKey, ComFeedEntry> = HashMap::new();
+
+    for entry in entries {
+        match merged.entry(entry.key.clone()) {
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+            }
+            hash_map::Entry::Occupied(mut occupied) => {
+                let read = occupied.get().read || entry.read;
+                let read_later = occupied.get().read_later || entry.read_later;
+                let highlighted = occupied.get().highlighted || entry.highlighted;
+                let mut sources = occupied.get().sources.clone();
+                sources.extend(entry.sources.iter().cloned());
+                sources.sort();
+                sources.dedup();
+                if entry.feed_url < occupied.get().feed_url {
+                    occupied.insert(entry);
+                }
+                occupied.get_mut().read = read;
+                occupied.get_mut().read_later = read_later;
+                occupied.get_mut().highlighted = highlighted;
+                occupied.get_mut().sources = sources;
+            }
         }
     }
+
+    merged.into_values().collect()
 }
 
 impl std::ops::Deref for RssCollection {
     type Target = HashMap<Url, RssFeed>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.feeds
     }
 }
 
 impl std::ops::DerefMut for RssCollection {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.feeds
     }
 }
 
 /// Represents a single rss feed.
 ///
 /// Implements [Default] so that adding new entries won't break the loading of old files.
-#[derive(Serialize, Deserialize, Debug, Default, Hash)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(default)]
 pub struct RssFeed {
     info: FeedInfo,
     entries: FeedEntries,
+    /// The [ENTRY_KEY_SCHEME_VERSION] the keys of the `entries` were generated with.
+    /// Files saved before this was introduced default to 0.
+    key_scheme_version: u32,
 }
 
 impl RssFeed {
     pub fn new(info: FeedInfo, entries: FeedEntries) -> Self {
-        RssFeed { info, entries }
+        RssFeed {
+            info,
+            entries,
+            key_scheme_version: ENTRY_KEY_SCHEME_VERSION,
+        }
+    }
+
+    /// A feed that was just downloaded for the first time, because a user added it.
+    pub fn new_downloaded(feed: Feed, tags: HashSet<String>) -> Self {
+        let now = Utc::now();
+        let info = FeedInfo {
+            name: feed.title,
+            tags,
+            home_url: feed.home_url,
+            update_status: UpdateStatus::Ok { at: now },
+            last_successful_update: Some(now),
+            recommended_refresh_interval_minutes: feed.recommended_refresh_interval_minutes,
+            last_parse_diagnostics: Some(feed.parse_diagnostics),
+            description: feed.description,
+            language: feed.language,
+            icon_url: feed.icon_url,
+            ..Default::default()
+        };
+
+        RssFeed::new(info, feed.entries)
+    }
+
+    pub fn info(&self) -> &FeedInfo {
+        &self.info
+    }
+
+    /// Takes over the info of the feed that can change between downloads.
+    pub fn update_info(&mut self, feed_update: &Feed) {
+        self.info.home_url = feed_update.home_url.clone();
+        self.info.recommended_refresh_interval_minutes =
+            feed_update.recommended_refresh_interval_minutes;
+        self.info.last_parse_diagnostics = Some(feed_update.parse_diagnostics.clone());
+        self.info.description = feed_update.description.clone();
+        self.info.language = feed_update.language.clone();
+        self.info.icon_url = feed_update.icon_url.clone();
+    }
+
+    /// If the keys of the entries were generated with an older [ENTRY_KEY_SCHEME_VERSION],
+    /// they are generated again with the current scheme.
+    /// When multiple entries end up with the same key, they are merged, and the result counts
+    /// as read if any of them was read.
+    ///
+    /// Entries saved before guids were used don't have a guid, so for those this gives the same
+    /// title and link based key. They are moved to their guid based key on the next update
+    /// instead, see [RssFeed::update_entries].
+    pub fn migrate_entry_keys(&mut self) {
+        if self.key_scheme_version >= ENTRY_KEY_SCHEME_VERSION {
+            return;
+        }
+
+        let old_entries = std::mem::take(&mut self.entries);
+        for (_, entry) in old_entries {
+            let key = EntryKey::from_entry(&entry);
+
+            match self.entries.entry(key) {
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(entry);
+                }
+                hash_map::Entry::Occupied(mut occupied) => {
+                    occupied.get_mut().read |= entry.read;
+                    occupied.get_mut().read_later |= entry.read_later;
+                }
+            }
+        }
+
+        self.key_scheme_version = ENTRY_KEY_SCHEME_VERSION;
     }
 
     /// Checks if any of the given entries are new, and updates the feed with them.
     /// Leaves any existing entries as-is.
-    pub fn update_entries(&mut self, maybe_entries: Result<FeedEntries, String>) {
+    ///
+    /// Entries saved before guids were used for the [EntryKey] are stored under their title
+    /// and link based key. When such an entry comes by with a guid, it is moved to its new key,
+    /// keeping its read state. Otherwise it would show up again as a new unread entry.
+    ///
+    /// Afterwards, old unread entries and entries with a muted keyword are marked read if the
+    /// feed is set up for that. See [RssFeed::mark_old_entries_read] and [RssFeed::mute_entries].
+    pub fn update_entries(&mut self, maybe_entries: Result<FeedEntries, FeedFetchError>) {
         match maybe_entries {
             Ok(entries) => {
                 for (key, entry) in entries.into_iter() {
-                    self.entries.entry(key).or_insert(entry);
+                    if let Some(existing) = self.entries.get_mut(&key) {
+                        // Entries with a guid keep their key when they are edited,
+                        // so this is how edits show up when sorting by last activity.
+                        existing.updated = entry.updated;
+                        continue;
+                    }
+
+                    let legacy_key = EntryKey::from_title_and_link(&entry);
+                    if let Some(existing) = self.entries.remove(&legacy_key) {
+                        self.entries.insert(
+                            key,
+                            FeedEntry {
+                                read: existing.read,
+                                read_later: existing.read_later,
+                                ..entry
+                            },
+                        );
+                    } else {
+                        self.entries.insert(key, entry);
+                    }
                 }
 
-                self.info.last_update_result = Ok(());
+                let now = Utc::now();
+                self.mark_old_entries_read(now);
+                self.mute_entries();
+                self.info.update_status = UpdateStatus::Ok { at: now };
+                self.info.last_successful_update = Some(now);
             }
             Err(error) => {
-                self.info.last_update_result = Err(error);
+                self.info.update_status = UpdateStatus::Failed {
+                    at: Utc::now(),
+                    message: error.to_string(),
+                    kind: error.kind(),
+                };
+            }
+        }
+    }
+
+    /// Marks unread entries that were published more than [FeedInfo::mark_read_after_days] before
+    /// `now` as read. Entries on the "read later" list stay unread.
+    fn mark_old_entries_read(&mut self, now: DateTime<Utc>) {
+        let Some(days) = self.info.mark_read_after_days else {
+            return;
+        };
+        let cutoff = now - chrono::Duration::days(days.into());
+
+        for entry in self.entries.values_mut() {
+            if !entry.read_later && entry.pub_date < cutoff {
+                entry.read = true;
+            }
+        }
+    }
+
+    /// Marks unread entries with one of the [FeedInfo::mute_keywords] in their title as read.
+    /// Entries on the "read later" list stay unread.
+    fn mute_entries(&mut self) {
+        for entry in self.entries.values_mut() {
+            if !entry.read_later && self.info.is_muted(&entry.title) {
+                entry.read = true;
+            }
+        }
+    }
+
+    /// Removes old read entries, so the saved collections don't keep growing forever.
+    /// - Read entries published longer than `max_read_age` ago are removed.
+    /// - If there are still more than `max_entries`, the oldest read entries are removed until
+    ///   there are `max_entries` left, or there is nothing left to remove.
+    ///
+    /// Unread entries are never removed, and neither are entries on the "read later" list.
+    /// Nor entries that are in `latest_entries` (what the feed currently publishes), because
+    /// those would come back as unread on the next update.
+    pub fn prune(
+        &mut self,
+        max_entries: Option<usize>,
+        max_read_age: Option<chrono::Duration>,
+        latest_entries: &FeedEntries,
+    ) {
+        let removable = |key: &EntryKey, entry: &FeedEntry| {
+            entry.read && !entry.read_later && !latest_entries.contains_key(key)
+        };
+
+        if let Some(max_read_age) = max_read_age {
+            let cutoff = Utc::now() - max_read_age;
+            self.entries
+                .retain(|key, entry| !(removable(key, entry) && entry.pub_date < cutoff));
+        }
+
+        if let Some(max_entries) = max_entries {
+            if self.entries.len() > max_entries {
+                let mut candidates: Vec<(DateTime<Utc>, EntryKey)> = self
+                    .entries
+                    .iter()
+                    .filter(|&(key, entry)| removable(key, entry))
+                    .map(|(key, entry)| (entry.pub_date, key.clone()))
+                    .collect();
+                // Oldest first.
+                candidates.sort();
+
+                let excess = self.entries.len() - max_entries;
+                for (_, key) in candidates.into_iter().take(excess) {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// How many old entries are kept per feed. See [RssFeed::prune].
+#[derive(Clone, Copy, Debug)]
+pub struct EntryRetention {
+    /// [None] means there is no limit.
+    pub max_entries_per_feed: Option<usize>,
+    /// [None] means read entries are kept forever.
+    pub max_read_age: Option<chrono::Duration>,
+}
+
+/// Query parameters that are removed from entry links before they are sent to the client.
+/// See [Url::without_query_params] for the format.
+#[derive(Clone, Debug, Default)]
+pub struct StripQueryParams(pub Vec<String>);
+
+/// Limits that keep a single user from taking up too much of the server.
+/// [None] means there is no limit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UserLimits {
+    pub max_feeds_per_user: Option<usize>,
+    pub max_tags_per_feed: Option<usize>,
+}
+
+impl UserLimits {
+    /// Returns the message for the user if another feed would go over the limit.
+    fn check_new_feed(&self, current_feed_amount: usize) -> Result<(), String> {
+        match self.max_feeds_per_user {
+            Some(max) if current_feed_amount >= max => {
+                Err(format!("You can have at most {} feeds", max))
             }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the message for the user if a feed can't have this many tags.
+    fn check_tags(&self, tags: &HashSet<String>) -> Result<(), String> {
+        match self.max_tags_per_feed {
+            Some(max) if tags.len() > max => Err(format!("A feed can have at most {} tags", max)),
+            _ => Ok(()),
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[post("/feeds")]
 pub async fn get_feeds(
+    http_request: HttpRequest,
     request: web::Json<FeedsRequest>,
     auth: Authenticated,
     collections: web::Data<RssCollections>,
     requester: web::Data<FeedRequester>,
+    timeouts: web::Data<FeedTimeouts>,
+    strip_query_params: web::Data<StripQueryParams>,
+    update_statuses: web::Data<UpdateStatuses>,
 ) -> impl Responder {
+    // Read before anything else, so the tag never claims a newer state than the response has.
+    let etag = feeds_etag(collections.version(), auth.user_id(), &request);
+    let only_reads = matches!(
+        request.additional_action,
+        AdditionalAction::None | AdditionalAction::IncludeFeedsInfo
+    );
+    if only_reads && is_not_modified(&http_request, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag.to_string()))
+            .finish();
+    }
+
     let result = {
-        let feeds_info = match request.additional_action {
+        let feeds_info = match &request.additional_action {
             AdditionalAction::None => None,
             AdditionalAction::IncludeFeedsInfo => {
                 let collections = collections.read().unwrap();
-                collections.get(auth.user_id()).map(|collection| {
-                    collection
-                        .iter()
-                        .map(|(key, feed)| (key.clone(), feed.info.clone()))
-                        .collect()
-                })
+                collections
+                    .get(auth.user_id())
+                    .and_then(|user_collections| user_collections.get(&request.collection_id))
+                    .map(|collection| {
+                        collection
+                            .iter()
+                            .map(|(key, feed)| (key.clone(), feed.info.clone()))
+                            .collect()
+                    })
             }
-            AdditionalAction::UpdateFeeds => {
-                // Update all url's
+            AdditionalAction::UpdateFeeds | AdditionalAction::UpdateSingle(_) => {
+                // Update all url's, or only the requested one.
                 // We collect the urls to be updated separately from the update:
                 // Because according to clippy, it is not a good idea to hold a mutex lock across an `await`.
                 let maybe_urls = {
@@ -190,31 +943,62 @@ pub async fn get_feeds(
 
                     collections
                         .get(auth.user_id())
-                        .map(|collection| collection.iter().map(|(url, _)| url.clone()).collect())
+                        .and_then(|user_collections| user_collections.get(&request.collection_id))
+                        .map(|collection| {
+                            collection
+                                .iter()
+                                .filter(|(url, feed)| match &request.additional_action {
+                                    // Also paused feeds, the user asked for this one specifically.
+                                    AdditionalAction::UpdateSingle(single_url) => {
+                                        *url == single_url
+                                    }
+                                    _ => feed.info.enabled,
+                                })
+                                .map(|(url, _)| url.clone())
+                                .collect::<HashSet<Url>>()
+                        })
                 };
 
                 if let Some(urls) = maybe_urls {
-                    let update_timeout = core::time::Duration::from_secs(5);
-
                     // This is the call that performs the actual updates.
                     // TODO (2024-09-03): On the raspberry pi there are too many requests that go wrong, that go ok the next time I try.
                     //                    This does not happen when I test this locally on my laptop. Then only the feeds that don't exist get a red question mark.
                     //                    What are the errors that happen, and why?
-                    let mut feeds = requester.request_feeds(&urls, update_timeout).await;
+                    let mut feeds =
+                        if matches!(request.additional_action, AdditionalAction::UpdateFeeds) {
+                            let user = *auth.user_id();
+                            update_statuses.start(user, urls.len());
+                            let feeds = requester
+                                .request_feeds_reporting(&urls, timeouts.update, |url, success| {
+                                    update_statuses.record(user, url, success)
+                                })
+                                .await;
+                            update_statuses.finish(user);
+                            feeds
+                        } else {
+                            requester.request_feeds(&urls, timeouts.update).await
+                        };
 
                     let mut collections = collections.write().unwrap();
-                    if let Some(collection) = collections.get_mut(auth.user_id()) {
+                    if let Some(collection) =
+                        collections
+                            .get_mut(auth.user_id())
+                            .and_then(|user_collections| {
+                                user_collections.get_mut(&request.collection_id)
+                            })
+                    {
                         for url in &urls {
                             if let Some(feed) = collection.get_mut(url) {
                                 // Feed exists in the users collection.
                                 if let Some(maybe_feed_update) = feeds.remove(url) {
-                                    let maybe_entries = maybe_feed_update
-                                        .map(|feed| feed.entries)
-                                        .map_err(|error| full_error_to_string(&error));
+                                    if let Ok(feed_update) = &maybe_feed_update {
+                                        feed.update_info(feed_update);
+                                    }
+                                    let maybe_entries = maybe_feed_update.map(|feed| feed.entries);
                                     feed.update_entries(maybe_entries);
                                 } else {
                                     // Feed is in the users collection, but the update request did not return a result.
-                                    feed.update_entries(Err("Feed update was requested, but the function did not return anything.".to_string()));
+                                    feed.update_entries(Err(FeedFetchError::Other("Feed update was requested, but the function did not return anything.".to_string())));
                                 }
                             }
                         }
@@ -235,19 +1019,61 @@ pub async fn get_feeds(
         };
 
         let collections = collections.read().unwrap();
+        let empty_collection = RssCollection::default();
+
+        let maybe_collection = collections
+            .get(auth.user_id())
+            .and_then(|user_collections| user_collections.get(&request.collection_id));
+        let (collection, feeds_info) = match maybe_collection {
+            Some(collection) => (Some(collection), feeds_info),
+            // The default collection is only created when the first feed is added.
+            // So a user without feeds simply gets an empty response.
+            None if request.collection_id == CollectionId::default() => {
+                let feeds_info = match request.additional_action {
+                    AdditionalAction::None => None,
+                    _ => Some(HashMap::new()),
+                };
+                (Some(&empty_collection), feeds_info)
+            }
+            // The collection does not exist for this user.
+            None => (None, None),
+        };
 
-        if let Some(collection) = collections.get(auth.user_id()) {
-            let (entries, total) = collection.get_sorted_com_entries_with_filter(
+        if let Some(collection) = collection {
+            let (entries, total, next_cursor) = collection.get_sorted_com_entries_with_filter(
                 request.amount,
                 request.filter.clone(),
                 request.entry_filter,
+                request.after.as_ref(),
+                request.dedupe,
+                request.published_after,
+                request.sort_order,
             );
 
-            HttpResponse::Ok().json(FeedsResponse {
-                feed_entries: entries,
-                total_available: total,
-                feeds_info,
-            })
+            // Only the links that are sent are cleaned, the stored entries keep the original link.
+            // Otherwise the entry keys, which are based on the link, would change.
+            let entries: Vec<ComFeedEntry> = entries
+                .into_iter()
+                .map(|mut entry| {
+                    entry.link = entry
+                        .link
+                        .map(|link| link.without_query_params(&strip_query_params.0));
+                    entry
+                })
+                .collect();
+
+            HttpResponse::Ok()
+                .insert_header((ETAG, etag.to_string()))
+                .json(FeedsResponse {
+                    feed_entries: entries,
+                    total_available: total,
+                    has_more: next_cursor.is_some(),
+                    next_cursor,
+                    requested_after: request.after.clone(),
+                    total_entries: feeds_info.as_ref().map(|_| collection.entry_total()),
+                    feeds_info,
+                    total_unread: collection.unread_total(),
+                })
         } else {
             HttpResponse::Forbidden().finish()
         }
@@ -256,60 +1082,259 @@ pub async fn get_feeds(
     result
 }
 
+/// The `ETag` of the response to a `/feeds` request, which can be made without building the
+/// response. Anything that changes the collections changes the [RssCollections::version], and
+/// with it the tag.
+fn feeds_etag(version: u64, user: &UserId, request: &FeedsRequest) -> EntityTag {
+    // The version starts at 0 again when the server restarts, so the start of this server run
+    // keeps the tags from being the same as the ones of an earlier run.
+    static SERVER_RUN: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+    let mut hasher = DefaultHasher::new();
+    SERVER_RUN.get_or_init(Utc::now).hash(&mut hasher);
+    version.hash(&mut hasher);
+    user.hash(&mut hasher);
+    // The request is small, unlike the response.
+    serde_json::to_vec(request)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    EntityTag::new_strong(format!("{:016x}", hasher.finish()))
+}
+
+/// Whether the client already has the response with the `etag`, according to the
+/// `If-None-Match` header of the request. Then only `304 Not Modified` has to be sent.
+fn is_not_modified(http_request: &HttpRequest, etag: &EntityTag) -> bool {
+    match http_request.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        None => false,
+    }
+}
+
 /// Adds the given rss feed to the feed collection of the user.
-/// TODO (Wybe 2022-07-16): Sanitize url?
 #[post("/add_feed")]
 pub async fn add_feed(
     request: web::Json<AddFeedRequest>,
     auth: Authenticated,
     collections: web::Data<RssCollections>,
     requester: web::Data<FeedRequester>,
+    timeouts: web::Data<FeedTimeouts>,
+    limits: web::Data<UserLimits>,
 ) -> impl Responder {
-    info!(
-        "Adding feed for user `{}`: `{}`",
-        auth.user_name(),
-        request.url
-    );
+    let url = match Url::parse_and_normalize(request.url.as_str()) {
+        Ok(url) => url,
+        Err(error) => return HttpResponse::BadRequest().body(error.to_string()),
+    };
+    if let Err(message) = limits.check_tags(&request.tags) {
+        return HttpResponse::Forbidden().body(message);
+    }
+
+    info!("Adding feed for user `{}`: `{}`", auth.user_name(), url);
 
     {
-        // TODO (2024-08-21): Don't hold the collections mutex accross the await point.
         let mut collections = collections.write().unwrap();
-        let collection = if let Some(collection) = collections.get_mut(auth.user_id()) {
-            collection
-        } else {
-            collections.insert(*auth.user_id(), RssCollection::default());
-            collections.get_mut(auth.user_id()).unwrap()
+        let user_collections = collections.entry(*auth.user_id()).or_default();
+        if let Err(message) = limits.check_new_feed(user_collections.feed_amount()) {
+            return HttpResponse::Forbidden().body(message);
+        }
+        let collection = match user_collections.get_or_create(request.collection_id) {
+            Some(collection) => collection,
+            // The collection does not exist for this user.
+            None => return HttpResponse::Unauthorized().finish(),
         };
 
-        if !collection.contains_key(&request.url) {
-            // This feed is new for the user.
-            if let (_, Ok(new_feed)) = requester
-                .request_feed(&request.url, NEW_FEED_REQUEST_TIMEOUT)
-                .await
-            {
-                let info = FeedInfo {
-                    name: new_feed.title,
-                    tags: request.tags.clone(),
-                    last_update_result: Ok(()),
-                };
-
-                collection.insert(request.url.clone(), RssFeed::new(info, new_feed.entries));
-            } else {
-                // TODO (Wybe 2022-10-01): Return an error.
-            }
-        } else {
+        if collection.contains_key(&url) {
             info!(
                 "User `{}` already had feed `{}` in their collection",
                 auth.user_name(),
-                request.url
+                url
             );
             // TODO (Wybe 2022-09-19): Return an error.
+            return HttpResponse::Ok().finish();
+        }
+    } // The lock is dropped here, so that it isn't held while the feed is downloaded.
+
+    // This feed is new for the user.
+    if let (_, Ok(new_feed)) = requester.request_feed(&url, timeouts.new_feed).await {
+        let mut collections = collections.write().unwrap();
+        if let Some(user_collections) = collections.get_mut(auth.user_id()) {
+            // Other requests could have added feeds while this one was downloading.
+            if let Err(message) = limits.check_new_feed(user_collections.feed_amount()) {
+                return HttpResponse::Forbidden().body(message);
+            }
+            if let Some(collection) = user_collections.get_or_create(request.collection_id) {
+                collection
+                    .entry(url)
+                    .or_insert_with(|| RssFeed::new_downloaded(new_feed, request.tags.clone()));
+            }
         }
+    } else {
+        // TODO (Wybe 2022-10-01): Return an error.
     }
 
     HttpResponse::Ok().finish()
 }
 
+/// Adds multiple feeds to the feed collection of the user at once.
+/// The feeds are downloaded concurrently.
+#[post("/add_feeds")]
+pub async fn add_feeds(
+    request: web::Json<Vec<AddFeedRequest>>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+    requester: web::Data<FeedRequester>,
+    timeouts: web::Data<FeedTimeouts>,
+    limits: web::Data<UserLimits>,
+) -> impl Responder {
+    info!(
+        "Adding {} feeds for user `{}`",
+        request.len(),
+        auth.user_name()
+    );
+
+    let results = add_feeds_for_user(
+        &request,
+        *auth.user_id(),
+        &collections,
+        &requester,
+        timeouts.new_feed,
+        &limits,
+    )
+    .await;
+
+    HttpResponse::Ok().json(results)
+}
+
+/// Lighter than an OPML import: one url per line. Blank lines and lines starting with `#`
+/// are skipped. The feeds are added the same way as with [add_feeds].
+#[post("/import_url_list")]
+pub async fn import_url_list(
+    request: web::Json<ImportUrlListRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+    requester: web::Data<FeedRequester>,
+    timeouts: web::Data<FeedTimeouts>,
+    limits: web::Data<UserLimits>,
+) -> impl Responder {
+    let feed_requests: Vec<AddFeedRequest> = parse_url_list(&request.urls)
+        .into_iter()
+        .map(|url| AddFeedRequest {
+            collection_id: request.collection_id,
+            url,
+            tags: request.tags.clone(),
+        })
+        .collect();
+
+    info!(
+        "Importing a list of {} feeds for user `{}`",
+        feed_requests.len(),
+        auth.user_name()
+    );
+
+    let results = add_feeds_for_user(
+        &feed_requests,
+        *auth.user_id(),
+        &collections,
+        &requester,
+        timeouts.new_feed,
+        &limits,
+    )
+    .await;
+
+    HttpResponse::Ok().json(results)
+}
+
+/// The urls in a text with one url per line. Blank lines and lines starting with `#` are skipped.
+fn parse_url_list(text: &str) -> Vec<Url> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Url::new(line.to_string()))
+        .collect()
+}
+
+/// Downloads the feeds concurrently, and adds the ones that work.
+/// Returns the result for every requested url, in the same order.
+/// Feeds that would go over the [UserLimits] are not downloaded.
+async fn add_feeds_for_user(
+    feed_requests: &[AddFeedRequest],
+    user: UserId,
+    collections: &RssCollections,
+    requester: &FeedRequester,
+    timeout: core::time::Duration,
+    limits: &UserLimits,
+) -> AddFeedsResponse {
+    // Feeds that don't need to be downloaded already get their result here.
+    let mut results: Vec<(Url, Option<Result<(), String>>)> = Vec::new();
+    let mut requested_feeds: HashMap<Url, (CollectionId, HashSet<String>)> = HashMap::new();
+    {
+        let collections = collections.read().unwrap();
+        let user_collections = collections.get(&user);
+        let feed_amount = user_collections
+            .map(|user_collections| user_collections.feed_amount())
+            .unwrap_or_default();
+
+        for feed_request in feed_requests {
+            let collection = user_collections
+                .and_then(|user_collections| user_collections.get(&feed_request.collection_id));
+
+            match Url::parse_and_normalize(feed_request.url.as_str()) {
+                Ok(url) => {
+                    let already_added = requested_feeds.contains_key(&url)
+                        || collection.is_some_and(|collection| collection.contains_key(&url));
+                    let within_limits = limits
+                        .check_new_feed(feed_amount + requested_feeds.len())
+                        .and_then(|_| limits.check_tags(&feed_request.tags));
+                    if already_added {
+                        results.push((url, Some(Err("Feed was already added".to_string()))));
+                    } else if let Err(message) = within_limits {
+                        results.push((url, Some(Err(message))));
+                    } else {
+                        requested_feeds.insert(
+                            url.clone(),
+                            (feed_request.collection_id, feed_request.tags.clone()),
+                        );
+                        results.push((url, None));
+                    }
+                }
+                Err(error) => {
+                    results.push((feed_request.url.clone(), Some(Err(error.to_string()))));
+                }
+            }
+        }
+    } // The lock is dropped here, so that it isn't held while the feeds are downloaded.
+
+    let urls: HashSet<Url> = requested_feeds.keys().cloned().collect();
+    let mut feeds = requester.request_feeds(&urls, timeout).await;
+
+    let mut collections = collections.write().unwrap();
+    let user_collections = collections.entry(user).or_default();
+
+    results
+        .into_iter()
+        .map(|(url, maybe_result)| {
+            let result = maybe_result.unwrap_or_else(|| match feeds.remove(&url) {
+                Some(Ok(new_feed)) => {
+                    let (collection_id, tags) = requested_feeds.remove(&url).unwrap_or_default();
+                    // Other requests could have added feeds while these were downloading.
+                    limits.check_new_feed(user_collections.feed_amount())?;
+                    let Some(collection) = user_collections.get_or_create(collection_id) else {
+                        return Err("Collection does not exist".to_string());
+                    };
+                    collection
+                        .entry(url.clone())
+                        .or_insert_with(|| RssFeed::new_downloaded(new_feed, tags));
+                    Ok(())
+                }
+                Some(Err(error)) => Err(error.to_string()),
+                None => Err("Feed was not downloaded".to_string()),
+            });
+
+            (url, result)
+        })
+        .collect()
+}
+
 /// Checks a given rss feed for existence.
 /// Sends back the title of the feed if it exists.
 /// TODO (Wybe 2022-07-14): Can we do Rust object notation, instead of parsing from Json?
@@ -319,6 +1344,7 @@ pub async fn is_url_an_rss_feed(
     request: web::Json<IsUrlAnRssFeedRequest>,
     auth: Authenticated,
     requester: web::Data<FeedRequester>,
+    timeouts: web::Data<FeedTimeouts>,
 ) -> impl Responder {
     info!(
         "User `{}` tests url `{}` for existence of an rss feed",
@@ -326,20 +1352,53 @@ pub async fn is_url_an_rss_feed(
         request.url,
     );
 
-    let (_, maybe_feed) = requester
-        .request_feed(&request.url, NEW_FEED_REQUEST_TIMEOUT)
-        .await;
-    let result = match maybe_feed {
-        Ok(feed) => Ok(feed.title),
-        Err(err) => Err(err.to_string()),
+    let url = match Url::parse_and_normalize(request.url.as_str()) {
+        Ok(url) => url,
+        Err(error) => {
+            return HttpResponse::Ok().json(IsUrlAnRssFeedResponse {
+                requested_url: request.url.clone(),
+                result: Err(error.to_string()),
+                preview: vec![],
+                suggested_tags: vec![],
+            })
+        }
+    };
+
+    let (_, maybe_feed) = requester.request_feed(&url, timeouts.new_feed).await;
+    let (result, preview, suggested_tags) = match maybe_feed {
+        Ok(feed) => (
+            Ok(feed.title),
+            preview_entries(&feed.entries),
+            feed.suggested_tags,
+        ),
+        // The message includes the underlying reason, such as the host being refused.
+        Err(err) => (Err(err.to_string()), vec![], vec![]),
     };
 
+    // The normalized url is sent back, so that is the url the client will add.
     HttpResponse::Ok().json(IsUrlAnRssFeedResponse {
-        requested_url: Url::new(request.url.to_string()),
+        requested_url: url,
         result,
+        preview,
+        suggested_tags,
     })
 }
 
+/// The newest [FEED_PREVIEW_ENTRIES] entries, newest first.
+fn preview_entries(entries: &FeedEntries) -> Vec<PreviewEntry> {
+    let mut preview: Vec<PreviewEntry> = entries
+        .values()
+        .map(|entry| PreviewEntry {
+            title: entry.title.clone(),
+            link: entry.link.clone(),
+            pub_date: entry.pub_date,
+        })
+        .collect();
+    preview.sort_by(|a, b| b.pub_date.cmp(&a.pub_date).then(a.title.cmp(&b.title)));
+    preview.truncate(FEED_PREVIEW_ENTRIES);
+    preview
+}
+
 #[post("/set_entry_read")]
 pub async fn set_entry_read(
     request: web::Json<SetEntryReadRequestAndResponse>,
@@ -348,20 +1407,47 @@ pub async fn set_entry_read(
 ) -> impl Responder {
     {
         let mut collections = collections.write().unwrap();
-        if let Some(collection) = collections.get_mut(auth.user_id()) {
-            if let Some(feed) = collection.get_mut(&request.feed_url) {
-                if let Some(entry) = feed.entries.get_mut(&request.entry_key) {
-                    entry.read = request.read;
-                } else {
-                    // Entry does not exist in this feed.
-                    return HttpResponse::Unauthorized().finish();
-                }
-            } else {
-                // Feed does not exist for this user.
+        if let Some(collection) = collections
+            .get_mut(auth.user_id())
+            .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        {
+            if !collection.set_entry_read(&request.feed_url, &request.entry_key, request.read) {
+                // Feed or entry does not exist for this user.
+                return HttpResponse::Unauthorized().finish();
+            }
+        } else {
+            // The collection does not exist for this user.
+            return HttpResponse::Unauthorized().finish();
+        };
+    }
+
+    // Send the request straight back to the client, so it doesn't need to remember all the
+    // things it has requested from the server.
+    HttpResponse::Ok().json(request.into_inner())
+}
+
+#[post("/set_entry_read_later")]
+pub async fn set_entry_read_later(
+    request: web::Json<SetEntryReadLaterRequestAndResponse>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    {
+        let mut collections = collections.write().unwrap();
+        if let Some(collection) = collections
+            .get_mut(auth.user_id())
+            .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        {
+            if !collection.set_entry_read_later(
+                &request.feed_url,
+                &request.entry_key,
+                request.read_later,
+            ) {
+                // Feed or entry does not exist for this user.
                 return HttpResponse::Unauthorized().finish();
             }
         } else {
-            // A collection does not exist for this user.
+            // The collection does not exist for this user.
             return HttpResponse::Unauthorized().finish();
         };
     }
@@ -371,23 +1457,142 @@ pub async fn set_entry_read(
     HttpResponse::Ok().json(request.into_inner())
 }
 
+#[post("/set_entries_read")]
+pub async fn set_entries_read(
+    request: web::Json<SetEntriesReadRequestAndResponse>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let mut collections = collections.write().unwrap();
+    let Some(user_collections) = collections.get_mut(auth.user_id()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let changed: SetEntriesReadRequestAndResponse = request
+        .into_inner()
+        .into_iter()
+        .filter(|entry| {
+            user_collections
+                .get_mut(&entry.collection_id)
+                .is_some_and(|collection| {
+                    collection.set_entry_read(&entry.feed_url, &entry.entry_key, entry.read)
+                })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(changed)
+}
+
+#[post("/mark_all_read")]
+pub async fn mark_all_read(
+    request: web::Json<MarkAllReadRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let mut collections = collections.write().unwrap();
+    let Some(collection) = collections
+        .get_mut(auth.user_id())
+        .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+    else {
+        // The collection does not exist for this user.
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let response = collection.mark_all_read(&request);
+    if let MarkAllReadResponse::Marked(marked) = &response {
+        info!(
+            "User `{}` marked {} entries as read",
+            auth.user_name(),
+            marked.len()
+        );
+    }
+
+    HttpResponse::Ok().json(response)
+}
+
+#[post("/reset_read")]
+pub async fn reset_read(
+    request: web::Json<ResetReadRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let mut collections = collections.write().unwrap();
+    let Some(reset) = collections
+        .get_mut(auth.user_id())
+        .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        .and_then(|collection| collection.reset_read(&request.feed_url))
+    else {
+        // The collection or the feed does not exist for this user.
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    info!(
+        "User `{}` marked {} entries of feed `{}` as unread",
+        auth.user_name(),
+        reset,
+        request.feed_url
+    );
+
+    HttpResponse::Ok().json(ResetReadResponse { reset })
+}
+
+/// Amount of unread entries over all feeds in a collection of the user.
+/// Is cheap to request, so the client can keep it up to date after marking entries as read.
+#[post("/unread_total")]
+pub async fn get_unread_total(
+    request: web::Json<UnreadTotalRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let collections = collections.read().unwrap();
+    let total_unread = collections
+        .get(auth.user_id())
+        .and_then(|user_collections| user_collections.get(&request.collection_id))
+        .map(|collection| collection.unread_total())
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(UnreadTotalResponse { total_unread })
+}
+
 #[post("/set_feed_info")]
 pub async fn set_feed_info(
     request: web::Json<SetFeedInfoRequestAndResponse>,
     auth: Authenticated,
     collections: web::Data<RssCollections>,
+    limits: web::Data<UserLimits>,
 ) -> impl Responder {
+    if let Err(message) = limits.check_tags(&request.info.tags) {
+        return HttpResponse::Forbidden().body(message);
+    }
+
     {
         let mut collections = collections.write().unwrap();
-        if let Some(collection) = collections.get_mut(auth.user_id()) {
+        if let Some(collection) = collections
+            .get_mut(auth.user_id())
+            .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        {
             if let Some(feed) = collection.get_mut(&request.feed_url) {
-                feed.info = request.info.clone();
+                feed.info = FeedInfo {
+                    // Only the server knows when the feed was last downloaded, and what it recommends.
+                    update_status: feed.info.update_status.clone(),
+                    last_successful_update: feed.info.last_successful_update,
+                    recommended_refresh_interval_minutes: feed
+                        .info
+                        .recommended_refresh_interval_minutes,
+                    last_parse_diagnostics: feed.info.last_parse_diagnostics.clone(),
+                    description: feed.info.description.clone(),
+                    language: feed.info.language.clone(),
+                    icon_url: feed.info.icon_url.clone(),
+                    ..request.info.clone()
+                };
+                // So changed mute keywords apply right away, instead of after the next update.
+                feed.mute_entries();
             } else {
                 // Feed does not exist for this user.
                 return HttpResponse::Unauthorized().finish();
             }
         } else {
-            // A collection does not exist for this user.
+            // The collection does not exist for this user.
             return HttpResponse::Unauthorized().finish();
         };
     }
@@ -397,16 +1602,250 @@ pub async fn set_feed_info(
     HttpResponse::Ok().json(request.into_inner())
 }
 
+/// All entries of a single feed, for archiving it. Sent as a file download.
+#[post("/export_feed")]
+pub async fn export_feed(
+    request: web::Json<ExportFeedRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let collections = collections.read().unwrap();
+    let Some(collection) = collections
+        .get(auth.user_id())
+        .and_then(|user_collections| user_collections.get(&request.collection_id))
+    else {
+        // The collection does not exist for this user.
+        return HttpResponse::Unauthorized().finish();
+    };
+    let Some(feed) = collection.get(&request.url) else {
+        // Feed does not exist for this user.
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    info!("User `{}` exports feed `{}`", auth.user_name(), request.url);
+
+    let (entries, _, _): (ExportFeedResponse, _, _) = collection
+        .get_sorted_com_entries_with_filter(
+            usize::MAX,
+            FeedsFilter::Single(request.url.clone()),
+            EntryTypeFilter::All,
+            None,
+            false,
+            None,
+            SortOrder::Published,
+        );
+
+    HttpResponse::Ok()
+        .insert_header(ContentDisposition::attachment(export_file_name(
+            &feed.info.name,
+        )))
+        .json(entries)
+}
+
+/// Restores the read state of the entries of a feed, for example from an earlier export.
+/// Entries that don't exist in the feed are ignored.
+#[post("/import_feed")]
+pub async fn import_feed(
+    request: web::Json<ImportFeedRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let mut collections = collections.write().unwrap();
+    let maybe_response = collections
+        .get_mut(auth.user_id())
+        .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        .and_then(|collection| collection.import_read_state(&request.url, &request.entries));
+
+    match maybe_response {
+        Some(response) => {
+            info!(
+                "User `{}` imported the read state of {} entries of feed `{}`",
+                auth.user_name(),
+                response.matched,
+                request.url
+            );
+            HttpResponse::Ok().json(response)
+        }
+        // The collection or the feed does not exist for this user.
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+/// Removes a feed that is a duplicate of another one, keeping the read state of its entries.
+#[post("/merge_feeds")]
+pub async fn merge_feeds(
+    request: web::Json<MergeFeedsRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let mut collections = collections.write().unwrap();
+    let maybe_response = collections
+        .get_mut(auth.user_id())
+        .and_then(|user_collections| user_collections.get_mut(&request.collection_id))
+        .and_then(|collection| collection.merge_feeds(&request.keep, &request.merge));
+
+    match maybe_response {
+        Some(response) => {
+            info!(
+                "User `{}` merged feed `{}` into `{}`, carrying over the state of {} entries",
+                auth.user_name(),
+                request.merge,
+                request.keep,
+                response.carried_over
+            );
+            HttpResponse::Ok().json(response)
+        }
+        // The collection or one of the feeds does not exist for this user.
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+/// A file name based on the feed name, with only characters that are safe in all file systems.
+fn export_file_name(feed_name: &str) -> String {
+    let name: String = feed_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.is_empty() {
+        "feed.json".to_string()
+    } else {
+        format!("{}.json", name)
+    }
+}
+
+/// Lists the collections of the user. The default collection is always included.
+#[post("/collections")]
+pub async fn get_collections(
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let collections = collections.read().unwrap();
+    let infos: CollectionsResponse = collections
+        .get(auth.user_id())
+        .map(|user_collections| user_collections.infos())
+        .unwrap_or_else(|| UserCollections::default().infos());
+
+    HttpResponse::Ok().json(infos)
+}
+
+/// Creates a new, empty, collection.
+#[post("/create_collection")]
+pub async fn create_collection(
+    request: web::Json<CreateCollectionRequest>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().body("A collection needs a name");
+    }
+
+    info!("User `{}` creates collection `{}`", auth.user_name(), name);
+
+    let mut collections = collections.write().unwrap();
+    let user_collections = collections.entry(*auth.user_id()).or_default();
+    let id = user_collections.create(name.to_string());
+
+    HttpResponse::Ok().json(CollectionInfo {
+        id,
+        name: name.to_string(),
+    })
+}
+
+/// Removes a collection, including all of its feeds.
+/// The default collection can not be removed.
+#[post("/delete_collection")]
+pub async fn delete_collection(
+    request: web::Json<DeleteCollectionRequestAndResponse>,
+    auth: Authenticated,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    if request.id == CollectionId::default() {
+        return HttpResponse::BadRequest().body("The default collection can not be deleted");
+    }
+
+    {
+        let mut collections = collections.write().unwrap();
+        let removed = collections
+            .get_mut(auth.user_id())
+            .and_then(|user_collections| user_collections.remove(&request.id));
+        if removed.is_none() {
+            // The collection does not exist for this user.
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    info!(
+        "User `{}` deleted collection {}",
+        auth.user_name(),
+        request.id.0
+    );
+
+    // Send the request straight back to the client, so it doesn't need to remember all the
+    // things it has requested from the server.
+    HttpResponse::Ok().json(request.into_inner())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::rss_collection::{RssCollection, RssFeed};
+    use crate::rss_collection::{
+        export_file_name, feeds_etag, is_not_modified, parse_url_list, preview_entries,
+        LegacyRssCollection, LegacyRssCollections, RssCollection, RssFeed, UserCollections,
+        UserLimits, FEED_PREVIEW_ENTRIES,
+    };
     use crate::users::UserId;
     use crate::RssCollections;
+    use actix_web::http::header::IF_NONE_MATCH;
+    use actix_web::test::TestRequest;
+    use chrono::{DateTime, TimeZone, Utc};
     use pretty_assertions::assert_eq;
     use ron::ser::{to_string_pretty, PrettyConfig};
-    use rss_com_lib::rss_feed::{EntryKey, FeedEntries, FeedEntry, FeedInfo};
+    use rss_com_lib::message_body::{
+        AdditionalAction, CollectionId, CollectionInfo, ComFeedEntry, EntryTypeFilter, FeedsFilter,
+        FeedsRequest, ImportFeedResponse, ImportedEntry, MarkAllReadRequest, MarkAllReadResponse,
+        MergeFeedsResponse, PreviewEntry, SortOrder,
+    };
+    use rss_com_lib::rss_feed::{
+        EntryKey, FeedEntries, FeedEntry, FeedInfo, UpdateStatus, ENTRY_KEY_SCHEME_VERSION,
+    };
     use rss_com_lib::Url;
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    /// An entry with a link based on the title, so the title alone determines its key.
+    fn entry(title: &str, read: bool) -> FeedEntry {
+        FeedEntry {
+            title: title.to_string(),
+            link: Some(Url::new(format!("https://example.com/{title}"))),
+            pub_date: Default::default(),
+            read,
+            read_later: false,
+            guid: None,
+            updated: None,
+        }
+    }
+
+    fn feed(entries: Vec<FeedEntry>) -> RssFeed {
+        RssFeed::new(
+            FeedInfo::default(),
+            FeedEntries::new(
+                entries
+                    .into_iter()
+                    .map(|entry| (EntryKey::from_entry(&entry), entry))
+                    .collect(),
+            ),
+        )
+    }
 
     #[test]
     fn test_serialize_ron_rss_collections_empty() {
@@ -425,13 +1864,92 @@ mod tests {
         {
             let mut lock = collections.write().unwrap();
 
-            let collection = RssCollection::default();
-            lock.insert(UserId(0), collection);
+            let mut user_collections = UserCollections::default();
+            user_collections.insert(CollectionId::default(), RssCollection::default());
+            lock.insert(UserId(0), user_collections);
         }
 
         assert!(to_string_pretty(&collections, PrettyConfig::default()).is_ok());
     }
 
+    #[test]
+    fn test_legacy_collections_end_up_in_default_collection() {
+        // Given
+        let mut legacy_collection = LegacyRssCollection::default();
+        legacy_collection.0.insert(
+            Url::new("https://example.com/feed".to_string()),
+            RssFeed::default(),
+        );
+        let legacy = LegacyRssCollections::default();
+        legacy
+            .0
+            .write()
+            .unwrap()
+            .insert(UserId(0), legacy_collection);
+
+        // When
+        let collections = RssCollections::from(legacy);
+
+        // Then
+        let collections = collections.read().unwrap();
+        let user_collections = collections.get(&UserId(0)).unwrap();
+        assert_eq!(
+            user_collections.infos(),
+            vec![CollectionInfo {
+                id: CollectionId::default(),
+                name: "Default".to_string(),
+            }]
+        );
+        let default_collection = user_collections.get(&CollectionId::default()).unwrap();
+        assert!(default_collection.contains_key(&Url::new("https://example.com/feed".to_string())));
+    }
+
+    #[test]
+    fn test_create_collection() {
+        // Given
+        let mut user_collections = UserCollections::default();
+
+        // When
+        let first = user_collections.create("Comics".to_string());
+        let second = user_collections.create("News".to_string());
+
+        // Then
+        assert_eq!(first, CollectionId(1));
+        assert_eq!(second, CollectionId(2));
+        // The default collection is always listed, even before it has any feeds.
+        assert_eq!(
+            user_collections.infos(),
+            vec![
+                CollectionInfo {
+                    id: CollectionId::default(),
+                    name: "Default".to_string(),
+                },
+                CollectionInfo {
+                    id: CollectionId(1),
+                    name: "Comics".to_string(),
+                },
+                CollectionInfo {
+                    id: CollectionId(2),
+                    name: "News".to_string(),
+                },
+            ]
+        );
+        // Only the default collection is created when it is asked for.
+        assert!(user_collections.get_or_create(CollectionId(3)).is_none());
+        assert!(user_collections
+            .get_or_create(CollectionId::default())
+            .is_some());
+    }
+
+    #[test]
+    fn test_export_file_name_only_contains_safe_characters() {
+        assert_eq!(
+            export_file_name("My Comic: part 2/3"),
+            "My_Comic__part_2_3.json"
+        );
+        assert_eq!(export_file_name("  "), "feed.json");
+    }
+
     #[test]
     fn test_updating_feed_leaves_existing_entries_intact() {
         // Given
@@ -439,7 +1957,20 @@ mod tests {
             FeedInfo {
                 name: "Test".to_string(),
                 tags: Default::default(),
-                last_update_result: Ok(()),
+                home_url: None,
+                update_status: UpdateStatus::NeverUpdated,
+                last_successful_update: None,
+                refresh_interval_minutes: None,
+                recommended_refresh_interval_minutes: None,
+                last_parse_diagnostics: None,
+                sort_index: None,
+                enabled: true,
+                description: None,
+                language: None,
+                icon_url: None,
+                mark_read_after_days: None,
+                mute_keywords: Vec::new(),
+                highlight_keywords: Vec::new(),
             },
             Default::default(),
         );
@@ -449,6 +1980,9 @@ mod tests {
             link: Some(Url::new("same link".to_string())),
             pub_date: Default::default(),
             read: false,
+            read_later: false,
+            guid: None,
+            updated: None,
         };
         let key_1 = EntryKey::from_entry(&entry_1);
 
@@ -459,6 +1993,9 @@ mod tests {
             link: Some(Url::new("same link".to_string())),
             pub_date: Default::default(),
             read: true,
+            read_later: false,
+            guid: None,
+            updated: None,
         };
         let key_2 = EntryKey::from_entry(&entry_2);
 
@@ -478,4 +2015,978 @@ mod tests {
         let expected_map = HashMap::<EntryKey, FeedEntry>::from([(key_1, entry_1)]);
         assert_eq!(feed.entries.inner(), expected_map);
     }
+
+    #[test]
+    fn test_entries_are_paginated_with_cursor() {
+        // Given
+        let mut entries = FeedEntries::default();
+        for day in 1..=5 {
+            let entry = FeedEntry {
+                title: format!("Entry {}", day),
+                link: None,
+                pub_date: Utc.with_ymd_and_hms(2022, 9, day, 0, 0, 0).unwrap(),
+                read: false,
+                read_later: false,
+                guid: None,
+                updated: None,
+            };
+            entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("feed".to_string()),
+            RssFeed::new(FeedInfo::default(), entries),
+        );
+
+        // When
+        let (first_page, first_total, first_cursor) = collection
+            .get_sorted_com_entries_with_filter(
+                2,
+                FeedsFilter::All,
+                EntryTypeFilter::All,
+                None,
+                false,
+                None,
+                SortOrder::Published,
+            );
+        let (second_page, second_total, second_cursor) = collection
+            .get_sorted_com_entries_with_filter(
+                10,
+                FeedsFilter::All,
+                EntryTypeFilter::All,
+                first_cursor.as_ref(),
+                false,
+                None,
+                SortOrder::Published,
+            );
+
+        // Then
+        let titles = |page: &Vec<ComFeedEntry>| {
+            page.iter()
+                .map(|entry| entry.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&first_page), vec!["Entry 5", "Entry 4"]);
+        assert_eq!(titles(&second_page), vec!["Entry 3", "Entry 2", "Entry 1"]);
+        // The total is always the total amount of entries, regardless of the cursor.
+        assert_eq!(first_total, 5);
+        assert_eq!(second_total, 5);
+        assert!(first_cursor.is_some());
+        // Everything has been sent, so there is no next page.
+        assert_eq!(second_cursor, None);
+    }
+
+    #[test]
+    fn test_entries_in_multiple_feeds_are_deduplicated() {
+        // Given
+        let entry = FeedEntry {
+            title: "Shared".to_string(),
+            link: Some(Url::new("https://example.com/shared".to_string())),
+            pub_date: Default::default(),
+            read: false,
+            read_later: false,
+            guid: None,
+            updated: None,
+        };
+        let key = EntryKey::from_entry(&entry);
+        let read_entry = FeedEntry {
+            read: true,
+            ..entry.clone()
+        };
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("main feed".to_string()),
+            RssFeed::new(
+                FeedInfo::default(),
+                FeedEntries::new(HashMap::from([(key.clone(), entry)])),
+            ),
+        );
+        collection.insert(
+            Url::new("category feed".to_string()),
+            RssFeed::new(
+                FeedInfo::default(),
+                FeedEntries::new(HashMap::from([(key, read_entry)])),
+            ),
+        );
+
+        // When
+        let (all, all_total, _) = collection.get_sorted_com_entries_with_filter(
+            10,
+            FeedsFilter::All,
+            EntryTypeFilter::All,
+            None,
+            true,
+            None,
+            SortOrder::Published,
+        );
+        let (unread, unread_total, _) = collection.get_sorted_com_entries_with_filter(
+            10,
+            FeedsFilter::All,
+            EntryTypeFilter::UnreadOnly,
+            None,
+            true,
+            None,
+            SortOrder::Published,
+        );
+
+        // Then
+        assert_eq!(all_total, 1);
+        assert_eq!(all.len(), 1);
+        // One of the copies is read, so the merged entry is read.
+        assert!(all[0].read);
+        // The copy from the feed with the lowest url is kept.
+        assert_eq!(all[0].feed_url, Url::new("category feed".to_string()));
+        // But it still shows where it came from.
+        assert_eq!(
+            all[0].sources,
+            vec![
+                Url::new("category feed".to_string()),
+                Url::new("main feed".to_string())
+            ]
+        );
+
+        assert_eq!(unread_total, 0);
+        assert!(unread.is_empty());
+    }
+
+    #[test]
+    fn test_zero_entries_still_gives_the_total() {
+        // Given
+        let mut entries = FeedEntries::default();
+        for day in 1..=3 {
+            let entry = FeedEntry {
+                title: format!("Entry {}", day),
+                link: None,
+                pub_date: Utc.with_ymd_and_hms(2022, 9, day, 0, 0, 0).unwrap(),
+                read: false,
+                read_later: false,
+                guid: None,
+                updated: None,
+            };
+            entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("feed".to_string()),
+            RssFeed::new(FeedInfo::default(), entries),
+        );
+
+        // When
+        let (entries, total, next_cursor) = collection.get_sorted_com_entries_with_filter(
+            0,
+            FeedsFilter::All,
+            EntryTypeFilter::All,
+            None,
+            false,
+            None,
+            SortOrder::Published,
+        );
+
+        // Then
+        assert!(entries.is_empty());
+        assert_eq!(total, 3);
+        assert_eq!(next_cursor, None);
+    }
+
+    #[test]
+    fn test_published_after_leaves_out_older_and_undated_entries() {
+        // Given
+        let mut entries = FeedEntries::default();
+        for (title, pub_date) in [
+            ("Old", Utc.with_ymd_and_hms(2022, 9, 1, 0, 0, 0).unwrap()),
+            ("New", Utc.with_ymd_and_hms(2022, 9, 3, 0, 0, 0).unwrap()),
+            // Entries without a date get the default date.
+            ("Undated", DateTime::UNIX_EPOCH),
+        ] {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date,
+                read: false,
+                read_later: false,
+                guid: None,
+                updated: None,
+            };
+            entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("feed".to_string()),
+            RssFeed::new(FeedInfo::default(), entries),
+        );
+
+        // When
+        let (entries, total, _) = collection.get_sorted_com_entries_with_filter(
+            10,
+            FeedsFilter::All,
+            EntryTypeFilter::All,
+            None,
+            false,
+            Some(Utc.with_ymd_and_hms(2022, 9, 2, 0, 0, 0).unwrap()),
+            SortOrder::Published,
+        );
+
+        // Then
+        assert_eq!(total, 1);
+        let titles: Vec<&str> = entries.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(titles, vec!["New"]);
+    }
+
+    #[test]
+    fn test_unchanged_response_is_not_sent_again() {
+        // Given
+        let request = |amount| FeedsRequest {
+            collection_id: CollectionId::default(),
+            filter: FeedsFilter::All,
+            entry_filter: EntryTypeFilter::UnreadOnly,
+            amount,
+            after: None,
+            dedupe: false,
+            published_after: None,
+            sort_order: SortOrder::Published,
+            additional_action: AdditionalAction::None,
+        };
+        let etag = feeds_etag(3, &UserId(1), &request(50));
+        let with_etag = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, etag.to_string()))
+            .to_http_request();
+
+        // Then
+        assert!(is_not_modified(
+            &with_etag,
+            &feeds_etag(3, &UserId(1), &request(50))
+        ));
+        // Something changed in the collections.
+        assert!(!is_not_modified(
+            &with_etag,
+            &feeds_etag(4, &UserId(1), &request(50))
+        ));
+        // Another request, or another user.
+        assert!(!is_not_modified(
+            &with_etag,
+            &feeds_etag(3, &UserId(1), &request(100))
+        ));
+        assert!(!is_not_modified(
+            &with_etag,
+            &feeds_etag(3, &UserId(2), &request(50))
+        ));
+        assert!(!is_not_modified(
+            &TestRequest::default().to_http_request(),
+            &etag
+        ));
+    }
+
+    #[test]
+    fn test_sorting_by_last_activity_puts_edited_entries_first() {
+        // Given
+        let mut entries = FeedEntries::default();
+        let edited_at = Utc.with_ymd_and_hms(2022, 9, 5, 0, 0, 0).unwrap();
+        for (title, pub_date, updated) in [
+            (
+                "Old, edited",
+                Utc.with_ymd_and_hms(2022, 9, 1, 0, 0, 0).unwrap(),
+                Some(edited_at),
+            ),
+            (
+                "Older, edited at the same time",
+                Utc.with_ymd_and_hms(2022, 8, 1, 0, 0, 0).unwrap(),
+                Some(edited_at),
+            ),
+            (
+                "New",
+                Utc.with_ymd_and_hms(2022, 9, 3, 0, 0, 0).unwrap(),
+                None,
+            ),
+        ] {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date,
+                read: false,
+                read_later: false,
+                guid: None,
+                updated,
+            };
+            entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("feed".to_string()),
+            RssFeed::new(FeedInfo::default(), entries),
+        );
+        let sorted_titles = |sort_order| {
+            let (entries, _, _) = collection.get_sorted_com_entries_with_filter(
+                10,
+                FeedsFilter::All,
+                EntryTypeFilter::All,
+                None,
+                false,
+                None,
+                sort_order,
+            );
+            entries
+                .into_iter()
+                .map(|entry| entry.title)
+                .collect::<Vec<_>>()
+        };
+
+        // When
+        let published = sorted_titles(SortOrder::Published);
+        let last_activity = sorted_titles(SortOrder::LastActivity);
+
+        // Then
+        assert_eq!(
+            published,
+            vec!["New", "Old, edited", "Older, edited at the same time"]
+        );
+        assert_eq!(
+            last_activity,
+            vec!["Old, edited", "Older, edited at the same time", "New"]
+        );
+    }
+
+    #[test]
+    fn test_read_later_entries_are_found_across_feeds() {
+        // Given
+        let later = entry("later", false);
+        let later_key = EntryKey::from_entry(&later);
+
+        let mut collection = RssCollection::default();
+        collection.insert(Url::new("first".to_string()), feed(vec![later]));
+        collection.insert(
+            Url::new("second".to_string()),
+            feed(vec![entry("other", false)]),
+        );
+
+        // When
+        assert!(collection.set_entry_read_later(&Url::new("first".to_string()), &later_key, true));
+        // Reading it doesn't take it off the list.
+        assert!(collection.set_entry_read(&Url::new("first".to_string()), &later_key, true));
+        let (entries, total, _) = collection.get_sorted_com_entries_with_filter(
+            10,
+            FeedsFilter::All,
+            EntryTypeFilter::ReadLater,
+            None,
+            false,
+            None,
+            SortOrder::Published,
+        );
+
+        // Then
+        assert_eq!(total, 1);
+        let titles: Vec<&str> = entries.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(titles, vec!["later"]);
+        assert!(entries[0].read_later);
+        assert!(!collection.set_entry_read_later(
+            &Url::new("second".to_string()),
+            &later_key,
+            false
+        ));
+    }
+
+    #[test]
+    fn test_mark_all_read_for_tag_can_be_undone() {
+        // Given
+        let unread = entry("unread", false);
+        let unread_key = EntryKey::from_entry(&unread);
+
+        let mut tagged = feed(vec![unread, entry("already read", true)]);
+        tagged.info.tags = HashSet::from(["news".to_string()]);
+        let mut collection = RssCollection::default();
+        collection.insert(Url::new("tagged".to_string()), tagged);
+        collection.insert(
+            Url::new("untagged".to_string()),
+            feed(vec![entry("other tag", false)]),
+        );
+
+        // When
+        let MarkAllReadResponse::Marked(marked) = collection.mark_all_read(&MarkAllReadRequest {
+            collection_id: CollectionId::default(),
+            filter: FeedsFilter::Tag("news".to_string()),
+            confirm: false,
+        }) else {
+            panic!("Only marking everything needs confirmation");
+        };
+
+        // Then
+        // Only the entry that was unread is returned, so only that one is undone.
+        let marked_keys: Vec<&EntryKey> = marked.iter().map(|entry| &entry.entry_key).collect();
+        assert_eq!(marked_keys, vec![&unread_key]);
+        assert_eq!(collection.unread_total(), 1);
+
+        // When
+        for entry in &marked {
+            assert!(collection.set_entry_read(&entry.feed_url, &entry.entry_key, false));
+        }
+
+        // Then
+        assert_eq!(collection.unread_total(), 2);
+    }
+
+    #[test]
+    fn test_mark_all_read_for_all_feeds_needs_confirmation() {
+        // Given
+        let mut collection = RssCollection::default();
+        for url in ["first", "second"] {
+            collection.insert(Url::new(url.to_string()), feed(vec![entry(url, false)]));
+        }
+        let mut request = MarkAllReadRequest {
+            collection_id: CollectionId::default(),
+            filter: FeedsFilter::All,
+            confirm: false,
+        };
+
+        // When
+        let response = collection.mark_all_read(&request);
+
+        // Then
+        assert!(matches!(
+            response,
+            MarkAllReadResponse::NeedsConfirmation { unread: 2 }
+        ));
+        assert_eq!(collection.unread_total(), 2);
+
+        // When
+        request.confirm = true;
+        let response = collection.mark_all_read(&request);
+
+        // Then
+        assert!(matches!(response, MarkAllReadResponse::Marked(marked) if marked.len() == 2));
+        assert_eq!(collection.unread_total(), 0);
+    }
+
+    #[test]
+    fn test_reset_read_keeps_the_read_later_list() {
+        // Given
+        let read_later = FeedEntry {
+            read_later: true,
+            ..entry("read later", true)
+        };
+        let read_later_key = EntryKey::from_entry(&read_later);
+        let mut collection = RssCollection::default();
+        let url = Url::new("feed".to_string());
+        collection.insert(
+            url.clone(),
+            feed(vec![
+                entry("read", true),
+                entry("unread", false),
+                read_later,
+            ]),
+        );
+
+        // When
+        let reset = collection.reset_read(&url);
+
+        // Then
+        assert_eq!(reset, Some(2));
+        assert_eq!(collection.unread_total(), 3);
+        assert!(collection.get(&url).unwrap().entries[&read_later_key].read_later);
+        assert_eq!(collection.reset_read(&Url::new("other".to_string())), None);
+    }
+
+    #[test]
+    fn test_totals_count_entries_in_multiple_feeds_once() {
+        // Given
+        let mut collection = RssCollection::default();
+        collection.insert(
+            Url::new("main feed".to_string()),
+            feed(vec![
+                entry("Shared unread", false),
+                entry("Shared read", false),
+                entry("Unread", false),
+                entry("Read", true),
+            ]),
+        );
+        collection.insert(
+            Url::new("category feed".to_string()),
+            feed(vec![
+                entry("Shared unread", false),
+                entry("Shared read", true),
+            ]),
+        );
+
+        // When
+        let total = collection.unread_total();
+        let entry_total = collection.entry_total();
+
+        // Then
+        assert_eq!(total, 2);
+        assert_eq!(entry_total, 4);
+    }
+
+    #[test]
+    fn test_importing_read_state_matches_entries_by_key() {
+        // Given
+        let main_url = Url::new("main feed".to_string());
+        let category_url = Url::new("category feed".to_string());
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            main_url.clone(),
+            feed(vec![entry("Shared", false), entry("Own", false)]),
+        );
+        collection.insert(category_url.clone(), feed(vec![entry("Shared", false)]));
+
+        let imported = |title: &str| ImportedEntry {
+            key: EntryKey::from_entry(&entry(title, false)),
+            read: true,
+        };
+
+        // When
+        let response = collection.import_read_state(
+            &main_url,
+            &[imported("Shared"), imported("Own"), imported("Gone")],
+        );
+
+        // Then
+        assert_eq!(
+            response,
+            Some(ImportFeedResponse {
+                matched: 2,
+                unmatched: 1,
+            })
+        );
+        assert_eq!(collection.unread_total(), 0);
+        assert_eq!(
+            collection.import_read_state(&Url::new("unknown feed".to_string()), &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merging_feeds_carries_over_state_by_key_or_link() {
+        // Given
+        let keep_url = Url::new("https://example.com/feed".to_string());
+        let merge_url = Url::new("https://example.com/feed/".to_string());
+
+        let mut collection = RssCollection::default();
+        collection.insert(
+            keep_url.clone(),
+            feed(vec![
+                entry("Same", false),
+                entry("Renamed", false),
+                entry("Unread", false),
+            ]),
+        );
+        collection.insert(
+            merge_url.clone(),
+            feed(vec![
+                entry("Same", true),
+                // Different title, so a different key, but the same link.
+                FeedEntry {
+                    link: entry("Renamed", false).link,
+                    read_later: true,
+                    ..entry("Old title", false)
+                },
+                entry("Unread", false),
+                entry("Gone", true),
+            ]),
+        );
+
+        // When
+        let response = collection.merge_feeds(&keep_url, &merge_url);
+
+        // Then
+        assert_eq!(response, Some(MergeFeedsResponse { carried_over: 2 }));
+        assert!(!collection.contains_key(&merge_url));
+        let kept = &collection.get(&keep_url).unwrap().entries;
+        let state = |title: &str| {
+            let kept_entry = &kept[&EntryKey::from_entry(&entry(title, false))];
+            (kept_entry.read, kept_entry.read_later)
+        };
+        assert_eq!(state("Same"), (true, false));
+        assert_eq!(state("Renamed"), (false, true));
+        assert_eq!(state("Unread"), (false, false));
+
+        assert_eq!(collection.merge_feeds(&keep_url, &keep_url), None);
+        assert_eq!(collection.merge_feeds(&keep_url, &merge_url), None);
+    }
+
+    #[test]
+    fn test_prune_removes_old_read_entries_but_keeps_unread_entries() {
+        // Given
+        let mut feed = RssFeed::default();
+        let mut insert = |title: &str, days_old: i64, read: bool| {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date: Utc::now() - chrono::Duration::days(days_old),
+                read,
+                read_later: false,
+                guid: None,
+                updated: None,
+            };
+            let key = EntryKey::from_entry(&entry);
+            feed.entries.insert(key.clone(), entry.clone());
+            (key, entry)
+        };
+
+        insert("Old read", 100, true);
+        insert("Old unread", 100, false);
+        insert("New read", 1, true);
+        let still_published = insert("Old read but still published", 100, true);
+
+        let latest_entries = FeedEntries::new(HashMap::from([still_published]));
+
+        // When
+        feed.prune(None, Some(chrono::Duration::days(30)), &latest_entries);
+
+        // Then
+        let mut titles: Vec<String> = feed
+            .entries
+            .values()
+            .map(|entry| entry.title.clone())
+            .collect();
+        titles.sort();
+        assert_eq!(
+            titles,
+            vec!["New read", "Old read but still published", "Old unread"]
+        );
+    }
+
+    #[test]
+    fn test_old_unread_entries_are_marked_read_except_for_read_later() {
+        // Given
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let mut feed = RssFeed::default();
+        feed.info.mark_read_after_days = Some(7);
+        for (title, minutes_old, read_later) in [
+            ("Just too old", 7 * 24 * 60 + 1, false),
+            ("Exactly old enough", 7 * 24 * 60, false),
+            ("Recent", 60, false),
+            ("Old but read later", 30 * 24 * 60, true),
+        ] {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date: now - chrono::Duration::minutes(minutes_old),
+                read: false,
+                read_later,
+                guid: None,
+                updated: None,
+            };
+            feed.entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        // When
+        feed.mark_old_entries_read(now);
+
+        // Then
+        let mut unread: Vec<String> = feed
+            .entries
+            .values()
+            .filter(|entry| !entry.read)
+            .map(|entry| entry.title.clone())
+            .collect();
+        unread.sort();
+        assert_eq!(
+            unread,
+            vec!["Exactly old enough", "Old but read later", "Recent"]
+        );
+    }
+
+    #[test]
+    fn test_entries_with_muted_keywords_are_marked_read_except_for_read_later() {
+        // Given
+        let mut feed = RssFeed::default();
+        feed.info.mute_keywords = vec!["giveaway".to_string()];
+        for (title, read_later) in [
+            ("Big GIVEAWAY this week", false),
+            ("Giveaway results", true),
+            ("Release notes", false),
+        ] {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date: Utc::now(),
+                read: false,
+                read_later,
+                guid: None,
+                updated: None,
+            };
+            feed.entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        // When
+        feed.mute_entries();
+
+        // Then
+        let mut unread: Vec<String> = feed
+            .entries
+            .values()
+            .filter(|entry| !entry.read)
+            .map(|entry| entry.title.clone())
+            .collect();
+        unread.sort();
+        assert_eq!(unread, vec!["Giveaway results", "Release notes"]);
+    }
+
+    #[test]
+    fn test_prune_removes_oldest_read_entries_above_max_amount() {
+        // Given
+        let mut feed = RssFeed::default();
+        for (title, days_old, read) in [
+            ("Oldest read", 5, true),
+            ("Oldest unread", 6, false),
+            ("Middle read", 3, true),
+            ("Newest read", 1, true),
+        ] {
+            let entry = FeedEntry {
+                title: title.to_string(),
+                link: None,
+                pub_date: Utc::now() - chrono::Duration::days(days_old),
+                read,
+                read_later: false,
+                guid: None,
+                updated: None,
+            };
+            feed.entries.insert(EntryKey::from_entry(&entry), entry);
+        }
+
+        // When
+        feed.prune(Some(2), None, &FeedEntries::default());
+
+        // Then
+        let mut titles: Vec<String> = feed
+            .entries
+            .values()
+            .map(|entry| entry.title.clone())
+            .collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Newest read", "Oldest unread"]);
+    }
+
+    #[test]
+    fn test_updating_feed_migrates_entries_to_guid_keys() {
+        // Given
+        let old_entry = FeedEntry {
+            title: "Title".to_string(),
+            link: Some(Url::new("link".to_string())),
+            pub_date: Default::default(),
+            read: true,
+            read_later: false,
+            guid: None,
+            updated: None,
+        };
+        let mut feed = RssFeed::default();
+        feed.entries
+            .insert(EntryKey::from_entry(&old_entry), old_entry.clone());
+
+        let new_entry = FeedEntry {
+            read: false,
+            guid: Some("guid".to_string()),
+            ..old_entry
+        };
+        let new_key = EntryKey::from_entry(&new_entry);
+        let update_entries = FeedEntries::new(HashMap::from([(new_key.clone(), new_entry)]));
+
+        // When
+        feed.update_entries(Ok(update_entries));
+
+        // Then
+        // The entry is now stored under the guid based key, and is still read.
+        assert_eq!(feed.entries.len(), 1);
+        let entry = feed
+            .entries
+            .get(&new_key)
+            .expect("Entry should be migrated");
+        assert!(entry.read);
+        assert_eq!(entry.guid, Some("guid".to_string()));
+    }
+
+    #[test]
+    fn test_entries_saved_without_guids_keep_their_read_state_once_the_feed_has_guids() {
+        // Given
+        let old_entry = entry("Title", true);
+        let old_key = EntryKey::from_entry(&old_entry);
+        // As loaded from a file saved before the key scheme version was introduced.
+        let mut feed = RssFeed {
+            key_scheme_version: 0,
+            ..feed(vec![old_entry.clone()])
+        };
+
+        // When
+        feed.migrate_entry_keys();
+
+        // Then
+        // Without a guid, the key stays the same.
+        assert_eq!(
+            feed.entries.clone().inner(),
+            HashMap::from([(old_key, old_entry.clone())])
+        );
+        assert_eq!(feed.key_scheme_version, ENTRY_KEY_SCHEME_VERSION);
+
+        // When
+        let new_entry = FeedEntry {
+            read: false,
+            guid: Some("guid".to_string()),
+            ..old_entry
+        };
+        let new_key = EntryKey::from_entry(&new_entry);
+        feed.update_entries(Ok(FeedEntries::new(HashMap::from([(
+            new_key.clone(),
+            new_entry,
+        )]))));
+
+        // Then
+        assert_eq!(feed.entries.len(), 1);
+        let entry = feed.entries.get(&new_key).expect("Entry should be moved");
+        assert!(entry.read);
+    }
+
+    #[test]
+    fn test_orphaned_users_are_the_ones_that_do_not_exist() {
+        // Given
+        let collections = RssCollections::default();
+        {
+            let mut lock = collections.write().unwrap();
+            for user in [3, 1, 2] {
+                lock.insert(UserId(user), UserCollections::default());
+            }
+        }
+
+        // When
+        let orphans = collections.orphaned_users(|user| *user == UserId(2));
+
+        // Then
+        assert_eq!(orphans, vec![UserId(1), UserId(3)]);
+    }
+
+    #[test]
+    fn test_user_limits_allow_up_to_the_maximum() {
+        // Given
+        let limits = UserLimits {
+            max_feeds_per_user: Some(2),
+            max_tags_per_feed: Some(1),
+        };
+        let no_limits = UserLimits::default();
+        let one_tag = HashSet::from(["a".to_string()]);
+        let two_tags = HashSet::from(["a".to_string(), "b".to_string()]);
+
+        // Then
+        assert!(limits.check_new_feed(1).is_ok());
+        assert!(limits.check_new_feed(2).is_err());
+        assert!(limits.check_tags(&one_tag).is_ok());
+        assert!(limits.check_tags(&two_tags).is_err());
+        assert!(no_limits.check_new_feed(usize::MAX).is_ok());
+        assert!(no_limits.check_tags(&two_tags).is_ok());
+    }
+
+    #[test]
+    fn test_url_list_skips_blank_lines_and_comments() {
+        // Given
+        let text = "# My feeds\nhttps://example.com/feed\n\n   \n  https://example.org/rss  \n#https://example.net/old\n";
+
+        // When
+        let urls = parse_url_list(text);
+
+        // Then
+        assert_eq!(
+            urls,
+            vec![
+                Url::new("https://example.com/feed".to_string()),
+                Url::new("https://example.org/rss".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preview_has_only_the_newest_entries() {
+        // Given
+        let entries: Vec<FeedEntry> = (0..(FEED_PREVIEW_ENTRIES as i64 + 2))
+            .map(|day| FeedEntry {
+                title: format!("Entry {}", day),
+                link: None,
+                pub_date: Utc.timestamp_opt(day * 86400, 0).unwrap(),
+                read: false,
+                read_later: false,
+                guid: None,
+                updated: None,
+            })
+            .collect();
+        let entries = FeedEntries::new(
+            entries
+                .into_iter()
+                .map(|entry| (EntryKey::from_entry(&entry), entry))
+                .collect(),
+        );
+
+        // When
+        let preview = preview_entries(&entries);
+
+        // Then
+        let titles: Vec<&str> = preview.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Entry 6", "Entry 5", "Entry 4", "Entry 3", "Entry 2"]
+        );
+        assert_eq!(
+            preview[0],
+            PreviewEntry {
+                title: "Entry 6".to_string(),
+                link: None,
+                pub_date: Utc.timestamp_opt(6 * 86400, 0).unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_serializing_does_not_deadlock_with_a_writer() {
+        // Given
+        let collections = Arc::new(RssCollections::default());
+        let (done_sender, done_receiver) = mpsc::channel();
+
+        // When
+        let writer_collections = collections.clone();
+        let writer = thread::spawn(move || {
+            for user in 0..1000 {
+                let mut lock = writer_collections.write().unwrap();
+                let mut user_collections = UserCollections::default();
+                user_collections.insert(CollectionId::default(), RssCollection::default());
+                lock.insert(UserId(user), user_collections);
+            }
+        });
+        let saver_collections = collections.clone();
+        let saver = thread::spawn(move || {
+            for _ in 0..100 {
+                // The same as the periodic saving task does.
+                saver_collections.version();
+                to_string_pretty(&*saver_collections, PrettyConfig::default()).unwrap();
+            }
+        });
+        thread::spawn(move || {
+            writer.join().unwrap();
+            saver.join().unwrap();
+            done_sender.send(()).unwrap();
+        });
+
+        // Then
+        assert!(done_receiver.recv_timeout(Duration::from_secs(30)).is_ok());
+        assert_eq!(collections.read().unwrap().len(), 1000);
+        assert_eq!(collections.version(), 1000);
+    }
+
+    #[test]
+    fn test_version_is_not_saved() {
+        // Given
+        let collections = RssCollections::default();
+        collections
+            .write()
+            .unwrap()
+            .insert(UserId(0), UserCollections::default());
+
+        // When
+        let serialized = to_string_pretty(&collections, PrettyConfig::default()).unwrap();
+        let loaded: RssCollections = ron::from_str(&serialized).unwrap();
+
+        // Then
+        assert_eq!(collections.version(), 1);
+        assert_eq!(loaded.version(), 0);
+        assert!(loaded.read().unwrap().contains_key(&UserId(0)));
+    }
 }
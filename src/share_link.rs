@@ -0,0 +1,273 @@
+//! Read-only links to a selection of feeds, for sharing them with people that don't have an
+//! account. The link contains the user, collection and [FeedsFilter], signed by the server so
+//! it can't be changed to show other feeds. It expires, and only shows the names and urls of
+//! the feeds as OPML. No entries or read state.
+
+use crate::rss_collection::RssCollections;
+use crate::users::UserId;
+use crate::Authenticated;
+use actix_web::http::header::ContentDisposition;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use rss_com_lib::message_body::{
+    CollectionId, CreateShareLinkRequest, CreateShareLinkResponse, FeedsFilter,
+};
+use rss_com_lib::rss_feed::FeedInfo;
+use rss_com_lib::Url;
+use serde::{Deserialize, Serialize};
+
+/// Links stop working after at most this many days, so a forgotten link doesn't stay valid forever.
+pub const MAX_SHARE_LINK_DAYS: u32 = 30;
+
+/// What is needed to create and check share links.
+pub struct ShareLinks {
+    /// [None] if share links are turned off.
+    key: Option<[u8; 32]>,
+    /// Where the server can be reached, including the route prefix.
+    /// For example `https://example.com/feedreader`.
+    base_url: String,
+}
+
+/// What a share link gives access to. Signed, so it can't be changed by the person that has the link.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ShareToken {
+    user: UserId,
+    collection_id: CollectionId,
+    filter: FeedsFilter,
+    expires: DateTime<Utc>,
+}
+
+impl ShareLinks {
+    pub fn new(enabled: bool, session_key: &[u8], base_url: String) -> Self {
+        ShareLinks {
+            // Derived instead of using the session key directly, so a share link signature
+            // can never be used for anything that the session key is used for.
+            key: enabled.then(|| blake3::derive_key("rss_r share links", session_key)),
+            base_url,
+        }
+    }
+
+    /// The token as `<hex json>.<hex signature>`, so it can be put in a url as is.
+    fn sign(key: &[u8; 32], token: &ShareToken) -> String {
+        let payload = serde_json::to_vec(token).unwrap();
+        let signature = blake3::keyed_hash(key, &payload);
+        format!("{}.{}", to_hex(&payload), signature.to_hex())
+    }
+
+    /// The token, if it was signed by this server and has not expired at `now`.
+    fn verify(&self, token: &str, now: DateTime<Utc>) -> Option<ShareToken> {
+        let key = self.key.as_ref()?;
+        let (payload, signature) = token.split_once('.')?;
+        let payload = from_hex(payload)?;
+        let signature = blake3::Hash::from_hex(signature).ok()?;
+        // Comparing blake3 hashes takes constant time, so this doesn't tell how much of
+        // a forged signature was right.
+        if blake3::keyed_hash(key, &payload) != signature {
+            return None;
+        }
+
+        let token: ShareToken = serde_json::from_slice(&payload).ok()?;
+        (token.expires > now).then_some(token)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// An OPML 2.0 document listing the `feeds`, that other feed readers can import.
+fn to_opml(title: &str, feeds: &[(&Url, &FeedInfo)]) -> String {
+    let mut opml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <opml version=\"2.0\">\n  \
+           <head>\n    \
+             <title>{}</title>\n  \
+           </head>\n  \
+           <body>\n",
+        escape_xml(title)
+    );
+    for (url, info) in feeds {
+        let name = escape_xml(&info.name);
+        let html_url = info
+            .home_url
+            .as_ref()
+            .map(|home_url| format!(" htmlUrl=\"{}\"", escape_xml(home_url.as_str())))
+            .unwrap_or_default();
+        opml.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{}\" title=\"{}\" xmlUrl=\"{}\"{}/>\n",
+            name,
+            name,
+            escape_xml(url.as_str()),
+            html_url
+        ));
+    }
+    opml.push_str("  </body>\n</opml>\n");
+    opml
+}
+
+#[post("/create_share_link")]
+pub async fn create_share_link(
+    request: web::Json<CreateShareLinkRequest>,
+    auth: Authenticated,
+    share_links: web::Data<ShareLinks>,
+) -> impl Responder {
+    let Some(key) = &share_links.key else {
+        return HttpResponse::Forbidden().body("Share links are turned off on this server");
+    };
+
+    let valid_days = request.valid_days.clamp(1, MAX_SHARE_LINK_DAYS);
+    let token = ShareToken {
+        user: *auth.user_id(),
+        collection_id: request.collection_id,
+        filter: request.filter.clone(),
+        expires: Utc::now() + Duration::days(valid_days.into()),
+    };
+    info!(
+        "User `{}` created a share link for {:?}, valid until {}",
+        auth.user_name(),
+        token.filter,
+        token.expires
+    );
+
+    HttpResponse::Ok().json(CreateShareLinkResponse {
+        url: format!(
+            "{}/shared/{}",
+            share_links.base_url,
+            ShareLinks::sign(key, &token)
+        ),
+        expires: token.expires,
+    })
+}
+
+/// Does not require authentication, the signed token is the permission.
+/// Gives a 404 for invalid and expired tokens, and when share links are turned off.
+#[get("/shared/{token}")]
+pub async fn get_shared_feeds(
+    token: web::Path<String>,
+    share_links: web::Data<ShareLinks>,
+    collections: web::Data<RssCollections>,
+) -> impl Responder {
+    let Some(token) = share_links.verify(&token, Utc::now()) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let collections = collections.read().unwrap();
+    let Some(collection) = collections
+        .get(&token.user)
+        .and_then(|user_collections| user_collections.get(&token.collection_id))
+    else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let mut feeds: Vec<(&Url, &FeedInfo)> = collection
+        .iter()
+        .map(|(url, feed)| (url, feed.info()))
+        .filter(|(url, info)| match &token.filter {
+            FeedsFilter::All => true,
+            FeedsFilter::Tag(tag) => info.tags.contains(tag),
+            FeedsFilter::Single(single) => single == *url,
+        })
+        .collect();
+    feeds.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+    let title = match &token.filter {
+        FeedsFilter::Tag(tag) => format!("Feeds tagged {}", tag),
+        _ => "Feeds".to_string(),
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/x-opml; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("feeds.opml"))
+        .body(to_opml(&title, &feeds))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::share_link::{escape_xml, to_hex, ShareLinks, ShareToken};
+    use crate::users::UserId;
+    use chrono::{Duration, Utc};
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::{CollectionId, FeedsFilter};
+
+    fn share_links() -> ShareLinks {
+        ShareLinks::new(true, &[7; 64], "https://example.com".to_string())
+    }
+
+    fn token() -> ShareToken {
+        ShareToken {
+            user: UserId(1),
+            collection_id: CollectionId(2),
+            filter: FeedsFilter::Tag("news".to_string()),
+            expires: Utc::now() + Duration::days(1),
+        }
+    }
+
+    #[test]
+    fn test_signed_token_is_accepted_until_it_expires() {
+        // Given
+        let share_links = share_links();
+        let token = token();
+
+        // When
+        let signed = ShareLinks::sign(share_links.key.as_ref().unwrap(), &token);
+
+        // Then
+        assert_eq!(share_links.verify(&signed, Utc::now()), Some(token.clone()));
+        assert_eq!(
+            share_links.verify(&signed, Utc::now() + Duration::days(2)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_changed_or_foreign_tokens_are_rejected() {
+        // Given
+        let share_links = share_links();
+        let signed = ShareLinks::sign(share_links.key.as_ref().unwrap(), &token());
+        let (_, signature) = signed.split_once('.').unwrap();
+        let other_filter = ShareToken {
+            filter: FeedsFilter::All,
+            ..token()
+        };
+        let changed = format!(
+            "{}.{}",
+            to_hex(&serde_json::to_vec(&other_filter).unwrap()),
+            signature
+        );
+        let other_server = ShareLinks::new(true, &[8; 64], "https://example.com".to_string());
+        let disabled = ShareLinks::new(false, &[7; 64], "https://example.com".to_string());
+
+        // Then
+        assert_eq!(share_links.verify(&changed, Utc::now()), None);
+        assert_eq!(share_links.verify("not a token", Utc::now()), None);
+        assert_eq!(other_server.verify(&signed, Utc::now()), None);
+        assert_eq!(disabled.verify(&signed, Utc::now()), None);
+    }
+
+    #[test]
+    fn test_feed_names_are_escaped_in_the_opml() {
+        assert_eq!(
+            escape_xml(r#"Tom & Jerry's <"news">"#),
+            "Tom &amp; Jerry&apos;s &lt;&quot;news&quot;&gt;"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Progress of the "Update all feeds" requests that are running, so the client can show how far
+//! along a long update is while it waits for the response.
+
+use crate::users::UserId;
+use crate::Authenticated;
+use actix_web::{post, web, HttpResponse, Responder};
+use rss_com_lib::message_body::{UpdateProgress, UpdateStatusResponse};
+use rss_com_lib::Url;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Only kept in memory. A user has an entry only while an update of theirs is running.
+#[derive(Default, Debug)]
+pub struct UpdateStatuses(RwLock<HashMap<UserId, UpdateProgress>>);
+
+impl UpdateStatuses {
+    pub fn start(&self, user: UserId, total: usize) {
+        self.0.write().unwrap().insert(
+            user,
+            UpdateProgress {
+                completed: 0,
+                total,
+                currently_failing: Vec::new(),
+            },
+        );
+    }
+
+    /// Called for every feed when its download is done.
+    pub fn record(&self, user: UserId, url: &Url, success: bool) {
+        if let Some(progress) = self.0.write().unwrap().get_mut(&user) {
+            progress.completed += 1;
+            if !success {
+                progress.currently_failing.push(url.clone());
+            }
+        }
+    }
+
+    pub fn finish(&self, user: UserId) {
+        self.0.write().unwrap().remove(&user);
+    }
+
+    fn get(&self, user: UserId) -> UpdateStatusResponse {
+        self.0.read().unwrap().get(&user).cloned()
+    }
+}
+
+#[post("/update_status")]
+pub async fn get_update_status(
+    auth: Authenticated,
+    statuses: web::Data<UpdateStatuses>,
+) -> impl Responder {
+    HttpResponse::Ok().json(statuses.get(*auth.user_id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::update_status::UpdateStatuses;
+    use crate::users::UserId;
+    use pretty_assertions::assert_eq;
+    use rss_com_lib::message_body::UpdateProgress;
+    use rss_com_lib::Url;
+
+    #[test]
+    fn test_progress_is_only_available_while_updating() {
+        // Given
+        let statuses = UpdateStatuses::default();
+        let user = UserId(1);
+        let ok_url = Url::new("https://example.com/ok".to_string());
+        let failing_url = Url::new("https://example.com/failing".to_string());
+
+        // When
+        statuses.start(user, 3);
+        statuses.record(user, &ok_url, true);
+        statuses.record(user, &failing_url, false);
+
+        // Then
+        assert_eq!(
+            statuses.get(user),
+            Some(UpdateProgress {
+                completed: 2,
+                total: 3,
+                currently_failing: vec![failing_url],
+            })
+        );
+        assert_eq!(statuses.get(UserId(2)), None);
+
+        // When
+        statuses.finish(user);
+
+        // Then
+        assert_eq!(statuses.get(user), None);
+    }
+}
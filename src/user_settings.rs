@@ -0,0 +1,63 @@
+use crate::users::UserId;
+use crate::{Authenticated, SaveInRonFile};
+use actix_web::{post, web, HttpResponse, Responder};
+use log::info;
+use rss_com_lib::message_body::UserSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// The [UserSettings] of all users.
+/// Users that never changed their settings don't have an entry, they use the default settings.
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct UserSettingsCollection(RwLock<HashMap<UserId, UserSettings>>);
+
+impl SaveInRonFile for UserSettingsCollection {
+    const FILE_NAME: &'static str = "user_settings.ron";
+}
+
+impl std::ops::Deref for UserSettingsCollection {
+    type Target = RwLock<HashMap<UserId, UserSettings>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for UserSettingsCollection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[post("/user_settings")]
+pub async fn get_user_settings(
+    auth: Authenticated,
+    settings: web::Data<UserSettingsCollection>,
+) -> impl Responder {
+    let settings = settings.read().unwrap();
+    let user_settings = settings.get(auth.user_id()).cloned().unwrap_or_default();
+
+    HttpResponse::Ok().json(user_settings)
+}
+
+#[post("/set_user_settings")]
+pub async fn set_user_settings(
+    request: web::Json<UserSettings>,
+    auth: Authenticated,
+    settings: web::Data<UserSettingsCollection>,
+) -> impl Responder {
+    info!("User `{}` changed their settings", auth.user_name());
+
+    {
+        let mut settings = settings.write().unwrap();
+        settings.insert(*auth.user_id(), request.clone());
+    }
+
+    // Settings change rarely, so they are saved right away instead of periodically.
+    settings.save();
+
+    // Send the request straight back to the client, so it doesn't need to remember all the
+    // things it has requested from the server.
+    HttpResponse::Ok().json(request.into_inner())
+}
@@ -23,6 +23,9 @@ pub struct UserInfo {
     // TODO (Wybe 2022-07-11): Encrypt password according to current best practices.
     //                         Maybe use Argon2, like in https://github.com/dimfeld/ergo/blob/deca6447c4cebdad4e4fa28317a8fcd9f8ed63f2/auth/password.rs
     pub password: String,
+    /// Admins can list and delete other users, see [crate::admin].
+    #[serde(default)]
+    pub is_admin: bool,
 }
 
 impl UserInfo {